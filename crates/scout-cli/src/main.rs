@@ -2,10 +2,13 @@ use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use tracing::{info, warn};
 
-use scout_crypto::keys::{DeviceKeys, KeyConfig};
-use scout_nav::{doctor as nav_doctor, gnss, nav, thermal::ThermalMonitor};
-use scout_proto::telemetry::{EventKind, TelemetryEvent};
-use scout_uplink::{doctor as uplink_doctor, Uplink};
+use scout_crypto::keys::{self as crypto_keys, DeviceKeys, KeyConfig, KeyId};
+use scout_crypto::keywrap::KdfParams;
+use scout_crypto::passphrase::{PassphraseConfig, PassphraseSource};
+use scout_nav::{doctor as nav_doctor, gnss, mission, nav, thermal::ThermalMonitor};
+use scout_proto::export;
+use scout_proto::telemetry::{self, Payload};
+use scout_uplink::{cert_pin::{self, PinConfig}, doctor as uplink_doctor, endpoint::{self, ClientAuth, EndpointConfig}, health::HealthConfig, spool, FrameConfig, Uplink};
 
 use scout_vision::{camera, Roi};
 #[cfg(feature = "vision-tflite")]
@@ -14,10 +17,13 @@ use scout_vision::power::{PowerConfig, PowerCtl, PowerMode};
 use scout_vision::tracker::{TrackingConfig, Tracker};
 
 use scout_fc::{FcConfig};
+use mavlink::common::MavMessage;
 use scout_fc::mav::{FcLink, BatteryStatus};
 use scout_fc::autodetect::{autodetect_fc, default_candidate_bauds, default_candidate_devs};
 use scout_fc::state::FcStatus;
 
+use scout_sim::{SimConfig, Simulator, TimelineKind};
+
 use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
 use tokio::sync::mpsc;
 use tokio::signal;
@@ -42,6 +48,47 @@ enum Command {
     Run,
     Vision { #[command(subcommand)] cmd: VisionCmd },
     Fc { #[command(subcommand)] cmd: FcCmd },
+    Mission { #[command(subcommand)] cmd: MissionCmd },
+    /// Fly nav.route against a simulated vehicle/autopilot and print the mission timeline.
+    Sim,
+    /// Inspect, decrypt and export a telemetry spool (e.g. after a flight without link).
+    Spool {
+        /// Spool directory (default: uplink.spool_dir)
+        #[arg(long)]
+        dir: Option<String>,
+        #[command(subcommand)]
+        cmd: SpoolCmd,
+    },
+    Uplink { #[command(subcommand)] cmd: UplinkCmd },
+}
+
+#[derive(Debug, Subcommand)]
+enum UplinkCmd {
+    /// Print the SPKI SHA-256 pins of a server (host:port) or a PEM certificate file.
+    Pin {
+        target: String,
+        /// SNI name to send when `target` is an address
+        #[arg(long)]
+        server_name: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SpoolCmd {
+    /// List spool files, oldest first, with size and write time.
+    Ls,
+    /// Totals, event counts by type and files that fail authentication.
+    Stats,
+    /// Decrypt and print events as JSONL (all files, or the given ones).
+    Decode { files: Vec<String> },
+    /// Decrypt all files and write their events as JSONL, CSV or GPX.
+    Export {
+        /// jsonl | csv | gpx (default: from --out extension)
+        #[arg(long)]
+        format: Option<String>,
+        #[arg(long)]
+        out: Option<String>,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -56,7 +103,61 @@ enum FcCmd {
 }
 
 #[derive(Debug, Subcommand)]
-enum KeysCmd { Init, Rotate }
+enum MissionCmd {
+    /// Convert a QGroundControl .plan, KML or GeoJSON file into a [nav] mission TOML.
+    Import {
+        file: String,
+        /// Output path; prints to stdout when omitted.
+        #[arg(long)]
+        out: Option<String>,
+        /// Corridor width for the imported route (default: nav.route.corridor_width_m).
+        #[arg(long)]
+        corridor_width_m: Option<f64>,
+    },
+    /// Write the configured home/route/zone as .plan, KML or GeoJSON.
+    Export {
+        /// plan | kml | geojson (default: from --out extension)
+        #[arg(long)]
+        format: Option<String>,
+        #[arg(long)]
+        out: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum KeysCmd {
+    Init,
+    /// Replace the device key; the old one stays in the keyring for the spool.
+    Rotate,
+    /// Current and previous keys, with the spool files sealed by each.
+    List,
+    /// Rewrap the current and previous keys under a new passphrase (and the
+    /// configured kdf_* cost). Prompts unless --new-env or --new-file is given;
+    /// update passphrase_source afterwards.
+    ChangePassphrase {
+        /// Environment variable holding the new passphrase
+        #[arg(long)]
+        new_env: Option<String>,
+        /// File (0600) whose first line is the new passphrase
+        #[arg(long)]
+        new_file: Option<String>,
+    },
+    /// Remove previous keys past crypto.keyring_keep / keyring_retire_days,
+    /// or one key by id. Keys still sealing spool files are kept unless --force.
+    Retire {
+        #[arg(long)]
+        key_id: Option<String>,
+        #[arg(long)]
+        force: bool,
+    },
+    /// Write a CSR for the uplink client certificate (CN = device id),
+    /// generating uplink.client_key_path if it does not exist.
+    Csr {
+        /// Output path; prints to stdout when omitted.
+        #[arg(long)]
+        out: Option<String>,
+    },
+}
 
 #[derive(Debug, serde::Deserialize)]
 struct Config {
@@ -72,17 +173,57 @@ struct Config {
     power: Option<PowerCfg>,
 
     fc: Option<FcConfig>,
+
+    sim: Option<SimConfig>,
 }
 
 #[derive(Debug, serde::Deserialize)]
-struct CryptoCfg { key_path: String, passphrase: Option<String> }
+struct CryptoCfg {
+    key_path: String,
+    /// passphrase_source and friends; see `PassphraseConfig`
+    #[serde(flatten)]
+    passphrase: PassphraseConfig,
+    /// Previous keys kept for opening older data. Default 4
+    keyring_keep: Option<usize>,
+    /// Days a previous key is kept after rotation. Default 30
+    keyring_retire_days: Option<u64>,
+    /// Argon2id cost for key files written by keys init/rotate/change-passphrase.
+    /// Defaults 19456 KiB, 2 iterations, 1 lane
+    kdf_memory_kib: Option<u32>,
+    kdf_iterations: Option<u32>,
+    kdf_parallelism: Option<u32>,
+    /// mlock key memory and keep it out of core dumps (Linux). Default false
+    lock_memory: Option<bool>,
+}
 
 #[derive(Debug, serde::Deserialize)]
 struct UplinkCfg {
     enable: bool,
-    endpoint: String,
+    /// Single endpoint, pinned by `pinned_server_spki_sha256`; tried before `endpoints`
+    endpoint: Option<String>,
+    /// Endpoints in order of failover (`[[uplink.endpoints]]`)
+    #[serde(default)]
+    endpoints: Vec<EndpointConfig>,
+    /// Device id stamped on every telemetry envelope (default: hostname)
+    device_id: Option<String>,
+    /// Encoding, compression and batching of telemetry frames
+    #[serde(flatten)]
+    frames: FrameConfig,
+    /// Link quality thresholds and modem metrics (`[uplink.health]`)
+    #[serde(default)]
+    health: HealthConfig,
     pinned_server_spki_sha256: Option<String>,
+    /// Further pins for `endpoint` (backups, with optional expiry)
+    #[serde(default)]
+    pins: Vec<PinConfig>,
+    /// PEM client certificate chain for mutual TLS
+    client_cert_path: Option<String>,
+    /// PEM private key for `client_cert_path` (mode 0600)
+    client_key_path: Option<String>,
     spool_dir: String,
+    /// Sealed blob sequence numbers; outside the spool so clearing it cannot
+    /// rewind them (default: `<spool_dir>.seq`)
+    sequence_path: Option<String>,
     spool_max_mb: u64,
 }
 
@@ -160,9 +301,46 @@ struct PowerCfg {
     idle_to_scan_seconds: f32,
 }
 
+fn new_uplink(cfg: &Config, keys: DeviceKeys) -> Result<Uplink> {
+    Uplink::new(
+        uplink_endpoints(&cfg.uplink),
+        client_auth(&cfg.uplink)?,
+        cfg.uplink.spool_dir.clone(),
+        cfg.uplink.sequence_path.clone().unwrap_or_else(|| format!("{}.seq", cfg.uplink.spool_dir.trim_end_matches('/'))).into(),
+        cfg.uplink.spool_max_mb,
+        keys,
+        device_id(cfg),
+        cfg.uplink.frames.clone(),
+        cfg.uplink.health.clone(),
+    )
+}
+
+/// Key locations and keyring policy with the passphrase resolved from its
+/// configured source.
+fn key_config(cfg: &Config) -> Result<KeyConfig> {
+    let mut k = key_paths(cfg);
+    k.passphrase = cfg.crypto.passphrase.resolve(&k.key_path, false)?;
+    Ok(k)
+}
+
+/// Key locations and keyring policy, without the passphrase.
+fn key_paths(cfg: &Config) -> KeyConfig {
+    KeyConfig {
+        key_path: cfg.crypto.key_path.clone(),
+        passphrase: Default::default(),
+        keep_previous: cfg.crypto.keyring_keep.unwrap_or(crypto_keys::DEFAULT_KEEP_PREVIOUS),
+        retire_after_days: cfg.crypto.keyring_retire_days.unwrap_or(crypto_keys::DEFAULT_RETIRE_DAYS),
+        kdf: KdfParams {
+            memory_kib: cfg.crypto.kdf_memory_kib.unwrap_or(KdfParams::default().memory_kib),
+            iterations: cfg.crypto.kdf_iterations.unwrap_or(KdfParams::default().iterations),
+            parallelism: cfg.crypto.kdf_parallelism.unwrap_or(KdfParams::default().parallelism),
+        },
+    }
+}
+
 fn load_config(path: &str) -> Result<Config> {
     let s = std::fs::read_to_string(path).context("read config")?;
-    Ok(toml::from_str(&s).context("parse config toml")?)
+    toml::from_str(&s).context("parse config toml")
 }
 
 #[tokio::main]
@@ -173,6 +351,11 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
     let cfg = load_config(&cli.config)?;
+    if cfg.crypto.lock_memory.unwrap_or(false) {
+        if let Err(e) = scout_crypto::memory::enable() {
+            warn!("crypto: key memory not locked: {:#}", e);
+        }
+    }
 
     // FC status is shared (even for subcommands)
     let fc_status = Arc::new(Mutex::new(FcStatus::default()));
@@ -183,6 +366,10 @@ async fn main() -> Result<()> {
         Command::Run => run(&cfg, fc_status).await?,
         Command::Vision { cmd } => vision_cmd(&cfg, cmd).await?,
         Command::Fc { cmd } => fc_cmd(&cfg, cmd, fc_status).await?,
+        Command::Mission { cmd } => mission_cmd(&cfg, cmd)?,
+        Command::Sim => sim(&cfg).await?,
+        Command::Spool { dir, cmd } => spool_cmd(&cfg, dir, cmd)?,
+        Command::Uplink { cmd } => uplink_cmd(cmd).await?,
     }
     Ok(())
 }
@@ -190,18 +377,27 @@ async fn main() -> Result<()> {
 async fn doctor(cfg: &Config) -> Result<()> {
     info!("doctor: starting");
 
-    let kcfg = KeyConfig {
-        key_path: cfg.crypto.key_path.clone(),
-        passphrase: cfg.crypto.passphrase.clone().unwrap_or_default(),
-    };
-    scout_crypto::doctor::check_keys(&kcfg).or_else(|e| {
+    scout_crypto::doctor::check_keys(&key_paths(cfg)).or_else(|e| {
         warn!("keys missing or weak perms: {:#}", e);
         Ok::<(), anyhow::Error>(())
     })?;
+    cfg.crypto.passphrase.check().or_else(|e| {
+        warn!("key passphrase: {:#}", e);
+        Ok::<(), anyhow::Error>(())
+    })?;
 
     nav_doctor::check_geofence(&cfg.nav.home, &cfg.nav.route, &cfg.nav.zone, cfg.nav.max_radius_m)?;
     nav_doctor::check_gnss_thresholds(cfg.gnss.min_sats, cfg.gnss.max_hdop, cfg.gnss.max_fix_age_s)?;
     uplink_doctor::check_spool(&cfg.uplink.spool_dir, cfg.uplink.spool_max_mb)?;
+    if let Some(key) = &cfg.uplink.client_key_path {
+        scout_crypto::doctor::check_key_file("uplink.client_key_path", key).or_else(|e| {
+            warn!("client key missing or weak perms: {:#}", e);
+            Ok::<(), anyhow::Error>(())
+        })?;
+    }
+    if let Some(cert) = &cfg.uplink.client_cert_path {
+        anyhow::ensure!(std::path::Path::new(cert).exists(), "uplink.client_cert_path missing: {}", cert);
+    }
 
     if let Some(fc) = &cfg.fc {
         if fc.enable {
@@ -219,13 +415,124 @@ async fn doctor(cfg: &Config) -> Result<()> {
 }
 
 async fn keys(cfg: &Config, cmd: KeysCmd) -> Result<()> {
-    let kcfg = KeyConfig {
-        key_path: cfg.crypto.key_path.clone(),
-        passphrase: cfg.crypto.passphrase.clone().unwrap_or_default(),
+    let kcfg = match cmd {
+        KeysCmd::Csr { .. } => key_paths(cfg),
+        KeysCmd::Init => {
+            let mut k = key_paths(cfg);
+            k.passphrase = cfg.crypto.passphrase.resolve(&k.key_path, true)?;
+            k
+        }
+        _ => key_config(cfg)?,
     };
     match cmd {
         KeysCmd::Init => { DeviceKeys::init(&kcfg)?; info!("keys: initialized"); }
-        KeysCmd::Rotate => { DeviceKeys::rotate(&kcfg)?; info!("keys: rotated"); }
+        KeysCmd::Rotate => {
+            let id = DeviceKeys::rotate(&kcfg)?;
+            info!("keys: rotated, current key {}", id);
+            if cfg.uplink.enable {
+                // Spooled under the new key; delivered by the next `scout run`.
+                // The key has rotated either way, so a bad uplink config only warns
+                let spooled = async {
+                    let mut u = new_uplink(cfg, DeviceKeys::load(&kcfg)?)?;
+                    u.send(Payload::KeyRotated(telemetry::KeyRotated { key_id: id.to_string() })).await?;
+                    u.seal_batch().await
+                }.await;
+                if let Err(e) = spooled {
+                    warn!("keys: rotated to {}, but the KeyRotated event was not spooled: {:#}", id, e);
+                }
+            }
+            retire_keys(cfg, &kcfg)?;
+        }
+        KeysCmd::List => {
+            let keys = DeviceKeys::load(&kcfg)?;
+            let sealed = spool_key_ids(cfg, &keys)?;
+            let count = |id: &KeyId| sealed.get(&Some(*id)).copied().unwrap_or(0);
+            println!("{:<8}  {:<8}  {:<20}  {:<20}  spooled", "key id", "status", "retired", "retire after");
+            println!("{:<8}  {:<8}  {:<20}  {:<20}  {}", keys.key_id, "current", "-", "-", count(&keys.key_id));
+            for (i, k) in keys.previous.iter().enumerate() {
+                let after = k.retired_unix + kcfg.retire_after_days * 86400;
+                let after = if i < kcfg.keep_previous { export::rfc3339(after as i64 * 1000) } else { "now".into() };
+                println!("{:<8}  {:<8}  {:<20}  {:<20}  {}", k.id, "previous", export::rfc3339(k.retired_unix as i64 * 1000), after, count(&k.id));
+            }
+            if let Some(n) = sealed.get(&None) {
+                println!("{} spool files sealed with no known key", n);
+            }
+        }
+        KeysCmd::ChangePassphrase { new_env, new_file } => {
+            let new = PassphraseConfig {
+                passphrase_source: Some(match (&new_env, &new_file) {
+                    (Some(_), Some(_)) => anyhow::bail!("--new-env and --new-file are exclusive"),
+                    (Some(_), None) => PassphraseSource::Env,
+                    (None, Some(_)) => PassphraseSource::File,
+                    (None, None) => PassphraseSource::Prompt,
+                }),
+                passphrase_env: new_env,
+                passphrase_file: new_file,
+                ..Default::default()
+            };
+            DeviceKeys::change_passphrase(&kcfg, &new.resolve(&format!("{} (new)", kcfg.key_path), true)?)?;
+            info!("keys: passphrase changed; point crypto.passphrase_source at the new one");
+        }
+        KeysCmd::Retire { key_id: None, .. } => retire_keys(cfg, &kcfg)?,
+        KeysCmd::Retire { key_id: Some(id), force } => {
+            let id = KeyId::parse(&id)?;
+            let keys = DeviceKeys::load(&kcfg)?;
+            let n = spool_key_ids(cfg, &keys)?.get(&Some(id)).copied().unwrap_or(0);
+            anyhow::ensure!(n == 0 || force, "key {} still seals {} spool files; deliver them first or use --force", id, n);
+            DeviceKeys::retire_key(&kcfg, id)?;
+            info!("keys: retired {}", id);
+        }
+        KeysCmd::Csr { out } => {
+            let key = cfg.uplink.client_key_path.as_deref().context("set uplink.client_key_path")?;
+            let csr = scout_crypto::csr::generate_csr(key, &device_id(cfg))?;
+            match out {
+                Some(path) => { std::fs::write(&path, csr)?; info!("keys: CSR for {} written to {}", device_id(cfg), path); }
+                None => print!("{}", csr),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Apply the keyring retirement policy, keeping keys the spool still needs.
+fn retire_keys(cfg: &Config, kcfg: &KeyConfig) -> Result<()> {
+    let sealed = spool_key_ids(cfg, &DeviceKeys::load(kcfg)?)?;
+    let r = DeviceKeys::retire(kcfg, &|id| sealed.contains_key(&Some(id)))?;
+    for id in &r.removed {
+        info!("keys: retired {}", id);
+    }
+    for id in &r.kept_in_use {
+        warn!("keys: {} is due for retirement but still seals {} spool files; kept", id, sealed[&Some(*id)]);
+    }
+    Ok(())
+}
+
+/// Spool files per sealing key; `None` counts files no key opens.
+fn spool_key_ids(cfg: &Config, keys: &DeviceKeys) -> Result<std::collections::BTreeMap<Option<KeyId>, usize>> {
+    let mut n = std::collections::BTreeMap::new();
+    for f in spool::list(std::path::Path::new(&cfg.uplink.spool_dir))? {
+        let id = std::fs::read(&f.path).ok()
+            .and_then(|rec| spool::decode_record(&f.path, &rec).ok().and_then(|blob| keys.key_of(scout_proto::link::TELEMETRY_AAD, blob)));
+        *n.entry(id).or_insert(0) += 1;
+    }
+    Ok(n)
+}
+
+async fn uplink_cmd(cmd: UplinkCmd) -> Result<()> {
+    match cmd {
+        UplinkCmd::Pin { target, server_name } => {
+            let pins = if std::path::Path::new(&target).is_file() {
+                cert_pin::pins_from_pem(&target)?
+            } else {
+                let url = if target.contains("://") { target.clone() } else { format!("tls://{}", target) };
+                let (host, port) = endpoint::parse_url(&url)?;
+                let name = server_name.unwrap_or_else(|| host.clone());
+                cert_pin::pins_from_server(&host, port, &name).await?
+            };
+            for (i, pin) in pins.iter().enumerate() {
+                println!("{}  {}", pin, if i == 0 { "leaf (pin this)".to_string() } else { format!("chain[{}]", i) });
+            }
+        }
     }
     Ok(())
 }
@@ -247,6 +554,134 @@ async fn vision_cmd(cfg: &Config, cmd: VisionCmd) -> Result<()> {
     }
 }
 
+fn mission_cmd(cfg: &Config, cmd: MissionCmd) -> Result<()> {
+    match cmd {
+        MissionCmd::Import { file, out, corridor_width_m } => {
+            let width = corridor_width_m.unwrap_or(cfg.nav.route.corridor_width_m);
+            let m = mission::import(std::path::Path::new(&file), width)?;
+            for n in &m.notes {
+                warn!("mission import: {}", n);
+            }
+            let home = m.home.as_ref().unwrap_or(&cfg.nav.home);
+            nav_doctor::check_geofence(home, &m.route, &m.zone, cfg.nav.max_radius_m)
+                .context("imported mission failed geofence checks")?;
+
+            #[derive(serde::Serialize)]
+            struct MissionToml<'a> { nav: &'a mission::Mission }
+            let s = toml::to_string(&MissionToml { nav: &m }).context("serialize mission toml")?;
+            write_or_print(out.as_deref(), &s)?;
+            info!("mission import: {} waypoints, {} zone vertices, {} rally points",
+                m.route.waypoints.len(), m.zone.garden_polygon.len(), m.rally_points.len());
+            Ok(())
+        }
+        MissionCmd::Export { format, out } => {
+            let fmt = match (&format, &out) {
+                (Some(f), _) => mission::MissionFormat::parse(f)?,
+                (None, Some(o)) => mission::MissionFormat::from_path(std::path::Path::new(o))?,
+                (None, None) => anyhow::bail!("--format or --out required"),
+            };
+            let m = mission::Mission {
+                cruise_alt_m: Some(cfg.nav.cruise_alt_m),
+                home: Some(cfg.nav.home.clone()),
+                route: cfg.nav.route.clone(),
+                zone: cfg.nav.zone.clone(),
+                rally_points: Vec::new(),
+                notes: Vec::new(),
+            };
+            write_or_print(out.as_deref(), &mission::export(&m, fmt)?)
+        }
+    }
+}
+
+fn spool_cmd(cfg: &Config, dir: Option<String>, cmd: SpoolCmd) -> Result<()> {
+    let dir = std::path::PathBuf::from(dir.unwrap_or_else(|| cfg.uplink.spool_dir.clone()));
+    let all = spool::list(&dir)?;
+    match cmd {
+        SpoolCmd::Ls => {
+            for f in &all {
+                println!("{:<32}  {:<8}  {:>8}  {}", spool::file_name(f.id, f.priority), f.priority.name(), f.bytes, export::rfc3339(f.written_unix_ms()));
+            }
+            info!("spool: {} files, {} bytes in {:?}", all.len(), all.iter().map(|f| f.bytes).sum::<u64>(), dir);
+            Ok(())
+        }
+        SpoolCmd::Stats => {
+            let keys = DeviceKeys::load(&key_config(cfg)?)?;
+            let (envs, failed) = spool_decode(&keys, &all);
+            let mut by_type = std::collections::BTreeMap::new();
+            for e in &envs {
+                *by_type.entry(export::payload_type(&e.payload)).or_insert(0u64) += 1;
+            }
+            println!("files:   {} ({} bytes)", all.len(), all.iter().map(|f| f.bytes).sum::<u64>());
+            if let (Some(first), Some(last)) = (all.first(), all.last()) {
+                println!("written: {} .. {}", export::rfc3339(first.written_unix_ms()), export::rfc3339(last.written_unix_ms()));
+            }
+            println!("events:  {}", envs.len());
+            for (t, n) in &by_type {
+                println!("  {:<22} {}", t, n);
+            }
+            println!("quarantined: {} (in {})", spool::quarantined(&dir), spool::QUARANTINE_DIR);
+            println!("failed:  {}", failed.len());
+            for p in &failed {
+                println!("  {}", p.display());
+            }
+            Ok(())
+        }
+        SpoolCmd::Decode { files } => {
+            let keys = DeviceKeys::load(&key_config(cfg)?)?;
+            let selected = if files.is_empty() {
+                all
+            } else {
+                files.iter().map(|f| {
+                    let p = std::path::Path::new(f);
+                    let path = if p.exists() { p.to_path_buf() } else { dir.join(f) };
+                    let bytes = std::fs::metadata(&path).with_context(|| format!("{:?}", path))?.len();
+                    let (id, priority) = spool::parse_name(&path).unwrap_or((0, telemetry::Priority::Routine));
+                    Ok(spool::SpoolFile { id, priority, path, bytes })
+                }).collect::<Result<Vec<_>>>()?
+            };
+            let (envs, _) = spool_decode(&keys, &selected);
+            print!("{}", export::export(&envs, export::ExportFormat::Jsonl)?);
+            Ok(())
+        }
+        SpoolCmd::Export { format, out } => {
+            let fmt = match (&format, &out) {
+                (Some(f), _) => export::ExportFormat::parse(f)?,
+                (None, Some(o)) => export::ExportFormat::from_path(std::path::Path::new(o))?,
+                (None, None) => anyhow::bail!("--format or --out required"),
+            };
+            let keys = DeviceKeys::load(&key_config(cfg)?)?;
+            let (envs, failed) = spool_decode(&keys, &all);
+            write_or_print(out.as_deref(), &export::export(&envs, fmt)?)?;
+            info!("spool export: {} events from {} files ({} failed)", envs.len(), all.len() - failed.len(), failed.len());
+            Ok(())
+        }
+    }
+}
+
+/// Decrypt spool files in order; files that fail are reported and skipped.
+fn spool_decode(keys: &DeviceKeys, files: &[spool::SpoolFile]) -> (Vec<telemetry::Envelope>, Vec<std::path::PathBuf>) {
+    let mut envs = Vec::new();
+    let mut failed = Vec::new();
+    for f in files {
+        match std::fs::read(&f.path).map_err(anyhow::Error::from).and_then(|blob| spool::open(keys, &f.path, &blob)) {
+            Ok(mut batch) => envs.append(&mut batch),
+            Err(e) => {
+                // stderr, not the log: this is the report the user asked for
+                eprintln!("spool: {}: {:#}", f.path.display(), e);
+                failed.push(f.path.clone());
+            }
+        }
+    }
+    (envs, failed)
+}
+
+fn write_or_print(out: Option<&str>, s: &str) -> Result<()> {
+    match out {
+        Some(path) => std::fs::write(path, s).with_context(|| format!("write {}", path)),
+        None => { print!("{}", s); Ok(()) }
+    }
+}
+
 async fn fc_cmd(cfg: &Config, cmd: FcCmd, fc_status: Arc<Mutex<FcStatus>>) -> Result<()> {
     match cmd {
         FcCmd::Autodetect => {
@@ -305,10 +740,7 @@ async fn run(cfg: &Config, fc_status: Arc<Mutex<FcStatus>>) -> Result<()> {
         shutdown_clone.store(true, Ordering::SeqCst);
     });
 
-    let keys = DeviceKeys::load(&KeyConfig {
-        key_path: cfg.crypto.key_path.clone(),
-        passphrase: cfg.crypto.passphrase.clone().unwrap_or_default(),
-    })?;
+    let keys = DeviceKeys::load(&key_config(cfg)?)?;
 
     let mut src = match cfg.gnss.source.as_str() {
         "nmea-serial" => gnss::GnssSource::serial(cfg.gnss.nmea_device.as_ref().context("gnss.nmea_device missing")?)?,
@@ -316,22 +748,22 @@ async fn run(cfg: &Config, fc_status: Arc<Mutex<FcStatus>>) -> Result<()> {
         other => anyhow::bail!("unknown gnss.source: {}", other),
     };
 
+    // The uplink runs in its own task; the loop below only queues payloads
     let mut uplink = if cfg.uplink.enable {
-        Some(Uplink::new(
-            cfg.uplink.endpoint.clone(),
-            cfg.uplink.pinned_server_spki_sha256.clone().unwrap_or_default(),
-            cfg.uplink.spool_dir.clone(),
-            cfg.uplink.spool_max_mb,
-            keys.clone(),
-        )?)
+        Some(new_uplink(cfg, keys.clone())?.spawn(SHUTDOWN_DRAIN))
     } else { None };
 
+    if let Some(u) = uplink.as_mut() {
+        let startup = telemetry::Startup { version: env!("CARGO_PKG_VERSION").to_string(), command: "run".to_string() };
+        u.submit(Payload::Startup(startup));
+    }
+
     let mut nav_engine = nav::NavEngine::new(
         cfg.nav.home.clone(),
         cfg.nav.route.clone(),
         cfg.nav.zone.clone(),
         cfg.nav.max_radius_m,
-        nav::RthPolicy { grace_link_loss_s: cfg.rth.grace_link_loss_s, gnss_bad_fix_s: cfg.rth.gnss_bad_fix_s },
+        rth_policy(cfg),
     );
 
     // Battery status (shared with FC reader thread) - must be defined before FC section
@@ -339,6 +771,7 @@ async fn run(cfg: &Config, fc_status: Arc<Mutex<FcStatus>>) -> Result<()> {
 
     // FC: background link (optional)
     let (fc_tx_cmd, mut fc_rx_cmd) = mpsc::channel::<FcCommand>(8);
+    let (fc_ack_tx, mut fc_ack_rx) = mpsc::channel::<telemetry::FcAck>(8);
     let mut fc_handle = None;
 
     if let Some(fc_cfg) = cfg.fc.as_ref() {
@@ -363,6 +796,7 @@ async fn run(cfg: &Config, fc_status: Arc<Mutex<FcStatus>>) -> Result<()> {
             let fc_status2 = fc_status.clone();
             let battery_status2 = battery_status.clone();
             let shutdown2 = shutdown.clone();
+            let fc_ack_tx = fc_ack_tx.clone();
             let mut link = FcLink::open(
                 &dev, baud,
                 sys_id, comp_id,
@@ -385,6 +819,9 @@ async fn run(cfg: &Config, fc_status: Arc<Mutex<FcStatus>>) -> Result<()> {
 
                     // Read (best-effort)
                     if let Ok(Some(msg)) = link.poll_once_nonblocking() {
+                        if let MavMessage::COMMAND_ACK(ack) = &msg {
+                            let _ = fc_ack_tx.try_send(telemetry::FcAck { command_id: ack.command as u16, result: ack.result as u8 });
+                        }
                         let mut st = fc_status2.lock().unwrap();
                         st.connected = true;
                         let msg_str = format!("{:?}", msg);
@@ -431,43 +868,77 @@ async fn run(cfg: &Config, fc_status: Arc<Mutex<FcStatus>>) -> Result<()> {
     let thermal = ThermalMonitor::default();
 
     let mut last_state = nav::MissionState::Idle;
+    let mut last_health: Option<std::time::Instant> = None;
 
     info!("run: entering main loop (Ctrl+C to stop)");
 
     while !shutdown.load(Ordering::SeqCst) {
         let fix = src.next_fix().await?;
         let quality = fix.quality.clone();
+        // Battery, thermal and link-loss RTH are rehearsed in `scout sim` only
         let nav_out = nav_engine.step(fix.clone());
 
+        // Read thermal status
+        let cpu_temp = thermal.check().ok().map(|s| s.temp_c);
+
+        // Read battery status from FC
+        let batt = battery_status.lock().unwrap().clone();
+
+        // Typed telemetry produced this iteration, sent in order at the end of the loop
+        let mut outbox: Vec<Payload> = Vec::new();
+
+        if nav_out.state != last_state {
+            info!("nav: {:?} -> {:?}: {} [{}]", last_state, nav_out.state, nav_out.message, nav_out.metrics);
+            outbox.push(Payload::MissionStateChange(telemetry::MissionStateChange {
+                from: proto_state(last_state),
+                to: proto_state(nav_out.state),
+                reason: nav_out.message.clone(),
+            }));
+            if let (nav::MissionState::Rth | nav::MissionState::Abort, Some(f)) = (nav_out.state, nav_out.failsafe) {
+                outbox.push(Payload::FailsafeTriggered(telemetry::FailsafeTriggered {
+                    failsafe: proto_failsafe(f),
+                    detail: nav_out.message.clone(),
+                }));
+            }
+        }
+
         // On entering RTH: send RTL to FC (short-lived command link to avoid cross-thread borrow complexity)
         if nav_out.state == nav::MissionState::Rth && last_state != nav::MissionState::Rth {
             if let Some(fc_cfg) = cfg.fc.as_ref() {
                 if fc_cfg.enable && fc_cfg.allow_rtl {
-                    if let Ok((dev, baud)) = resolve_fc_port(fc_cfg) {
-                        if let Ok(mut cmdlink) = FcLink::open(
+                    let sent = resolve_fc_port(fc_cfg).and_then(|(dev, baud)| {
+                        let mut cmdlink = FcLink::open(
                             &dev, baud,
                             fc_cfg.sys_id, fc_cfg.comp_id,
                             fc_cfg.target_sys, fc_cfg.target_comp,
                             fc_cfg.allow_rtl, fc_cfg.allow_hold,
                             fc_cfg.require_heartbeat,
-                        ) {
-                            let _ = cmdlink.cmd_rtl();
-                        }
-                    }
+                        )?;
+                        cmdlink.cmd_rtl()
+                    });
+                    outbox.push(Payload::FcCommand(telemetry::FcCommand {
+                        command: telemetry::FcCommandKind::Rtl,
+                        error: sent.err().map(|e| format!("{:#}", e)),
+                    }));
                     let _ = fc_tx_cmd.send(FcCommand::RtlRequested).await;
                 }
             }
         }
-        last_state = nav_out.state.clone();
+        last_state = nav_out.state;
+
+        while let Ok(ack) = fc_ack_rx.try_recv() {
+            outbox.push(Payload::FcAck(ack));
+        }
 
         // Vision
         let do_infer = det.is_some() && power.tick_should_infer();
-        let mut vision_msg = String::new();
+        let mut vision: Option<telemetry::Vision> = None;
 
         if do_infer {
             if let Some(camcfg) = &cfg.camera {
                 #[allow(unused_variables)] // Used only when vision feature enabled
                 let jpeg = camera::capture_jpeg(camcfg).await?;
+                #[allow(unused_variables)] // Used only when vision feature enabled
                 let use_roi = power.current_mode() != PowerMode::Scan && last_lock_roi.is_some();
 
                 let dets: Vec<scout_vision::Detection> = match det.as_mut().unwrap() {
@@ -478,6 +949,7 @@ async fn run(cfg: &Config, fc_status: Arc<Mutex<FcStatus>>) -> Result<()> {
                     #[allow(unreachable_patterns)]
                     _ => Vec::new(),
                 };
+                let mut v = telemetry::Vision { detections: dets.len().min(u16::MAX as usize) as u16, lock_id: None, lock_conf: None };
 
                 if let Some(tr) = tracker.as_mut() {
                     let out = tr.update(&dets);
@@ -486,56 +958,49 @@ async fn run(cfg: &Config, fc_status: Arc<Mutex<FcStatus>>) -> Result<()> {
                     if let Some(lock) = out.locked {
                         last_lock_roi = Some(Roi { cx: lock.cx, cy: lock.cy, w: lock.w, h: lock.h }.clamp01());
                         power.on_target_event();
-                        vision_msg = format!("TRACK lock={} conf={:.2} roi={} mode={:?}", lock.id, lock.conf, if use_roi { "on" } else { "off" }, power.current_mode());
+                        v.lock_id = Some(lock.id);
+                        v.lock_conf = Some(lock.conf);
                     } else {
                         last_lock_roi = None;
-                        vision_msg = format!("TRACK none mode={:?}", power.current_mode());
                     }
-                } else {
-                    vision_msg = format!("DET n={} mode={:?}", dets.len(), power.current_mode());
                 }
+                vision = Some(v);
             }
-        } else {
-            vision_msg = format!("infer=skip mode={:?}", power.current_mode());
         }
 
-        // Read thermal status
-        let cpu_temp = thermal.check().ok().map(|s| s.temp_c);
-
-        // Read battery status from FC
-        let batt = battery_status.lock().unwrap().clone();
-
-        // Read link health from uplink
-        let (link_rtt, link_qual) = if let Some(u) = uplink.as_ref() {
-            let health = u.link_health();
-            (health.rtt_ms, Some(health.quality))
-        } else {
-            (None, None)
-        };
-
-        let ev = TelemetryEvent {
-            ts_unix_ms: time::OffsetDateTime::now_utc().unix_timestamp_nanos() as i64 / 1_000_000,
-            kind: match nav_out.state {
-                nav::MissionState::OperateInZone => EventKind::Status,
-                nav::MissionState::Rth => EventKind::Rth,
-                nav::MissionState::Abort => EventKind::Abort,
-                _ => EventKind::Status,
-            },
-            lat: fix.lat, lon: fix.lon,
-            sats: quality.sats as i32,
+        let m = &nav_out.metrics;
+        outbox.push(Payload::Position(telemetry::Position {
+            lat: fix.lat,
+            lon: fix.lon,
+            alt_m: None,
+            sats: quality.sats,
             hdop: quality.hdop,
-            msg: format!("{} {}", nav_out.message, vision_msg),
-            battery_voltage: batt.voltage,
-            battery_percent: batt.remaining,
-            battery_current: batt.current,
-            cpu_temp_c: cpu_temp,
-            link_rtt_ms: link_rtt,
-            link_quality: link_qual,
-        };
+            state: proto_state(nav_out.state),
+            dist_home_m: m.dist_home_m as f32,
+            cross_track_m: m.cross_track_m.map(|v| v as f32),
+            route_segment: m.active_segment.map(|i| i as u32),
+            route_progress: m.route_progress.map(|v| v as f32),
+            zone_edge_m: m.zone_edge_m.map(|v| v as f32),
+            time_in_state_s: m.time_in_state_s,
+            vision,
+        }));
 
         if let Some(u) = uplink.as_mut() {
-            if let Err(e) = u.send_event(&ev).await { warn!("uplink send failed: {:#}", e); }
-            if let Err(e) = u.flush_spool().await { warn!("uplink flush failed: {:#}", e); }
+            if last_health.map(|t| t.elapsed() >= HEALTH_INTERVAL).unwrap_or(true) {
+                last_health = Some(std::time::Instant::now());
+                let link = u.link_health();
+                outbox.push(Payload::Health(telemetry::Health {
+                    battery_voltage: batt.voltage,
+                    battery_percent: batt.remaining,
+                    battery_current: batt.current,
+                    cpu_temp_c: cpu_temp,
+                    link_rtt_ms: link.rtt_ms,
+                    link_quality: Some(link.quality),
+                }));
+            }
+            for p in outbox {
+                u.submit(p);
+            }
         }
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -545,11 +1010,9 @@ async fn run(cfg: &Config, fc_status: Arc<Mutex<FcStatus>>) -> Result<()> {
     info!("run: shutdown initiated, cleaning up...");
 
     // Final spool flush
-    if let Some(u) = uplink.as_mut() {
-        info!("run: flushing remaining telemetry spool...");
-        if let Err(e) = u.flush_spool().await {
-            warn!("final spool flush failed: {:#}", e);
-        }
+    if let Some(mut u) = uplink {
+        u.submit(Payload::Shutdown(telemetry::Shutdown { reason: "signal".to_string() }));
+        u.shutdown().await;
     }
 
     // Note: FC handle runs in spawn_blocking and will be dropped
@@ -559,6 +1022,147 @@ async fn run(cfg: &Config, fc_status: Arc<Mutex<FcStatus>>) -> Result<()> {
     Ok(())
 }
 
+const HEALTH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// How long shutdown waits for the ground station to ack the spool.
+const SHUTDOWN_DRAIN: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// `uplink.device_id`, else the hostname.
+fn device_id(cfg: &Config) -> String {
+    cfg.uplink.device_id.clone().unwrap_or_else(|| {
+        std::fs::read_to_string("/etc/hostname")
+            .map(|s| s.trim().to_string())
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "navscout".to_string())
+    })
+}
+
+fn proto_state(s: nav::MissionState) -> telemetry::MissionState {
+    match s {
+        nav::MissionState::Idle => telemetry::MissionState::Idle,
+        nav::MissionState::TransitToZone => telemetry::MissionState::TransitToZone,
+        nav::MissionState::OperateInZone => telemetry::MissionState::OperateInZone,
+        nav::MissionState::Rth => telemetry::MissionState::Rth,
+        nav::MissionState::Land => telemetry::MissionState::Land,
+        nav::MissionState::Abort => telemetry::MissionState::Abort,
+    }
+}
+
+fn proto_failsafe(f: nav::Failsafe) -> telemetry::Failsafe {
+    match f {
+        nav::Failsafe::MaxRadius => telemetry::Failsafe::MaxRadius,
+        nav::Failsafe::Gnss => telemetry::Failsafe::Gnss,
+        nav::Failsafe::Battery => telemetry::Failsafe::Battery,
+        nav::Failsafe::Thermal => telemetry::Failsafe::Thermal,
+        nav::Failsafe::LinkLoss => telemetry::Failsafe::LinkLoss,
+        nav::Failsafe::Geofence => telemetry::Failsafe::Geofence,
+    }
+}
+
+fn uplink_endpoints(u: &UplinkCfg) -> Vec<EndpointConfig> {
+    let legacy_pin = u.pinned_server_spki_sha256.iter().filter(|p| !p.is_empty()).map(|p| PinConfig::Sha256(p.clone()));
+    let pins: Vec<_> = legacy_pin.chain(u.pins.iter().cloned()).collect();
+    let single = u.endpoint.iter().map(|url| EndpointConfig {
+        url: url.clone(),
+        server_name: None,
+        pins: pins.clone(),
+    });
+    single.chain(u.endpoints.iter().cloned()).collect()
+}
+
+fn client_auth(u: &UplinkCfg) -> Result<Option<ClientAuth>> {
+    match (&u.client_cert_path, &u.client_key_path) {
+        (Some(cert), Some(key)) => Ok(Some(ClientAuth::load(cert, key)?)),
+        (None, None) => Ok(None),
+        _ => anyhow::bail!("uplink.client_cert_path and uplink.client_key_path must be set together"),
+    }
+}
+
+fn rth_policy(cfg: &Config) -> nav::RthPolicy {
+    nav::RthPolicy {
+        grace_link_loss_s: cfg.rth.grace_link_loss_s,
+        gnss_bad_fix_s: cfg.rth.gnss_bad_fix_s,
+        battery_low_pct: cfg.rth.battery_low_pct,
+        thermal_soft_c: cfg.rth.thermal_soft_c,
+    }
+}
+
+/// Software-in-the-loop rehearsal: the simulator feeds NMEA into a `GnssSource`
+/// and speaks MAVLink to a regular `FcLink`, so the nav engine and FC command
+/// gating run unmodified. Runs in simulated time, as fast as possible.
+async fn sim(cfg: &Config) -> Result<()> {
+    let sim_cfg = cfg.sim.clone().unwrap_or_default();
+    let mut sim = Simulator::new(sim_cfg, cfg.nav.home.clone(), &cfg.nav.route, cfg.nav.cruise_alt_m)?;
+
+    let (gnss_tx, gnss_rx) = mpsc::channel(8);
+    let mut src = gnss::GnssSource::feed(gnss_rx);
+
+    // Same ids and command gating as the real FC link; defaults when [fc] is absent
+    let fc_cfg = cfg.fc.as_ref();
+    let allow_rtl = fc_cfg.map(|f| f.allow_rtl).unwrap_or(true);
+    let mut link = FcLink::from_connection(
+        sim.mav_connection(),
+        fc_cfg.map(|f| f.sys_id).unwrap_or(254), fc_cfg.map(|f| f.comp_id).unwrap_or(190),
+        fc_cfg.map(|f| f.target_sys).unwrap_or(1), fc_cfg.map(|f| f.target_comp).unwrap_or(1),
+        allow_rtl, fc_cfg.map(|f| f.allow_hold).unwrap_or(false),
+        fc_cfg.map(|f| f.require_heartbeat).unwrap_or(true),
+    );
+
+    let mut nav_engine = nav::NavEngine::new(
+        cfg.nav.home.clone(),
+        cfg.nav.route.clone(),
+        cfg.nav.zone.clone(),
+        cfg.nav.max_radius_m,
+        rth_policy(cfg),
+    );
+
+    info!("sim: starting");
+    let mut last_state: Option<nav::MissionState> = None;
+
+    while !sim.finished() {
+        let tick = sim.step();
+        for line in tick.nmea {
+            gnss_tx.send((tick.ts, line)).await.context("gnss feed")?;
+        }
+        let fix = src.next_fix().await?;
+
+        // Drain autopilot traffic (heartbeat, SYS_STATUS, ACKs)
+        while let Ok(Some(_)) = link.poll_once_nonblocking() {}
+
+        let health = nav::HealthInputs {
+            battery_pct: link.battery_status().remaining,
+            cpu_temp_c: Some(tick.cpu_temp_c),
+            link_down_s: tick.link_down_s,
+        };
+        let nav_out = nav_engine.step_with_health(fix, &health);
+
+        if last_state != Some(nav_out.state) {
+            let from = last_state.map(|s| format!("{:?}", s)).unwrap_or_else(|| "-".to_string());
+            sim.note(TimelineKind::Nav, format!("{} -> {:?}: {} [{}]", from, nav_out.state, nav_out.message, nav_out.metrics));
+
+            if nav_out.state == nav::MissionState::Rth && allow_rtl {
+                match link.cmd_rtl() {
+                    Ok(()) => sim.note(TimelineKind::Fc, "send RTL"),
+                    Err(e) => sim.note(TimelineKind::Fc, format!("RTL not sent: {:#}", e)),
+                }
+            }
+            if nav_out.state == nav::MissionState::Abort {
+                sim.note(TimelineKind::Nav, "abort: simulation stopped");
+                break;
+            }
+        }
+        last_state = Some(nav_out.state);
+    }
+
+    for e in sim.timeline() {
+        println!("{:>8.1}s  {:<7}  {}", e.t_s, e.kind, e.text);
+    }
+    let v = sim.vehicle();
+    println!("end: t={:.1}s mode={:?} pos={:.6},{:.6} alt={:.1}m battery={:?}%",
+        sim.elapsed_s(), v.mode, v.lat, v.lon, v.alt_m, link.battery_status().remaining);
+    Ok(())
+}
+
 #[derive(Debug)]
 enum FcCommand {
     RtlRequested,
@@ -1,12 +1,65 @@
 use anyhow::Result;
 use chacha20poly1305::{aead::{Aead, KeyInit}, XChaCha20Poly1305, XNonce};
 use rand::RngCore;
+use std::fmt;
+use zeroize::Zeroize;
 
-#[derive(Clone)]
-pub struct AeadKey(pub [u8; 32]);
+/// 256-bit key in its own heap allocation: wiped on drop, never printed, and
+/// locked into RAM once `memory::enable` has been called.
+pub struct AeadKey(Box<[u8; 32]>);
+
+impl AeadKey {
+    pub fn generate() -> Self {
+        Self::fill(|k| rand::thread_rng().fill_bytes(k))
+    }
+
+    /// Key written in place by `f`, so its bytes sit nowhere else.
+    pub fn fill(f: impl FnOnce(&mut [u8; 32])) -> Self {
+        let mut key = Self::zero();
+        f(&mut key.0);
+        key
+    }
+
+    pub fn try_fill(f: impl FnOnce(&mut [u8; 32]) -> Result<()>) -> Result<Self> {
+        let mut key = Self::zero();
+        f(&mut key.0)?;
+        Ok(key)
+    }
+
+    fn zero() -> Self {
+        let key = Box::new([0u8; 32]);
+        crate::memory::lock(key.as_ptr(), key.len());
+        Self(key)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl Clone for AeadKey {
+    fn clone(&self) -> Self {
+        Self::fill(|k| k.copy_from_slice(&self.0[..]))
+    }
+}
+
+impl Drop for AeadKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+        crate::memory::unlock(self.0.as_ptr(), self.0.len());
+    }
+}
+
+impl zeroize::ZeroizeOnDrop for AeadKey {}
+
+impl fmt::Debug for AeadKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AeadKey(..)")
+    }
+}
 
 pub fn seal(key: &AeadKey, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
-    let cipher = XChaCha20Poly1305::new((&key.0).into());
+    let cipher = XChaCha20Poly1305::new(key.as_bytes().into());
     let mut nonce = [0u8; 24];
     rand::thread_rng().fill_bytes(&mut nonce);
     let ct = cipher.encrypt(XNonce::from_slice(&nonce), chacha20poly1305::aead::Payload { msg: plaintext, aad })
@@ -20,8 +73,293 @@ pub fn seal(key: &AeadKey, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
 pub fn open(key: &AeadKey, aad: &[u8], blob: &[u8]) -> Result<Vec<u8>> {
     anyhow::ensure!(blob.len() >= 24, "ciphertext too short");
     let (nonce, ct) = blob.split_at(24);
-    let cipher = XChaCha20Poly1305::new((&key.0).into());
+    let cipher = XChaCha20Poly1305::new(key.as_bytes().into());
     let pt = cipher.decrypt(XNonce::from_slice(nonce), chacha20poly1305::aead::Payload { msg: ct, aad })
         .map_err(|e| anyhow::anyhow!("AEAD decryption failed: {:?}", e))?;
     Ok(pt)
 }
+
+// Sealed blobs bound to their sender. The header travels in the clear ahead of
+// the nonce and is authenticated as AAD (after a caller-chosen label), so a
+// blob cannot be re-attributed to another device, stream or sequence number:
+//
+//   BOUND_MAGIC | key id u32 BE | device id len u8 | device id | boot id [16] | stream u8 | seq u64 BE | nonce | ct
+//
+// `seq` is per (device, stream) and monotonic across restarts; the receiver
+// keeps a `ReplayWindow` per stream to drop replays and report gaps.
+
+pub const BOUND_MAGIC: &[u8; 4] = b"NSB2";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobHeader {
+    /// Sealing key (see `keys::KeyId`)
+    pub key_id: u32,
+    pub device_id: String,
+    /// Identifies one run of the sender (see `boot_id`)
+    pub boot_id: [u8; 16],
+    /// Independent sequence space, e.g. a priority class
+    pub stream: u8,
+    pub seq: u64,
+}
+
+impl BlobHeader {
+    fn encode(&self) -> Result<Vec<u8>> {
+        let id = self.device_id.as_bytes();
+        let len = u8::try_from(id.len()).map_err(|_| anyhow::anyhow!("device id longer than 255 bytes"))?;
+        let mut out = Vec::with_capacity(4 + 4 + 1 + id.len() + 16 + 1 + 8);
+        out.extend_from_slice(BOUND_MAGIC);
+        out.extend_from_slice(&self.key_id.to_be_bytes());
+        out.push(len);
+        out.extend_from_slice(id);
+        out.extend_from_slice(&self.boot_id);
+        out.push(self.stream);
+        out.extend_from_slice(&self.seq.to_be_bytes());
+        Ok(out)
+    }
+
+    /// The (not yet authenticated) header of a bound blob.
+    pub fn parse(blob: &[u8]) -> Result<Self> {
+        Self::decode(blob).map(|(h, _)| h)
+    }
+
+    /// Returns the header and its encoded length.
+    fn decode(blob: &[u8]) -> Result<(Self, usize)> {
+        anyhow::ensure!(blob.starts_with(BOUND_MAGIC), "not a bound blob");
+        anyhow::ensure!(blob.len() >= 8, "truncated blob header");
+        let key_id = u32::from_be_bytes(blob[4..8].try_into().unwrap());
+        let at = 8;
+        let id_len = *blob.get(at).ok_or_else(|| anyhow::anyhow!("truncated blob header"))? as usize;
+        let len = at + 1 + id_len + 16 + 1 + 8;
+        anyhow::ensure!(blob.len() >= len, "truncated blob header");
+        let device_id = std::str::from_utf8(&blob[at + 1..at + 1 + id_len]).map_err(|_| anyhow::anyhow!("device id is not UTF-8"))?.to_string();
+        let rest = &blob[at + 1 + id_len..len];
+        Ok((Self {
+            key_id,
+            device_id,
+            boot_id: rest[..16].try_into().unwrap(),
+            stream: rest[16],
+            seq: u64::from_be_bytes(rest[17..].try_into().unwrap()),
+        }, len))
+    }
+}
+
+fn bound_aad(label: &[u8], header: &[u8]) -> Vec<u8> {
+    [label, header].concat()
+}
+
+/// Whether `blob` was sealed by `seal_bound` (as opposed to plain `seal`).
+pub fn is_bound(blob: &[u8]) -> bool {
+    blob.starts_with(BOUND_MAGIC)
+}
+
+/// Seal `plaintext` with `header` in front of it, authenticated together with `label`.
+pub fn seal_bound(key: &AeadKey, label: &[u8], header: &BlobHeader, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let mut out = header.encode()?;
+    let ct = seal(key, &bound_aad(label, &out), plaintext)?;
+    out.extend_from_slice(&ct);
+    Ok(out)
+}
+
+/// Open a blob from `seal_bound`; the header is only returned once authenticated.
+pub fn open_bound(key: &AeadKey, label: &[u8], blob: &[u8]) -> Result<(BlobHeader, Vec<u8>)> {
+    let (header, len) = BlobHeader::decode(blob)?;
+    let pt = open(key, &bound_aad(label, &blob[..len]), &blob[len..])?;
+    Ok((header, pt))
+}
+
+/// This boot's id: the kernel's random boot id on Linux, otherwise random per
+/// process.
+pub fn boot_id() -> [u8; 16] {
+    let kernel = std::fs::read_to_string("/proc/sys/kernel/random/boot_id").ok()
+        .and_then(|s| hex::decode(s.trim().replace('-', "")).ok())
+        .and_then(|b| <[u8; 16]>::try_from(b).ok());
+    kernel.unwrap_or_else(|| {
+        let mut id = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut id);
+        id
+    })
+}
+
+/// What a receiver should do with a sequence number (see `ReplayWindow::check`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replay {
+    /// Not seen before: process it, then `ReplayWindow::insert` it
+    Fresh,
+    /// Already received
+    Duplicate,
+    /// Too far behind the newest to tell; treat as a replay
+    Stale,
+}
+
+/// Sequence numbers received on one stream: the newest plus a bitmap of the
+/// `REPLAY_WINDOW` before it. Senders emit a stream in order, so anything
+/// skipped over is reported as a gap, though it may still arrive late (a
+/// re-send) while it is inside the window.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayWindow {
+    highest: Option<u64>,
+    seen: [u64; REPLAY_WINDOW as usize / 64],
+}
+
+pub const REPLAY_WINDOW: u64 = 1024;
+const REPLAY_STATE_LEN: usize = 1 + 8 + REPLAY_WINDOW as usize / 8;
+
+impl ReplayWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn highest(&self) -> Option<u64> {
+        self.highest
+    }
+
+    fn bit(seq: u64) -> (usize, u64) {
+        let i = seq % REPLAY_WINDOW;
+        ((i / 64) as usize, 1 << (i % 64))
+    }
+
+    pub fn check(&self, seq: u64) -> Replay {
+        let Some(h) = self.highest else { return Replay::Fresh };
+        if seq > h {
+            return Replay::Fresh;
+        }
+        if h - seq >= REPLAY_WINDOW {
+            return Replay::Stale;
+        }
+        let (w, b) = Self::bit(seq);
+        if self.seen[w] & b != 0 { Replay::Duplicate } else { Replay::Fresh }
+    }
+
+    /// Record `seq` as received (it must have checked `Fresh`). Returns how many
+    /// sequence numbers it skips over, i.e. the size of a newly opened gap.
+    pub fn insert(&mut self, seq: u64) -> u64 {
+        let mut gap = 0;
+        match self.highest {
+            Some(h) if seq <= h => {}
+            Some(h) => {
+                gap = seq - h - 1;
+                if seq - h >= REPLAY_WINDOW {
+                    self.seen = Default::default();
+                } else {
+                    for s in h + 1..seq {
+                        let (w, b) = Self::bit(s);
+                        self.seen[w] &= !b;
+                    }
+                }
+                self.highest = Some(seq);
+            }
+            None => {
+                self.seen = Default::default();
+                self.highest = Some(seq);
+            }
+        }
+        let (w, b) = Self::bit(seq);
+        self.seen[w] |= b;
+        gap
+    }
+
+    /// Compact form for persisting the window across receiver restarts.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(REPLAY_STATE_LEN);
+        out.push(self.highest.is_some() as u8);
+        out.extend_from_slice(&self.highest.unwrap_or(0).to_be_bytes());
+        for w in self.seen {
+            out.extend_from_slice(&w.to_be_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(b: &[u8]) -> Result<Self> {
+        anyhow::ensure!(b.len() == REPLAY_STATE_LEN && b[0] <= 1, "invalid replay window state");
+        let mut r = Self::new();
+        if b[0] == 1 {
+            r.highest = Some(u64::from_be_bytes(b[1..9].try_into().unwrap()));
+        }
+        for (w, c) in r.seen.iter_mut().zip(b[9..].chunks_exact(8)) {
+            *w = u64::from_be_bytes(c.try_into().unwrap());
+        }
+        Ok(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_order_and_duplicates() {
+        let mut w = ReplayWindow::new();
+        assert_eq!(w.highest(), None);
+        for seq in 0..10 {
+            assert_eq!(w.check(seq), Replay::Fresh);
+            assert_eq!(w.insert(seq), 0);
+        }
+        assert_eq!(w.highest(), Some(9));
+        for seq in 0..10 {
+            assert_eq!(w.check(seq), Replay::Duplicate);
+        }
+        assert_eq!(w.check(10), Replay::Fresh);
+    }
+
+    #[test]
+    fn gaps_are_counted_and_fill_late() {
+        let mut w = ReplayWindow::new();
+        w.insert(5);
+        assert_eq!(w.insert(9), 3);
+        assert_eq!(w.check(7), Replay::Fresh);
+        assert_eq!(w.insert(7), 0);
+        assert_eq!(w.check(7), Replay::Duplicate);
+        assert_eq!(w.check(8), Replay::Fresh);
+        assert_eq!(w.highest(), Some(9));
+    }
+
+    #[test]
+    fn long_jump_shifts_the_window() {
+        let mut w = ReplayWindow::new();
+        for seq in 0..5 {
+            w.insert(seq);
+        }
+        let far = 4 + REPLAY_WINDOW + 100;
+        assert_eq!(w.insert(far), far - 5);
+        assert_eq!(w.check(4), Replay::Stale);
+        // Bits left over from before the jump must not read as received
+        for seq in far - REPLAY_WINDOW + 1..far {
+            assert_eq!(w.check(seq), Replay::Fresh, "seq {}", seq);
+        }
+        assert_eq!(w.check(far), Replay::Duplicate);
+    }
+
+    #[test]
+    fn stale_boundary() {
+        let mut w = ReplayWindow::new();
+        let h = REPLAY_WINDOW + 50;
+        w.insert(h);
+        assert_eq!(w.check(h - REPLAY_WINDOW + 1), Replay::Fresh);
+        assert_eq!(w.check(h - REPLAY_WINDOW), Replay::Stale);
+        w.insert(h - REPLAY_WINDOW + 1);
+        assert_eq!(w.check(h - REPLAY_WINDOW + 1), Replay::Duplicate);
+    }
+
+    #[test]
+    fn state_round_trips() {
+        let empty = ReplayWindow::new();
+        assert_eq!(ReplayWindow::from_bytes(&empty.to_bytes()).unwrap(), empty);
+
+        let mut w = ReplayWindow::new();
+        for seq in [3, 4, 70, 1000, 1500] {
+            w.insert(seq);
+        }
+        let bytes = w.to_bytes();
+        assert_eq!(bytes.len(), REPLAY_STATE_LEN);
+        let back = ReplayWindow::from_bytes(&bytes).unwrap();
+        assert_eq!(back, w);
+        assert_eq!(back.check(1000), Replay::Duplicate);
+        assert_eq!(back.check(999), Replay::Fresh);
+
+        assert!(ReplayWindow::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ReplayWindow::from_bytes(&[]).is_err());
+        let mut bad = bytes;
+        bad[0] = 2;
+        assert!(ReplayWindow::from_bytes(&bad).is_err());
+    }
+}
@@ -0,0 +1,73 @@
+// Client certificate identity for mutual TLS on the uplink: an ECDSA P-256
+// key (PKCS#8 PEM, mode 0600) and a certificate signing request for it.
+
+use anyhow::{Context, Result};
+use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Build a CSR with `common_name` as subject CN for the key at `key_path`,
+/// generating the key first if it does not exist. An existing key readable
+/// by anyone but its owner is refused. Returns the CSR as PEM.
+pub fn generate_csr(key_path: &str, common_name: &str) -> Result<String> {
+    anyhow::ensure!(!common_name.is_empty(), "CSR common name is empty");
+    let path = Path::new(key_path);
+    let key = if path.exists() {
+        crate::doctor::check_key_file("client key", key_path)?;
+        let pem = fs::read_to_string(path).with_context(|| format!("read {}", key_path))?;
+        KeyPair::from_pem(&pem).with_context(|| format!("parse {}", key_path))?
+    } else {
+        let key = KeyPair::generate().context("generate client key")?;
+        write_private(path, key.serialize_pem().as_bytes())?;
+        key
+    };
+
+    let mut params = CertificateParams::new(Vec::<String>::new())?;
+    let mut dn = DistinguishedName::new();
+    dn.push(DnType::CommonName, common_name);
+    params.distinguished_name = dn;
+    let csr = params.serialize_request(&key).context("sign CSR")?;
+    Ok(csr.pem()?)
+}
+
+/// Create `path` readable by the owner only; never overwrites.
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(p) = path.parent() { fs::create_dir_all(p)?; }
+    let mut opts = fs::OpenOptions::new();
+    opts.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.mode(0o600);
+    }
+    let mut f = opts.open(path).with_context(|| format!("create {:?}", path))?;
+    f.write_all(data)?;
+    f.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_is_created_private_and_reused() {
+        let dir = std::env::temp_dir().join(format!("scout-csr-{}", std::process::id()));
+        let key = dir.join("client.key");
+        let key = key.to_str().unwrap();
+        let first = generate_csr(key, "drone-1").unwrap();
+        assert!(first.contains("CERTIFICATE REQUEST"));
+        crate::doctor::check_key_file("client key", key).unwrap();
+        generate_csr(key, "drone-1").unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(key, fs::Permissions::from_mode(0o644)).unwrap();
+            let err = generate_csr(key, "drone-1").unwrap_err();
+            assert!(format!("{:#}", err).contains("permissions"));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
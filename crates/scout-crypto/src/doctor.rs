@@ -4,14 +4,29 @@ use std::path::Path;
 use crate::keys::KeyConfig;
 
 pub fn check_keys(cfg: &KeyConfig) -> Result<()> {
-    let p = Path::new(&cfg.key_path);
-    anyhow::ensure!(p.exists(), "crypto.key_path missing: {}", cfg.key_path);
+    check_key_file("crypto.key_path", &cfg.key_path)?;
+    let ring = cfg.ring_dir();
+    if ring.exists() {
+        for ent in std::fs::read_dir(&ring)? {
+            let path = ent?.path();
+            if path.extension().map(|e| e == "key").unwrap_or(false) {
+                check_key_file("keyring entry", &path.to_string_lossy())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A private key file must exist and be readable by its owner only.
+pub fn check_key_file(what: &str, path: &str) -> Result<()> {
+    let p = Path::new(path);
+    anyhow::ensure!(p.exists(), "{} missing: {}", what, path);
     #[cfg(unix)]
     {
         use std::os::unix::fs::MetadataExt;
         let md = std::fs::metadata(p)?;
         let mode = md.mode() & 0o777;
-        anyhow::ensure!(mode == 0o600, "key permissions should be 0600, got {:o}", mode);
+        anyhow::ensure!(mode == 0o600, "{} permissions should be 0600, got {:o}", what, mode);
     }
     Ok(())
 }
@@ -1,21 +1,94 @@
 use anyhow::{Context, Result};
-use argon2::{Argon2, password_hash::{SaltString, PasswordHash, PasswordVerifier}};
-use rand::RngCore;
+use std::fmt;
 use std::fs;
-use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+use crate::aead::{self, AeadKey, BlobHeader};
+use crate::keywrap::{self, KdfParams, WrapFormat};
+use crate::subkeys::{IpcAuthKey, LogKey, MavlinkSigningKey, RecordingKey, TelemetryKey};
+
+// Keyring: the current key lives at `key_path`; `rotate` moves it into
+// `<key_path>.ring/<key id>-<retired unix s>.key` (same passphrase, see `keywrap`)
+// so blobs sealed before the rotation can still be opened. Previous keys are
+// removed only by `retire`.
+//
+// `change_passphrase` rewraps every file as `<file>.rewrap`, then creates
+// `<key_path>.rewrap-commit` and renames them into place. A crash before the
+// marker leaves the old passphrase in force; after it, the next `load`
+// finishes the renames (see `finish_rewrap`).
+
+/// Previous keys kept by default.
+pub const DEFAULT_KEEP_PREVIOUS: usize = 4;
+/// Days a previous key is kept by default.
+pub const DEFAULT_RETIRE_DAYS: u64 = 30;
+
+/// Short public identifier of a device key, carried in sealed blob headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct KeyId(pub u32);
+
+impl KeyId {
+    pub fn of(key: &AeadKey) -> Self {
+        let h = blake3::derive_key("navscout device key id v1", key.as_bytes());
+        KeyId(u32::from_be_bytes(h[..4].try_into().unwrap()))
+    }
 
-use crate::aead::AeadKey;
+    pub fn parse(s: &str) -> Result<Self> {
+        anyhow::ensure!(s.len() == 8, "key id must be 8 hex digits");
+        Ok(KeyId(u32::from_str_radix(s, 16).map_err(|_| anyhow::anyhow!("key id must be 8 hex digits"))?))
+    }
+}
+
+impl fmt::Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x}", self.0)
+    }
+}
 
 #[derive(Clone)]
 pub struct DeviceKeys {
-    pub aead: AeadKey,
+    /// Current master key; only ever used through its subkeys
+    master: AeadKey,
+    pub key_id: KeyId,
+    /// Keys rotated out but still accepted for opening, newest first
+    pub previous: Vec<PreviousKey>,
+}
+
+#[derive(Clone)]
+pub struct PreviousKey {
+    pub id: KeyId,
+    master: AeadKey,
+    /// When it stopped being the current key
+    pub retired_unix: u64,
+    path: PathBuf,
 }
 
 #[derive(Clone)]
 pub struct KeyConfig {
     pub key_path: String,
-    pub passphrase: String, // optional, empty means raw key file
+    pub passphrase: Zeroizing<String>, // optional, empty means raw key file
+    /// Previous keys to keep for opening older blobs
+    pub keep_previous: usize,
+    /// Days a previous key is kept after rotation
+    pub retire_after_days: u64,
+    /// Cost of wrapping key files written from now on
+    pub kdf: KdfParams,
+}
+
+impl KeyConfig {
+    pub fn ring_dir(&self) -> PathBuf {
+        PathBuf::from(format!("{}.ring", self.key_path))
+    }
+}
+
+/// What `DeviceKeys::retire` did.
+#[derive(Debug, Default)]
+pub struct Retired {
+    pub removed: Vec<KeyId>,
+    /// Due for retirement but still sealing data that has not been delivered
+    pub kept_in_use: Vec<KeyId>,
 }
 
 impl DeviceKeys {
@@ -24,161 +97,395 @@ impl DeviceKeys {
         if let Some(p) = path.parent() { fs::create_dir_all(p)?; }
         anyhow::ensure!(!path.exists(), "key already exists");
 
-        let mut key = [0u8; 32];
-        rand::thread_rng().fill_bytes(&mut key);
+        keywrap::write(path, &AeadKey::generate(), &cfg.passphrase, &cfg.kdf)
+    }
 
-        if cfg.passphrase.is_empty() {
-            fs::write(path, &key)?;
-        } else {
-            // Wrap key: use proper Argon2 KDF to derive wrapping key from passphrase
-            let salt = SaltString::generate(&mut rand::thread_rng());
-
-            // Derive wrapping key using Argon2
-            let mut wrapping_key = [0u8; 32];
-            let argon = Argon2::default();
-            argon.hash_password_into(
-                cfg.passphrase.as_bytes(),
-                salt.as_str().as_bytes(),
-                &mut wrapping_key
-            ).map_err(|e| anyhow::anyhow!("Argon2 KDF failed: {:?}", e))?;
-
-            // Encrypt the master key with the derived wrapping key
-            let wrapped = crate::aead::seal(&AeadKey(wrapping_key), b"navscout-keywrap", &key)?;
-
-            // Store format: NAVSCOUT_KEYWRAP_V2\nsalt\nwrapped_blob
-            let mut file = String::new();
-            file.push_str("NAVSCOUT_KEYWRAP_V2\n");
-            file.push_str(salt.as_str());
-            file.push('\n');
-
-            let mut f = fs::File::create(path)?;
-            f.write_all(file.as_bytes())?;
-            f.write_all(&wrapped)?;
-            f.flush()?;
-        }
+    /// Replace the current key, keeping the old one in the keyring, and upgrade
+    /// keyring files in older wrap formats. Returns the new key's id.
+    ///
+    /// ```
+    /// use scout_crypto::keys::{DeviceKeys, KeyConfig};
+    /// use scout_crypto::keywrap::{self, WrapFormat};
+    /// let fixtures = std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/keywrap"));
+    /// let dir = std::env::temp_dir().join(format!("navscout-rotate-{}", std::process::id()));
+    /// for legacy in ["v1.key", "v2.key"] {
+    ///     let _ = std::fs::remove_dir_all(&dir);
+    ///     std::fs::create_dir_all(&dir).unwrap();
+    ///     let key_path = dir.join("device.key");
+    ///     std::fs::copy(fixtures.join(legacy), &key_path).unwrap();
+    ///     let cfg = KeyConfig {
+    ///         key_path: key_path.to_string_lossy().into(),
+    ///         passphrase: String::from("navscout-test").into(),
+    ///         keep_previous: 4,
+    ///         retire_after_days: 30,
+    ///         kdf: keywrap::KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 },
+    ///     };
+    ///     let old = DeviceKeys::load(&cfg).unwrap().key_id;
+    ///     let new = DeviceKeys::rotate(&cfg).unwrap();
+    ///     assert_eq!(keywrap::format(&key_path).unwrap(), WrapFormat::V3);
+    ///     let keys = DeviceKeys::load(&cfg).unwrap();
+    ///     assert_eq!((keys.key_id, keys.previous[0].id), (new, old), "{}", legacy);
+    ///     let ring = std::fs::read_dir(cfg.ring_dir()).unwrap().next().unwrap().unwrap().path();
+    ///     assert_eq!(keywrap::format(&ring).unwrap(), WrapFormat::V3);
+    /// }
+    /// std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn rotate(cfg: &KeyConfig) -> Result<KeyId> {
+        let path = Path::new(&cfg.key_path);
+        anyhow::ensure!(path.exists(), "key does not exist");
 
+        // Load current key to verify we can access it
+        let old = Self::load(cfg).context("cannot rotate: failed to load current key")?;
+
+        // Generate new master key
+        let new_key = AeadKey::generate();
+        let new_id = KeyId::of(&new_key);
+
+        // Keep the old key first: a crash in between leaves it in both places
+        let ring = cfg.ring_dir();
+        fs::create_dir_all(&ring)?;
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
-            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+            fs::set_permissions(&ring, fs::Permissions::from_mode(0o700))?;
         }
+        let name = format!("{}-{}.key", old.key_id, unix_now());
+        keywrap::write(&ring.join(&name), &old.master, &cfg.passphrase, &cfg.kdf)?;
+        for k in &old.previous {
+            if !matches!(keywrap::format(&k.path)?, WrapFormat::V3 | WrapFormat::Raw) {
+                keywrap::write(&k.path, &k.master, &cfg.passphrase, &cfg.kdf)?;
+            }
+        }
+
+        // New key with the same protection as the original, replaced atomically
+        keywrap::write(path, &new_key, &cfg.passphrase, &cfg.kdf)?;
+        Ok(new_id)
+    }
 
+    /// Wrap the current and previous keys under `new_passphrase` (and the
+    /// configured KDF cost); the keys themselves do not change. Interrupted
+    /// after all files are staged, the change is finished by the next `load`.
+    pub fn change_passphrase(cfg: &KeyConfig, new_passphrase: &str) -> Result<()> {
+        anyhow::ensure!(!new_passphrase.is_empty(), "new passphrase is empty");
+        let keys = Self::load(cfg).context("cannot change passphrase: failed to load keys")?;
+        let new = KeyConfig { passphrase: Zeroizing::new(new_passphrase.to_string()), ..cfg.clone() };
+
+        let mut files = vec![(PathBuf::from(&cfg.key_path), &keys.master)];
+        files.extend(keys.previous.iter().map(|k| (k.path.clone(), &k.master)));
+        for (path, key) in &files {
+            let staged = path.with_extension("rewrap");
+            keywrap::write(&staged, key, &new.passphrase, &new.kdf)?;
+        }
+        let marker = commit_marker(cfg);
+        fs::File::create(&marker).and_then(|f| f.sync_all()).with_context(|| format!("create {:?}", marker))?;
+        sync_dirs(cfg)?;
+        finish_rewrap(cfg).map(|_| ())
+    }
+
+    /// The current key and every readable keyring file. A keyring file that
+    /// cannot be read is skipped with a warning: it only costs opening the
+    /// blobs it sealed.
+    pub fn load(cfg: &KeyConfig) -> Result<DeviceKeys> {
+        let finished = finish_rewrap(cfg)?;
+        if finished > 0 {
+            info!("keys: finished an interrupted passphrase change ({} files)", finished);
+        }
+        let key = keywrap::read(Path::new(&cfg.key_path), &cfg.passphrase)?;
+        let key_id = KeyId::of(&key);
+
+        let mut previous = Vec::new();
+        let ring = cfg.ring_dir();
+        if ring.exists() {
+            for ent in fs::read_dir(&ring).with_context(|| format!("read {:?}", ring))? {
+                let path = ent?.path();
+                let Some(retired_unix) = parse_ring_name(&path) else { continue };
+                let master = match keywrap::read(&path, &cfg.passphrase) {
+                    Ok(k) => k,
+                    Err(e) => {
+                        warn!("keys: skipping keyring file {:?}: {:#}", path, e);
+                        continue;
+                    }
+                };
+                previous.push(PreviousKey { id: KeyId::of(&master), master, retired_unix, path });
+            }
+        }
+        previous.sort_by_key(|k| std::cmp::Reverse(k.retired_unix));
+        Ok(DeviceKeys { master: key, key_id, previous })
+    }
+
+    /// Remove previous keys beyond `keep_previous` or older than
+    /// `retire_after_days`, except those `in_use` still needs.
+    pub fn retire(cfg: &KeyConfig, in_use: &dyn Fn(KeyId) -> bool) -> Result<Retired> {
+        let keys = Self::load(cfg)?;
+        let cutoff = unix_now().saturating_sub(cfg.retire_after_days * 86400);
+        let mut r = Retired::default();
+        for (i, k) in keys.previous.iter().enumerate() {
+            if i < cfg.keep_previous && k.retired_unix >= cutoff {
+                continue;
+            }
+            if in_use(k.id) {
+                r.kept_in_use.push(k.id);
+                continue;
+            }
+            fs::remove_file(&k.path).with_context(|| format!("remove {:?}", k.path))?;
+            r.removed.push(k.id);
+        }
+        Ok(r)
+    }
+
+    /// Remove one previous key now, regardless of age.
+    pub fn retire_key(cfg: &KeyConfig, id: KeyId) -> Result<()> {
+        let keys = Self::load(cfg)?;
+        anyhow::ensure!(id != keys.key_id, "{} is the current key", id);
+        let found: Vec<_> = keys.previous.iter().filter(|k| k.id == id).collect();
+        anyhow::ensure!(!found.is_empty(), "no previous key {}", id);
+        for k in found {
+            fs::remove_file(&k.path).with_context(|| format!("remove {:?}", k.path))?;
+        }
         Ok(())
     }
 
-    pub fn rotate(cfg: &KeyConfig) -> Result<()> {
-        let path = Path::new(&cfg.key_path);
-        anyhow::ensure!(path.exists(), "key does not exist");
+    pub fn telemetry(&self) -> TelemetryKey {
+        TelemetryKey::derive(&self.master)
+    }
 
-        // Load current key to verify we can access it
-        let _old_keys = Self::load(cfg).context("cannot rotate: failed to load current key")?;
+    pub fn recordings(&self) -> RecordingKey {
+        RecordingKey::derive(&self.master)
+    }
 
-        // Generate new master key
-        let mut new_key = [0u8; 32];
-        rand::thread_rng().fill_bytes(&mut new_key);
+    pub fn logs(&self) -> LogKey {
+        LogKey::derive(&self.master)
+    }
 
-        // Write new key with same protection as original
-        let tmp = path.with_extension("new");
+    pub fn mavlink_signing(&self) -> MavlinkSigningKey {
+        MavlinkSigningKey::derive(&self.master)
+    }
+
+    pub fn ipc_auth(&self) -> IpcAuthKey {
+        IpcAuthKey::derive(&self.master)
+    }
+
+    /// Master key with this id, current or previous.
+    fn master(&self, id: KeyId) -> Option<&AeadKey> {
+        if id == self.key_id {
+            return Some(&self.master);
+        }
+        self.previous.iter().find(|k| k.id == id).map(|k| &k.master)
+    }
 
-        if cfg.passphrase.is_empty() {
-            // Raw key file
-            fs::write(&tmp, &new_key)?;
+    fn all(&self) -> impl Iterator<Item = &AeadKey> {
+        std::iter::once(&self.master).chain(self.previous.iter().map(|k| &k.master))
+    }
+
+    /// Blob header for sealing with the current key.
+    pub fn header(&self, device_id: &str, boot_id: [u8; 16], stream: u8, seq: u64) -> BlobHeader {
+        BlobHeader { key_id: self.key_id.0, device_id: device_id.to_string(), boot_id, stream, seq }
+    }
+
+    /// Open a telemetry blob from `seal_bound` with the telemetry subkey of
+    /// the key its header names.
+    pub fn open_bound(&self, label: &[u8], blob: &[u8]) -> Result<(BlobHeader, Vec<u8>)> {
+        let id = KeyId(BlobHeader::parse(blob)?.key_id);
+        let key = self.master(id).with_context(|| format!("sealed with unknown key {}", id))?;
+        TelemetryKey::derive(key).open_bound(label, blob)
+    }
+
+    /// Open a telemetry blob sealed by `seal` in builds before blob headers,
+    /// which used the master key directly.
+    pub fn open(&self, label: &[u8], blob: &[u8]) -> Result<Vec<u8>> {
+        self.all().find_map(|k| aead::open(k, label, blob).ok())
+            .context("AEAD decryption failed with every key")
+    }
+
+    /// Key a telemetry blob was sealed with, if it is one of ours.
+    pub fn key_of(&self, label: &[u8], blob: &[u8]) -> Option<KeyId> {
+        if aead::is_bound(blob) {
+            return BlobHeader::parse(blob).ok().map(|h| KeyId(h.key_id));
+        }
+        self.all().find(|k| aead::open(k, label, blob).is_ok()).map(KeyId::of)
+    }
+}
+
+/// Present while a passphrase change is renaming its staged files.
+fn commit_marker(cfg: &KeyConfig) -> PathBuf {
+    PathBuf::from(format!("{}.rewrap-commit", cfg.key_path))
+}
+
+/// The current key file and every keyring file, readable or not.
+fn key_files(cfg: &KeyConfig) -> Result<Vec<PathBuf>> {
+    let mut files = vec![PathBuf::from(&cfg.key_path)];
+    let ring = cfg.ring_dir();
+    if ring.exists() {
+        for ent in fs::read_dir(&ring).with_context(|| format!("read {:?}", ring))? {
+            let path = ent?.path();
+            if parse_ring_name(&path).is_some() {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Settle `.rewrap` files left by an interrupted passphrase change: renamed
+/// into place once the change was committed, otherwise removed, as the files
+/// they would replace are still complete. Returns the number of files renamed.
+fn finish_rewrap(cfg: &KeyConfig) -> Result<usize> {
+    let marker = commit_marker(cfg);
+    let commit = marker.exists();
+    let mut settled = 0;
+    for path in key_files(cfg)? {
+        let staged = path.with_extension("rewrap");
+        if !staged.exists() {
+            continue;
+        }
+        if commit {
+            fs::rename(&staged, &path).with_context(|| format!("rename {:?}", staged))?;
         } else {
-            // Wrapped key: use proper Argon2 KDF (same as init)
-            let salt = SaltString::generate(&mut rand::thread_rng());
-
-            let mut wrapping_key = [0u8; 32];
-            let argon = Argon2::default();
-            argon.hash_password_into(
-                cfg.passphrase.as_bytes(),
-                salt.as_str().as_bytes(),
-                &mut wrapping_key
-            ).map_err(|e| anyhow::anyhow!("Argon2 KDF failed: {:?}", e))?;
-
-            let wrapped = crate::aead::seal(&AeadKey(wrapping_key), b"navscout-keywrap", &new_key)?;
-
-            let mut file = String::new();
-            file.push_str("NAVSCOUT_KEYWRAP_V2\n");
-            file.push_str(salt.as_str());
-            file.push('\n');
-
-            let mut f = fs::File::create(&tmp)?;
-            f.write_all(file.as_bytes())?;
-            f.write_all(&wrapped)?;
-            f.flush()?;
+            fs::remove_file(&staged).with_context(|| format!("remove {:?}", staged))?;
         }
+        settled += 1;
+    }
+    if settled > 0 {
+        sync_dirs(cfg)?;
+    }
+    if commit {
+        fs::remove_file(&marker).with_context(|| format!("remove {:?}", marker))?;
+        sync_dirs(cfg)?;
+        return Ok(settled);
+    }
+    Ok(0)
+}
 
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            fs::set_permissions(&tmp, fs::Permissions::from_mode(0o600))?;
+/// Persist renames in the key directory and the keyring.
+fn sync_dirs(cfg: &KeyConfig) -> Result<()> {
+    let parent = Path::new(&cfg.key_path).parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::File::open(parent)?.sync_all()?;
+    let ring = cfg.ring_dir();
+    if ring.exists() {
+        fs::File::open(&ring)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Retirement time of a keyring file named `<key id>-<unix s>.key`.
+fn parse_ring_name(path: &Path) -> Option<u64> {
+    if path.extension()? != "key" {
+        return None;
+    }
+    let (id, t) = path.file_stem()?.to_str()?.split_once('-')?;
+    KeyId::parse(id).ok()?;
+    t.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(name: &str) -> KeyConfig {
+        let dir = std::env::temp_dir().join(format!("navscout-keys-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        KeyConfig {
+            key_path: dir.join("device.key").to_string_lossy().into(),
+            passphrase: String::from("navscout-test").into(),
+            keep_previous: DEFAULT_KEEP_PREVIOUS,
+            retire_after_days: DEFAULT_RETIRE_DAYS,
+            kdf: KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 },
         }
+    }
 
-        // Atomic replace
-        fs::rename(&tmp, path)?;
+    fn cleanup(cfg: &KeyConfig) {
+        fs::remove_dir_all(Path::new(&cfg.key_path).parent().unwrap()).unwrap();
+    }
 
-        // Zeroize old key material
-        zeroize::Zeroize::zeroize(&mut new_key);
+    #[test]
+    fn bound_blobs_open_only_with_the_telemetry_subkey() {
+        let cfg = config("subkey");
+        DeviceKeys::init(&cfg).unwrap();
+        let keys = DeviceKeys::load(&cfg).unwrap();
+        let header = keys.header("drone-1", [1; 16], 0, 7);
+        let blob = keys.telemetry().seal_bound(b"label", &header, b"frame").unwrap();
+        assert_eq!(keys.open_bound(b"label", &blob).unwrap(), (header.clone(), b"frame".to_vec()));
+        let by_master = aead::seal_bound(&keys.master, b"label", &header, b"frame").unwrap();
+        assert!(keys.open_bound(b"label", &by_master).is_err());
+        cleanup(&cfg);
+    }
 
-        Ok(())
+    #[test]
+    fn load_skips_unreadable_ring_files() {
+        let cfg = config("bad-ring");
+        DeviceKeys::init(&cfg).unwrap();
+        let old = DeviceKeys::load(&cfg).unwrap().key_id;
+        let new = DeviceKeys::rotate(&cfg).unwrap();
+        fs::write(cfg.ring_dir().join("0badc0de-1.key"), b"not a key").unwrap();
+        let keys = DeviceKeys::load(&cfg).unwrap();
+        assert_eq!(keys.key_id, new);
+        assert_eq!(keys.previous.iter().map(|k| k.id).collect::<Vec<_>>(), [old]);
+        cleanup(&cfg);
     }
 
-    pub fn load(cfg: &KeyConfig) -> Result<DeviceKeys> {
-        let path = Path::new(&cfg.key_path);
-        let bytes = fs::read(path).context("read key file")?;
-
-        if bytes.starts_with(b"NAVSCOUT_KEYWRAP_V2\n") {
-            anyhow::ensure!(!cfg.passphrase.is_empty(), "passphrase required for wrapped key");
-            // Parse header: magic + salt + wrapped blob
-            let mut parts = bytes.splitn(3, |b| *b == b'\n');
-            let _magic = parts.next().unwrap();
-            let salt_line = parts.next().context("bad key header")?;
-            let wrapped = parts.next().context("missing wrapped blob")?;
-
-            let salt_str = std::str::from_utf8(salt_line)?;
-            let salt = SaltString::from_b64(salt_str)
-                .map_err(|e| anyhow::anyhow!("Invalid salt: {:?}", e))?;
-
-            // Derive wrapping key using same Argon2 KDF
-            let mut wrapping_key = [0u8; 32];
-            let argon = Argon2::default();
-            argon.hash_password_into(
-                cfg.passphrase.as_bytes(),
-                salt.as_str().as_bytes(),
-                &mut wrapping_key
-            ).map_err(|e| anyhow::anyhow!("Argon2 KDF failed: {:?}", e))?;
-
-            // Decrypt master key
-            let key = crate::aead::open(&AeadKey(wrapping_key), b"navscout-keywrap", wrapped)?;
-            anyhow::ensure!(key.len() == 32, "bad key length");
-            let mut k = [0u8; 32];
-            k.copy_from_slice(&key);
-            Ok(DeviceKeys { aead: AeadKey(k) })
-        } else if bytes.starts_with(b"NAVSCOUT_KEYWRAP_V1\n") {
-            // Legacy format support (will be migrated on next rotation)
-            anyhow::ensure!(!cfg.passphrase.is_empty(), "passphrase required for wrapped key");
-            let mut parts = bytes.splitn(3, |b| *b == b'\n');
-            let _magic = parts.next().unwrap();
-            let hash_line = parts.next().context("bad key header")?;
-            let wrapped = parts.next().context("missing wrapped blob")?;
-
-            let hash_str = std::str::from_utf8(hash_line)?;
-            let parsed = PasswordHash::new(hash_str)
-                .map_err(|e| anyhow::anyhow!("Invalid password hash: {:?}", e))?;
-            Argon2::default().verify_password(cfg.passphrase.as_bytes(), &parsed)
-                .map_err(|e| anyhow::anyhow!("Passphrase verification failed: {:?}", e))?;
-
-            let wrapping = blake3::hash(hash_str.as_bytes()).as_bytes()[..32].try_into().unwrap();
-            let key = crate::aead::open(&AeadKey(wrapping), b"navscout-keywrap", wrapped)?;
-            anyhow::ensure!(key.len() == 32, "bad key length");
-            let mut k = [0u8; 32];
-            k.copy_from_slice(&key);
-            Ok(DeviceKeys { aead: AeadKey(k) })
-        } else {
-            anyhow::ensure!(bytes.len() == 32, "raw key file must be 32 bytes");
-            let mut k = [0u8; 32];
-            k.copy_from_slice(&bytes);
-            Ok(DeviceKeys { aead: AeadKey(k) })
+    /// Key, ring and staged files as a passphrase change crashing after
+    /// `done` renames would leave them; `committed` is whether the marker exists.
+    fn interrupted_change(name: &str, committed: bool, done: usize) -> KeyConfig {
+        let cfg = config(name);
+        DeviceKeys::init(&cfg).unwrap();
+        DeviceKeys::rotate(&cfg).unwrap();
+        DeviceKeys::rotate(&cfg).unwrap();
+        let keys = DeviceKeys::load(&cfg).unwrap();
+        let mut files = vec![(PathBuf::from(&cfg.key_path), &keys.master)];
+        files.extend(keys.previous.iter().map(|k| (k.path.clone(), &k.master)));
+        for (i, (path, key)) in files.iter().enumerate() {
+            let staged = path.with_extension("rewrap");
+            keywrap::write(&staged, key, "changed", &cfg.kdf).unwrap();
+            if i < done {
+                fs::rename(&staged, path).unwrap();
+            }
         }
+        if committed {
+            fs::File::create(commit_marker(&cfg)).unwrap();
+        }
+        cfg
+    }
+
+    fn staged_left(cfg: &KeyConfig) -> bool {
+        key_files(cfg).unwrap().iter().any(|p| p.with_extension("rewrap").exists()) || commit_marker(cfg).exists()
+    }
+
+    #[test]
+    fn interrupted_change_is_finished_once_committed() {
+        let cfg = interrupted_change("change-committed", true, 1);
+        let new = KeyConfig { passphrase: String::from("changed").into(), ..cfg.clone() };
+        let keys = DeviceKeys::load(&new).unwrap();
+        assert_eq!(keys.previous.len(), 2);
+        assert!(!staged_left(&cfg));
+        assert!(DeviceKeys::load(&cfg).is_err());
+        cleanup(&cfg);
+    }
+
+    #[test]
+    fn interrupted_change_is_dropped_before_commit() {
+        let cfg = interrupted_change("change-uncommitted", false, 0);
+        let keys = DeviceKeys::load(&cfg).unwrap();
+        assert_eq!(keys.previous.len(), 2);
+        assert!(!staged_left(&cfg));
+        cleanup(&cfg);
+    }
+
+    #[test]
+    fn change_passphrase_rewraps_everything() {
+        let cfg = config("change");
+        DeviceKeys::init(&cfg).unwrap();
+        DeviceKeys::rotate(&cfg).unwrap();
+        let before = DeviceKeys::load(&cfg).unwrap();
+        DeviceKeys::change_passphrase(&cfg, "changed").unwrap();
+        let after = DeviceKeys::load(&KeyConfig { passphrase: String::from("changed").into(), ..cfg.clone() }).unwrap();
+        assert_eq!((after.key_id, after.previous[0].id), (before.key_id, before.previous[0].id));
+        assert!(!staged_left(&cfg));
+        assert!(DeviceKeys::load(&cfg).is_err());
+        cleanup(&cfg);
     }
 }
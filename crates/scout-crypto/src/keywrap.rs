@@ -0,0 +1,251 @@
+// Key file formats. New files are raw (no passphrase) or V3:
+//
+//   NAVSCOUT_KEYWRAP_V3\n
+//   argon2id v=19 m=<KiB> t=<iterations> p=<lanes> salt=<hex>\n
+//   nonce | ct          (AAD: "navscout-keywrap-v3\n" | parameter line)
+//
+// V2 (salt only, `Argon2::default()` of whatever crate version) and V1
+// (PHC hash, wrapping key = blake3 of it) are still read; `keys rotate` and
+// `keys change-passphrase` rewrite them as V3.
+
+use anyhow::{Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version, password_hash::{SaltString, PasswordHash, PasswordVerifier}};
+use rand::RngCore;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::aead::AeadKey;
+
+const MAGIC_V3: &[u8] = b"NAVSCOUT_KEYWRAP_V3\n";
+const MAGIC_V2: &[u8] = b"NAVSCOUT_KEYWRAP_V2\n";
+const MAGIC_V1: &[u8] = b"NAVSCOUT_KEYWRAP_V1\n";
+/// Largest memory cost accepted from a key file (4 GiB).
+const MAX_MEMORY_KIB: u32 = 4 * 1024 * 1024;
+
+/// Argon2id cost of wrapping a key file under a passphrase. Tune per machine:
+/// unlocking should take around a second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self { memory_kib: Params::DEFAULT_M_COST, iterations: Params::DEFAULT_T_COST, parallelism: Params::DEFAULT_P_COST }
+    }
+}
+
+impl KdfParams {
+    /// Wrapping key for `passphrase`; Argon2's working memory is wiped after.
+    fn wrapping_key(&self, passphrase: &str, salt: &[u8]) -> Result<AeadKey> {
+        let argon2 = self.argon2()?;
+        AeadKey::try_fill(|k| argon2.hash_password_into(passphrase.as_bytes(), salt, k)
+            .map_err(|e| anyhow::anyhow!("Argon2 KDF failed: {:?}", e)))
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>> {
+        anyhow::ensure!(self.memory_kib <= MAX_MEMORY_KIB, "Argon2 memory cost {} KiB exceeds {} KiB", self.memory_kib, MAX_MEMORY_KIB);
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| anyhow::anyhow!("invalid Argon2 parameters {:?}: {}", self, e))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapFormat {
+    Raw,
+    V1,
+    V2,
+    V3,
+}
+
+/// Format of the key file at `path`.
+pub fn format(path: &Path) -> Result<WrapFormat> {
+    let bytes = fs::read(path).with_context(|| format!("read {:?}", path))?;
+    Ok(if bytes.starts_with(MAGIC_V3) {
+        WrapFormat::V3
+    } else if bytes.starts_with(MAGIC_V2) {
+        WrapFormat::V2
+    } else if bytes.starts_with(MAGIC_V1) {
+        WrapFormat::V1
+    } else {
+        WrapFormat::Raw
+    })
+}
+
+/// Write a key file readable by the owner only: raw if `passphrase` is empty,
+/// otherwise V3 under an Argon2id key derived with `kdf`. The file is written
+/// beside `path`, synced and renamed over it, so a crash leaves either the old
+/// file or the new one.
+pub fn write(path: &Path, key: &AeadKey, passphrase: &str, kdf: &KdfParams) -> Result<()> {
+    let mut contents = Zeroizing::new(Vec::new());
+    if passphrase.is_empty() {
+        contents.extend_from_slice(key.as_bytes());
+    } else {
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let params = format!("argon2id v=19 m={} t={} p={} salt={}",
+                             kdf.memory_kib, kdf.iterations, kdf.parallelism, hex::encode(salt));
+
+        let wrapping_key = kdf.wrapping_key(passphrase, &salt)?;
+        let wrapped = crate::aead::seal(&wrapping_key, &v3_aad(&params), key.as_bytes())?;
+
+        contents.extend_from_slice(MAGIC_V3);
+        contents.extend_from_slice(params.as_bytes());
+        contents.push(b'\n');
+        contents.extend_from_slice(&wrapped);
+    }
+
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name().with_context(|| format!("{:?} is not a file path", path))?;
+    let tmp = dir.join(format!(".{}.tmp", name.to_string_lossy()));
+    let mut opts = fs::OpenOptions::new();
+    opts.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.mode(0o600);
+    }
+    let mut f = opts.open(&tmp).with_context(|| format!("create {:?}", tmp))?;
+    #[cfg(unix)]
+    {
+        // A stale temp file keeps its old mode
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(0o600))?;
+    }
+    f.write_all(&contents)?;
+    f.sync_all()?;
+    drop(f);
+    fs::rename(&tmp, path).with_context(|| format!("rename {:?} to {:?}", tmp, path))?;
+    fs::File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+fn v3_aad(params: &str) -> Vec<u8> {
+    [b"navscout-keywrap-v3\n".as_slice(), params.as_bytes()].concat()
+}
+
+/// Split `magic | line \n | rest`.
+fn header_line<'a>(bytes: &'a [u8], magic: &[u8]) -> Result<(&'a str, &'a [u8])> {
+    let body = &bytes[magic.len()..];
+    let nl = body.iter().position(|b| *b == b'\n').context("bad key header")?;
+    Ok((std::str::from_utf8(&body[..nl])?, &body[nl + 1..]))
+}
+
+/// Parse `argon2id v=19 m=.. t=.. p=.. salt=..`.
+fn parse_v3_params(line: &str) -> Result<(KdfParams, Vec<u8>)> {
+    let mut it = line.split_whitespace();
+    let alg = it.next().context("empty key wrap parameters")?;
+    anyhow::ensure!(alg == "argon2id", "unsupported key wrap algorithm '{}'", alg);
+    let (mut v, mut m, mut t, mut p, mut salt) = (None, None, None, None, None);
+    for kv in it {
+        let (k, val) = kv.split_once('=').with_context(|| format!("bad key wrap parameter '{}'", kv))?;
+        let num = || val.parse::<u32>().with_context(|| format!("bad key wrap parameter '{}'", kv));
+        match k {
+            "v" => v = Some(num()?),
+            "m" => m = Some(num()?),
+            "t" => t = Some(num()?),
+            "p" => p = Some(num()?),
+            "salt" => salt = Some(hex::decode(val).with_context(|| format!("bad key wrap salt '{}'", val))?),
+            _ => anyhow::bail!("unknown key wrap parameter '{}'", k),
+        }
+    }
+    anyhow::ensure!(v == Some(19), "unsupported Argon2 version {:?}", v);
+    let kdf = KdfParams {
+        memory_kib: m.context("key wrap parameters lack m")?,
+        iterations: t.context("key wrap parameters lack t")?,
+        parallelism: p.context("key wrap parameters lack p")?,
+    };
+    let salt = salt.context("key wrap parameters lack salt")?;
+    anyhow::ensure!(salt.len() >= 8, "key wrap salt too short");
+    Ok((kdf, salt))
+}
+
+fn to_key(key: Zeroizing<Vec<u8>>) -> Result<AeadKey> {
+    anyhow::ensure!(key.len() == 32, "bad key length");
+    Ok(AeadKey::fill(|k| k.copy_from_slice(&key)))
+}
+
+/// The key in a file of any format. `testdata/keywrap` has one file per
+/// format, all holding the key 00 01 .. 1f under the passphrase
+/// `navscout-test`:
+///
+/// ```
+/// use scout_crypto::keywrap::{self, WrapFormat};
+/// let dir = std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/keywrap"));
+/// let want: [u8; 32] = std::array::from_fn(|i| i as u8);
+/// for (file, fmt) in [("raw.key", WrapFormat::Raw), ("v1.key", WrapFormat::V1), ("v2.key", WrapFormat::V2), ("v3.key", WrapFormat::V3)] {
+///     assert_eq!(keywrap::format(&dir.join(file)).unwrap(), fmt);
+///     assert_eq!(keywrap::read(&dir.join(file), "navscout-test").unwrap().as_bytes(), &want, "{}", file);
+/// }
+/// assert!(keywrap::read(&dir.join("v1.key"), "wrong").is_err());
+/// assert!(keywrap::read(&dir.join("v2.key"), "wrong").is_err());
+/// assert!(keywrap::read(&dir.join("v3.key"), "wrong").is_err());
+/// ```
+pub fn read(path: &Path, passphrase: &str) -> Result<AeadKey> {
+    let bytes = Zeroizing::new(fs::read(path).context("read key file")?);
+
+    if bytes.starts_with(MAGIC_V3) {
+        anyhow::ensure!(!passphrase.is_empty(), "passphrase required for wrapped key");
+        let (params, wrapped) = header_line(&bytes, MAGIC_V3)?;
+        let (kdf, salt) = parse_v3_params(params)?;
+        let wrapping_key = kdf.wrapping_key(passphrase, &salt)?;
+        let key = crate::aead::open(&wrapping_key, &v3_aad(params), wrapped)
+            .context("wrong passphrase or damaged key file")?;
+        to_key(Zeroizing::new(key))
+    } else if bytes.starts_with(MAGIC_V2) {
+        anyhow::ensure!(!passphrase.is_empty(), "passphrase required for wrapped key");
+        // Parse header: magic + salt + wrapped blob
+        let (salt_str, wrapped) = header_line(&bytes, MAGIC_V2)?;
+        let salt = SaltString::from_b64(salt_str)
+            .map_err(|e| anyhow::anyhow!("Invalid salt: {:?}", e))?;
+
+        // V2 used the crate's default Argon2 parameters
+        let wrapping_key = KdfParams::default().wrapping_key(passphrase, salt.as_str().as_bytes())?;
+        let key = crate::aead::open(&wrapping_key, b"navscout-keywrap", wrapped)?;
+        to_key(Zeroizing::new(key))
+    } else if bytes.starts_with(MAGIC_V1) {
+        anyhow::ensure!(!passphrase.is_empty(), "passphrase required for wrapped key");
+        let (hash_str, wrapped) = header_line(&bytes, MAGIC_V1)?;
+        let parsed = PasswordHash::new(hash_str)
+            .map_err(|e| anyhow::anyhow!("Invalid password hash: {:?}", e))?;
+        Argon2::default().verify_password(passphrase.as_bytes(), &parsed)
+            .map_err(|e| anyhow::anyhow!("Passphrase verification failed: {:?}", e))?;
+
+        let wrapping = AeadKey::fill(|k| k.copy_from_slice(blake3::hash(hash_str.as_bytes()).as_bytes()));
+        let key = crate::aead::open(&wrapping, b"navscout-keywrap", wrapped)?;
+        to_key(Zeroizing::new(key))
+    } else {
+        anyhow::ensure!(bytes.len() == 32, "raw key file must be 32 bytes");
+        to_key(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_replaces_whole_file() {
+        let dir = std::env::temp_dir().join(format!("navscout-keywrap-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("device.key");
+        let kdf = KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 };
+        let (first, second) = (AeadKey::generate(), AeadKey::generate());
+        write(&path, &first, "", &kdf).unwrap();
+        write(&path, &second, "navscout-test", &kdf).unwrap();
+        assert_eq!(read(&path, "navscout-test").unwrap().as_bytes(), second.as_bytes());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1, "temp file left behind");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
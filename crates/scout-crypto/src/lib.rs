@@ -1,3 +1,8 @@
 pub mod aead;
+pub mod csr;
 pub mod keys;
+pub mod keywrap;
+pub mod memory;
+pub mod passphrase;
+pub mod subkeys;
 pub mod doctor;
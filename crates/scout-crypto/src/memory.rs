@@ -0,0 +1,117 @@
+// Keeping key material out of swap and core dumps. Every `AeadKey` lives in
+// its own heap allocation; after `enable`, the pages under each key are
+// mlock(2)ed and marked MADV_DONTDUMP for as long as any key uses them. Best
+// effort: failing to lock one key is not an error. Linux only; elsewhere
+// `enable` fails and keys are just wiped on drop.
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Lock keys created from now on into RAM. Call before loading keys.
+pub fn enable() -> Result<()> {
+    imp::probe()?;
+    ENABLED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub(crate) fn lock(ptr: *const u8, len: usize) {
+    if enabled() {
+        imp::lock(ptr, len);
+    }
+}
+
+pub(crate) fn unlock(ptr: *const u8, len: usize) {
+    if enabled() {
+        imp::unlock(ptr, len);
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use anyhow::Result;
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    /// Keys using each locked page, by page address. Keys share pages, so a
+    /// page is unlocked only when its last key is dropped.
+    static PAGES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+    fn page_size() -> usize {
+        // SAFETY: sysconf has no memory-safety preconditions
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+
+    fn pages(ptr: *const u8, len: usize) -> impl Iterator<Item = usize> {
+        let ps = page_size();
+        let first = ptr as usize & !(ps - 1);
+        let last = (ptr as usize + len.max(1) - 1) & !(ps - 1);
+        (first..=last).step_by(ps)
+    }
+
+    pub fn probe() -> Result<()> {
+        let probe = Box::new([0u8; 32]);
+        let page = pages(probe.as_ptr(), 32).next().unwrap();
+        let users = PAGES.lock().unwrap();
+        if users.contains_key(&page) {
+            return Ok(());
+        }
+        // SAFETY: the page is mapped, it holds `probe`
+        if unsafe { libc::mlock(page as *const libc::c_void, page_size()) } != 0 {
+            anyhow::bail!("mlock: {} (raise RLIMIT_MEMLOCK / LimitMEMLOCK=)", std::io::Error::last_os_error());
+        }
+        // SAFETY: as above
+        unsafe { libc::munlock(page as *const libc::c_void, page_size()) };
+        Ok(())
+    }
+
+    pub fn lock(ptr: *const u8, len: usize) {
+        let mut users = PAGES.lock().unwrap();
+        for page in pages(ptr, len) {
+            let n = users.entry(page).or_insert(0);
+            if *n == 0 {
+                // SAFETY: the page is mapped, it holds part of a live key
+                unsafe {
+                    libc::mlock(page as *const libc::c_void, page_size());
+                    libc::madvise(page as *mut libc::c_void, page_size(), libc::MADV_DONTDUMP);
+                }
+            }
+            *n += 1;
+        }
+    }
+
+    pub fn unlock(ptr: *const u8, len: usize) {
+        let mut users = PAGES.lock().unwrap();
+        for page in pages(ptr, len) {
+            // Keys created before `enable` were never counted
+            let Some(n) = users.get_mut(&page) else { continue };
+            *n -= 1;
+            if *n == 0 {
+                users.remove(&page);
+                // SAFETY: the page is still mapped, the key being dropped is on it
+                unsafe {
+                    libc::munlock(page as *const libc::c_void, page_size());
+                    libc::madvise(page as *mut libc::c_void, page_size(), libc::MADV_DODUMP);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use anyhow::Result;
+
+    pub fn probe() -> Result<()> {
+        anyhow::bail!("locking key memory is only supported on Linux")
+    }
+
+    pub fn lock(_ptr: *const u8, _len: usize) {}
+
+    pub fn unlock(_ptr: *const u8, _len: usize) {}
+}
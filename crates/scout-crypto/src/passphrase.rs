@@ -0,0 +1,234 @@
+// Where the key file passphrase comes from. Keeping it out of the config file
+// that sits next to the key is the point; a literal `passphrase` still works
+// but `scout doctor` warns about it.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fmt;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+pub const DEFAULT_ENV: &str = "NAVSCOUT_PASSPHRASE";
+pub const DEFAULT_CREDENTIAL: &str = "navscout-passphrase";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PassphraseSource {
+    /// Raw, unwrapped key file
+    None,
+    /// `passphrase` from the config itself
+    Literal,
+    /// Environment variable `passphrase_env`
+    Env,
+    /// First line of `passphrase_file`, which must be 0600
+    File,
+    /// systemd credential `passphrase_credential` in `$CREDENTIALS_DIRECTORY`
+    Credential,
+    /// Ask on the terminal
+    Prompt,
+}
+
+/// The passphrase part of a `[crypto]` section (flattened into it).
+#[derive(Clone, Default, Deserialize)]
+pub struct PassphraseConfig {
+    /// Default `literal` if `passphrase` is set and non-empty, else `none`
+    pub passphrase_source: Option<PassphraseSource>,
+    pub passphrase: Option<String>,
+    /// Default NAVSCOUT_PASSPHRASE
+    pub passphrase_env: Option<String>,
+    pub passphrase_file: Option<String>,
+    /// Default navscout-passphrase (`LoadCredential=navscout-passphrase:...`)
+    pub passphrase_credential: Option<String>,
+}
+
+impl PassphraseConfig {
+    pub fn source(&self) -> PassphraseSource {
+        self.passphrase_source.unwrap_or(match self.passphrase.as_deref() {
+            Some(p) if !p.is_empty() => PassphraseSource::Literal,
+            _ => PassphraseSource::None,
+        })
+    }
+
+    /// The passphrase for `key_path`; empty means an unwrapped key. `confirm`
+    /// asks twice when prompting, for a passphrase that is being set.
+    pub fn resolve(&self, key_path: &str, confirm: bool) -> Result<Zeroizing<String>> {
+        let p = match self.source() {
+            PassphraseSource::None => return Ok(Zeroizing::default()),
+            PassphraseSource::Literal => Zeroizing::new(self.passphrase.clone().unwrap_or_default()),
+            PassphraseSource::Env => {
+                let var = self.env_var();
+                Zeroizing::new(std::env::var(var).with_context(|| format!("passphrase: environment variable {} not set", var))?)
+            }
+            PassphraseSource::File => {
+                let path = self.passphrase_file.as_deref().context("passphrase_source = \"file\" needs passphrase_file")?;
+                crate::doctor::check_key_file("crypto.passphrase_file", path)?;
+                first_line(Path::new(path))?
+            }
+            PassphraseSource::Credential => first_line(&self.credential_path()?)?,
+            PassphraseSource::Prompt => prompt(key_path, confirm)?,
+        };
+        anyhow::ensure!(!p.is_empty(), "passphrase from {:?} source is empty", self.source());
+        Ok(p)
+    }
+
+    fn env_var(&self) -> &str {
+        self.passphrase_env.as_deref().unwrap_or(DEFAULT_ENV)
+    }
+
+    fn credential_path(&self) -> Result<PathBuf> {
+        let dir = std::env::var_os("CREDENTIALS_DIRECTORY")
+            .context("passphrase: $CREDENTIALS_DIRECTORY not set (run under systemd with LoadCredential=)")?;
+        Ok(Path::new(&dir).join(self.passphrase_credential.as_deref().unwrap_or(DEFAULT_CREDENTIAL)))
+    }
+
+    /// Problems `resolve` would hit, without prompting or reading secrets into
+    /// memory, plus a literal passphrase left in the config.
+    pub fn check(&self) -> Result<()> {
+        match self.source() {
+            PassphraseSource::None | PassphraseSource::Prompt => {}
+            PassphraseSource::Literal => anyhow::bail!(
+                "crypto.passphrase is stored in the config next to the key; use passphrase_source = \"env\", \"file\", \"credential\" or \"prompt\""),
+            PassphraseSource::Env => anyhow::ensure!(std::env::var_os(self.env_var()).is_some(), "passphrase: {} not set", self.env_var()),
+            PassphraseSource::File => {
+                let path = self.passphrase_file.as_deref().context("passphrase_source = \"file\" needs passphrase_file")?;
+                crate::doctor::check_key_file("crypto.passphrase_file", path)?;
+            }
+            PassphraseSource::Credential => {
+                let path = self.credential_path()?;
+                anyhow::ensure!(path.exists(), "passphrase credential missing: {:?}", path);
+            }
+        }
+        if self.passphrase.as_deref().map(|p| !p.is_empty()).unwrap_or(false) && self.source() != PassphraseSource::Literal {
+            anyhow::bail!("crypto.passphrase is set but unused (passphrase_source = {:?}); remove it from the config", self.source());
+        }
+        Ok(())
+    }
+}
+
+// Redacts a literal `passphrase`
+impl fmt::Debug for PassphraseConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PassphraseConfig")
+            .field("passphrase_source", &self.passphrase_source)
+            .field("passphrase", &self.passphrase.as_ref().map(|_| ".."))
+            .field("passphrase_env", &self.passphrase_env)
+            .field("passphrase_file", &self.passphrase_file)
+            .field("passphrase_credential", &self.passphrase_credential)
+            .finish()
+    }
+}
+
+fn first_line(path: &Path) -> Result<Zeroizing<String>> {
+    let s = Zeroizing::new(std::fs::read_to_string(path).with_context(|| format!("read {:?}", path))?);
+    Ok(Zeroizing::new(s.lines().next().unwrap_or("").to_string()))
+}
+
+fn prompt(key_path: &str, confirm: bool) -> Result<Zeroizing<String>> {
+    anyhow::ensure!(std::io::stdin().is_terminal(), "passphrase_source = \"prompt\" needs an interactive terminal");
+    let p = Zeroizing::new(rpassword::prompt_password(format!("Passphrase for {}: ", key_path))?);
+    if confirm {
+        let again = Zeroizing::new(rpassword::prompt_password("Repeat passphrase: ")?);
+        anyhow::ensure!(p == again, "passphrases do not match");
+    }
+    Ok(p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("navscout-passphrase-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn config(source: PassphraseSource) -> PassphraseConfig {
+        PassphraseConfig { passphrase_source: Some(source), ..Default::default() }
+    }
+
+    /// A file readable by its owner only, as `check_key_file` wants.
+    fn private_file(path: &Path, data: &str) {
+        std::fs::write(path, data).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+    }
+
+    fn resolve(cfg: &PassphraseConfig) -> Result<String> {
+        cfg.resolve("device.key", false).map(|p| p.to_string())
+    }
+
+    #[test]
+    fn none_and_literal() {
+        assert_eq!(PassphraseConfig::default().source(), PassphraseSource::None);
+        assert_eq!(resolve(&PassphraseConfig::default()).unwrap(), "");
+        let literal = PassphraseConfig { passphrase: Some("in the config".into()), ..Default::default() };
+        assert_eq!(literal.source(), PassphraseSource::Literal);
+        assert_eq!(resolve(&literal).unwrap(), "in the config");
+        let empty = PassphraseConfig { passphrase: Some(String::new()), ..Default::default() };
+        assert_eq!(empty.source(), PassphraseSource::None);
+        assert!(resolve(&config(PassphraseSource::Literal)).is_err());
+    }
+
+    #[test]
+    fn env() {
+        let var = format!("NAVSCOUT_TEST_PASSPHRASE_{}", std::process::id());
+        let cfg = PassphraseConfig { passphrase_env: Some(var.clone()), ..config(PassphraseSource::Env) };
+        let err = format!("{:#}", resolve(&cfg).unwrap_err());
+        assert!(err.contains(&var) && err.contains("not set"), "{}", err);
+        std::env::set_var(&var, "from the environment");
+        assert_eq!(resolve(&cfg).unwrap(), "from the environment");
+        std::env::set_var(&var, "");
+        assert!(format!("{:#}", resolve(&cfg).unwrap_err()).contains("empty"));
+        std::env::remove_var(&var);
+    }
+
+    #[test]
+    fn file() {
+        let dir = scratch("file");
+        let path = dir.join("passphrase");
+        let cfg = PassphraseConfig { passphrase_file: Some(path.to_string_lossy().into()), ..config(PassphraseSource::File) };
+        assert!(resolve(&cfg).is_err());
+        private_file(&path, "first line\nsecond line\n");
+        assert_eq!(resolve(&cfg).unwrap(), "first line");
+        private_file(&path, "");
+        assert!(format!("{:#}", resolve(&cfg).unwrap_err()).contains("empty"));
+        private_file(&path, "\nafter a blank line\n");
+        assert!(format!("{:#}", resolve(&cfg).unwrap_err()).contains("empty"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            private_file(&path, "readable by others\n");
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+            assert!(format!("{:#}", resolve(&cfg).unwrap_err()).contains("permissions"));
+        }
+        assert!(resolve(&config(PassphraseSource::File)).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn credential() {
+        let dir = scratch("credential");
+        let cfg = PassphraseConfig { passphrase_credential: Some("test-passphrase".into()), ..config(PassphraseSource::Credential) };
+        private_file(&dir.join("test-passphrase"), "from systemd\n");
+        std::env::set_var("CREDENTIALS_DIRECTORY", &dir);
+        assert_eq!(resolve(&cfg).unwrap(), "from systemd");
+        assert!(resolve(&config(PassphraseSource::Credential)).is_err());
+        std::env::remove_var("CREDENTIALS_DIRECTORY");
+        assert!(format!("{:#}", resolve(&cfg).unwrap_err()).contains("CREDENTIALS_DIRECTORY"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prompt_needs_a_terminal() {
+        if std::io::stdin().is_terminal() {
+            return;
+        }
+        assert!(format!("{:#}", resolve(&config(PassphraseSource::Prompt)).unwrap_err()).contains("interactive terminal"));
+    }
+}
@@ -0,0 +1,140 @@
+// Purpose-specific subkeys of the device master key: HKDF-SHA256 with a fixed
+// salt and one fixed info label per purpose. Each purpose is its own type with
+// only the operations that purpose needs, so a key derived for one cannot be
+// handed to another. The master key itself is never used directly.
+
+use anyhow::Result;
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use crate::aead::{self, AeadKey, BlobHeader};
+
+const SALT: &[u8] = b"navscout-subkeys-v1";
+
+fn derive(master: &AeadKey, label: &[u8]) -> AeadKey {
+    AeadKey::fill(|okm| {
+        Hkdf::<Sha256>::new(Some(SALT), master.as_bytes())
+            .expand(label, okm)
+            .expect("32 bytes is a valid HKDF-SHA256 output length")
+    })
+}
+
+/// Seals telemetry batches for the uplink and the spool.
+#[derive(Clone)]
+pub struct TelemetryKey(AeadKey);
+
+impl TelemetryKey {
+    pub const LABEL: &'static [u8] = b"navscout/telemetry/v1";
+
+    pub fn derive(master: &AeadKey) -> Self {
+        Self(derive(master, Self::LABEL))
+    }
+
+    pub fn seal_bound(&self, label: &[u8], header: &BlobHeader, plaintext: &[u8]) -> Result<Vec<u8>> {
+        aead::seal_bound(&self.0, label, header, plaintext)
+    }
+
+    pub fn open_bound(&self, label: &[u8], blob: &[u8]) -> Result<(BlobHeader, Vec<u8>)> {
+        aead::open_bound(&self.0, label, blob)
+    }
+}
+
+/// Encrypts camera recordings at rest.
+#[derive(Clone)]
+pub struct RecordingKey(AeadKey);
+
+impl RecordingKey {
+    pub const LABEL: &'static [u8] = b"navscout/recordings/v1";
+
+    pub fn derive(master: &AeadKey) -> Self {
+        Self(derive(master, Self::LABEL))
+    }
+
+    pub fn seal(&self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        aead::seal(&self.0, aad, plaintext)
+    }
+
+    pub fn open(&self, aad: &[u8], blob: &[u8]) -> Result<Vec<u8>> {
+        aead::open(&self.0, aad, blob)
+    }
+}
+
+/// Encrypts logs at rest.
+#[derive(Clone)]
+pub struct LogKey(AeadKey);
+
+impl LogKey {
+    pub const LABEL: &'static [u8] = b"navscout/logs/v1";
+
+    pub fn derive(master: &AeadKey) -> Self {
+        Self(derive(master, Self::LABEL))
+    }
+
+    pub fn seal(&self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        aead::seal(&self.0, aad, plaintext)
+    }
+
+    pub fn open(&self, aad: &[u8], blob: &[u8]) -> Result<Vec<u8>> {
+        aead::open(&self.0, aad, blob)
+    }
+}
+
+/// MAVLink 2 message signing secret, shared with the flight controller.
+#[derive(Clone)]
+pub struct MavlinkSigningKey(AeadKey);
+
+impl MavlinkSigningKey {
+    pub const LABEL: &'static [u8] = b"navscout/mavlink-signing/v1";
+
+    pub fn derive(master: &AeadKey) -> Self {
+        Self(derive(master, Self::LABEL))
+    }
+
+    /// The secret as MAVLink's SETUP_SIGNING expects it.
+    pub fn secret(&self) -> &[u8; 32] {
+        self.0.as_bytes()
+    }
+}
+
+/// Authenticates messages between local processes (keyed BLAKE3).
+#[derive(Clone)]
+pub struct IpcAuthKey(AeadKey);
+
+impl IpcAuthKey {
+    pub const LABEL: &'static [u8] = b"navscout/ipc-auth/v1";
+
+    pub fn derive(master: &AeadKey) -> Self {
+        Self(derive(master, Self::LABEL))
+    }
+
+    pub fn tag(&self, msg: &[u8]) -> [u8; 32] {
+        *blake3::keyed_hash(self.0.as_bytes(), msg).as_bytes()
+    }
+
+    /// Constant-time check of a tag from `tag`.
+    pub fn verify(&self, msg: &[u8], tag: &[u8; 32]) -> bool {
+        blake3::keyed_hash(self.0.as_bytes(), msg) == blake3::Hash::from(*tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Known answers for master key 00 01 .. 1f. The vectors are fixed:
+    /// changing any of them orphans every key already derived.
+    #[test]
+    fn derivation_vectors() {
+        let master = AeadKey::fill(|k| *k = std::array::from_fn(|i| i as u8));
+        let vectors: [(&str, AeadKey, &str); 5] = [
+            ("telemetry", TelemetryKey::derive(&master).0, "7b6e7bf98bd9d2c8e86dbca28526786ccf1476c9845eb46c65b2436db39f8156"),
+            ("recordings", RecordingKey::derive(&master).0, "24b506a5f0bcb74e6b3494821aa43c157652aa80122a4e9d84c0efe4583545fe"),
+            ("logs", LogKey::derive(&master).0, "aec61258e5f741e8cb25f7d6ae7ddfd6a14939463920ecee5fb4df13a5d5f48d"),
+            ("mavlink signing", MavlinkSigningKey::derive(&master).0, "fb413a9039e9b467e7fca25baed77fd3b11daca4377d917859154d4769d083d8"),
+            ("ipc auth", IpcAuthKey::derive(&master).0, "89674cad224953bfe1fd7e0caeb1683879839eae4f3bdcf4ef2147b5473f8193"),
+        ];
+        for (what, got, want) in vectors {
+            assert_eq!(hex::encode(got.as_bytes()), want, "{} subkey", what);
+        }
+    }
+}
@@ -35,6 +35,7 @@ pub fn default_candidate_bauds() -> Vec<u32> {
     vec![57600, 115200, 230400, 921600]
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn autodetect_fc(
     candidate_devs: Vec<String>,
     candidate_bauds: Vec<u32>,
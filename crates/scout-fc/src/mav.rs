@@ -32,6 +32,7 @@ pub struct FcLink {
 }
 
 impl FcLink {
+    #[allow(clippy::too_many_arguments)]
     pub fn open(
         dev: &str,
         baud: u32,
@@ -52,7 +53,29 @@ impl FcLink {
         let conn = mavlink::connect::<MavMessage>(&url)
             .with_context(|| format!("mavlink connect {}", url))?;
 
-        Ok(Self {
+        Ok(Self::from_connection(
+            conn,
+            sys_id, comp_id,
+            target_sys, target_comp,
+            allow_rtl, allow_hold,
+            require_heartbeat,
+        ))
+    }
+
+    /// Wrap an already-open MAVLink connection (e.g. the SITL autopilot).
+    /// The same command gating applies as for a serial link.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_connection(
+        conn: Box<dyn MavConnection<MavMessage> + Send>,
+        sys_id: u8,
+        comp_id: u8,
+        target_sys: u8,
+        target_comp: u8,
+        allow_rtl: bool,
+        allow_hold: bool,
+        require_heartbeat: bool,
+    ) -> Self {
+        Self {
             conn,
             hdr: MavHeader { system_id: sys_id, component_id: comp_id, sequence: 0 },
             target_sys,
@@ -63,7 +86,7 @@ impl FcLink {
             allow_hold,
             require_heartbeat,
             battery: BatteryStatus::default(),
-        })
+        }
     }
 
     /// Best-effort: returns Ok(None) if recv fails.
@@ -131,7 +154,7 @@ impl FcLink {
         let cmd = COMMAND_LONG_DATA {
             target_system: self.target_sys,
             target_component: self.target_comp,
-            command: MavCmd::MAV_CMD_NAV_RETURN_TO_LAUNCH.into(),
+            command: MavCmd::MAV_CMD_NAV_RETURN_TO_LAUNCH,
             confirmation: 0,
             param1: 0.0,
             param2: 0.0,
@@ -160,7 +183,7 @@ impl FcLink {
         let cmd = COMMAND_LONG_DATA {
             target_system: self.target_sys,
             target_component: self.target_comp,
-            command: MavCmd::MAV_CMD_NAV_LOITER_UNLIM.into(),
+            command: MavCmd::MAV_CMD_NAV_LOITER_UNLIM,
             confirmation: 0,
             param1: 0.0,
             param2: 0.0,
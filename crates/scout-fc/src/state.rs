@@ -1,6 +1,6 @@
 use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct FcStatus {
     pub connected: bool,
     pub port: Option<String>,
@@ -9,18 +9,6 @@ pub struct FcStatus {
     pub last_msg: Option<String>,
 }
 
-impl Default for FcStatus {
-    fn default() -> Self {
-        Self {
-            connected: false,
-            port: None,
-            baud: None,
-            last_heartbeat: None,
-            last_msg: None,
-        }
-    }
-}
-
 impl FcStatus {
     pub fn hb_age(&self) -> Option<Duration> {
         self.last_heartbeat.map(|t| t.elapsed())
@@ -0,0 +1,554 @@
+//! Reference ground-station receiver: the server side of `scout_proto::link`.
+//! Terminates TLS, opens sealed blobs with the shared device key, appends the
+//! decoded envelopes to `<out_dir>/<device_id>.jsonl` and acks each frame
+//! once it is stored. Frames that can never be stored are rejected (see
+//! `link`); other failures end the session unacked, so the frame is re-sent.
+//! Each blob carries its device, boot and per-class sequence number (see
+//! `aead::BlobHeader`); replays are dropped using a `ReplayWindow` per class,
+//! persisted next to the JSONL as `<device_id>.replay`. With client
+//! certificates required, a session only stores frames from the device its
+//! certificate names (subject CN or DNS SAN).
+
+use anyhow::{Context, Result};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use rustls_pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use scout_crypto::aead::{self, BlobHeader, Replay, ReplayWindow};
+use scout_crypto::keys::DeviceKeys;
+use scout_proto::{codec, link, telemetry::Envelope};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroundConfig {
+    /// Listen address. Default 0.0.0.0:8443
+    pub listen: Option<String>,
+    /// PEM certificate chain presented to aircraft
+    pub cert_path: String,
+    /// PEM private key for `cert_path`
+    pub key_path: String,
+    /// Where `<device_id>.jsonl` files go. Default data/ground
+    pub out_dir: Option<String>,
+    /// PEM CA certificates for aircraft client certificates. When set, only
+    /// aircraft presenting a certificate issued by one of them can connect,
+    /// and only with frames from the device id the certificate names.
+    pub client_ca_path: Option<String>,
+    /// Accept frames sealed by builds without replay protection (no device or
+    /// sequence binding, so they can be replayed). Default false.
+    pub accept_legacy_frames: Option<bool>,
+}
+
+/// Frame ids remembered per device for dropping re-sent legacy frames.
+const RECENT_FRAMES: usize = 4096;
+
+pub async fn serve(cfg: &GroundConfig, keys: DeviceKeys) -> Result<()> {
+    let certs = CertificateDer::pem_file_iter(&cfg.cert_path)
+        .with_context(|| format!("read {}", cfg.cert_path))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("parse {}", cfg.cert_path))?;
+    anyhow::ensure!(!certs.is_empty(), "no certificates in {}", cfg.cert_path);
+    let key = PrivateKeyDer::from_pem_file(&cfg.key_path).with_context(|| format!("read {}", cfg.key_path))?;
+    let builder = match &cfg.client_ca_path {
+        Some(ca) => {
+            let mut roots = RootCertStore::empty();
+            for c in CertificateDer::pem_file_iter(ca).with_context(|| format!("read {}", ca))? {
+                roots.add(c.with_context(|| format!("parse {}", ca))?)?;
+            }
+            anyhow::ensure!(!roots.is_empty(), "no CA certificates in {}", ca);
+            info!("ground: requiring client certificates issued by {}", ca);
+            ServerConfig::builder().with_client_cert_verifier(WebPkiClientVerifier::builder(Arc::new(roots)).build()?)
+        }
+        None => ServerConfig::builder().with_no_client_auth(),
+    };
+    let tls = builder.with_single_cert(certs, key)?;
+    let acceptor = TlsAcceptor::from(Arc::new(tls));
+
+    let out_dir = PathBuf::from(cfg.out_dir.clone().unwrap_or_else(|| "data/ground".into()));
+    fs::create_dir_all(&out_dir).with_context(|| format!("create {:?}", out_dir))?;
+    let accept_legacy = cfg.accept_legacy_frames.unwrap_or(false);
+    if accept_legacy {
+        warn!("ground: accepting legacy frames, which are not protected against replay");
+    }
+    let store = Arc::new(Mutex::new(Store::new(out_dir.clone(), accept_legacy)));
+    let keys = Arc::new(keys);
+
+    let listen = cfg.listen.clone().unwrap_or_else(|| "0.0.0.0:8443".into());
+    let listener = TcpListener::bind(&listen).await.with_context(|| format!("bind {}", listen))?;
+    info!("ground: listening on {} (storing to {:?})", listen, out_dir);
+
+    loop {
+        let (tcp, peer) = listener.accept().await?;
+        let acceptor = acceptor.clone();
+        let keys = keys.clone();
+        let store = store.clone();
+        tokio::spawn(async move {
+            let result = async {
+                let tls = acceptor.accept(tcp).await.context("TLS handshake")?;
+                let client = match tls.get_ref().1.peer_certificates() {
+                    Some(chain) => Some(cert_identities(chain.first().context("empty client certificate chain")?)?),
+                    None => None,
+                };
+                session(tls, peer, client.as_deref(), &keys, &store).await
+            }.await;
+            match result {
+                Ok(()) => info!("ground: {} disconnected", peer),
+                Err(e) => warn!("ground: {} session ended: {:#}", peer, e),
+            }
+        });
+    }
+}
+
+/// Device ids a client certificate speaks for: its subject CNs and DNS SANs.
+pub fn cert_identities(cert: &CertificateDer) -> Result<Vec<String>> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert).map_err(|e| anyhow::anyhow!("client certificate: {}", e))?;
+    let mut names = Vec::new();
+    for cn in cert.subject().iter_common_name() {
+        names.push(cn.as_str().map_err(|e| anyhow::anyhow!("client certificate CN: {}", e))?.to_string());
+    }
+    if let Some(san) = cert.subject_alternative_name().map_err(|e| anyhow::anyhow!("client certificate SAN: {}", e))? {
+        for name in &san.value.general_names {
+            if let x509_parser::extensions::GeneralName::DNSName(n) = name {
+                names.push(n.to_string());
+            }
+        }
+    }
+    anyhow::ensure!(!names.is_empty(), "client certificate names no device (no CN or DNS SAN)");
+    Ok(names)
+}
+
+/// Serve one uplink session on an established stream until the peer disconnects.
+/// `client` holds the device ids of the peer's certificate, when it presented one.
+pub async fn session<S>(mut io: S, peer: SocketAddr, client: Option<&[String]>, keys: &Arc<DeviceKeys>, store: &Arc<Mutex<Store>>) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut hello = [0u8; 4];
+    io.read_exact(&mut hello).await.context("read hello")?;
+    anyhow::ensure!(&hello == link::HELLO, "not an uplink session (hello {:02x?})", hello);
+    match client {
+        Some(names) => info!("ground: {} session open as {}", peer, names.join(", ")),
+        None => info!("ground: {} session open", peer),
+    }
+    let client: Option<Arc<[String]>> = client.map(Arc::from);
+
+    loop {
+        let mut hdr = [0u8; link::FRAME_HEADER_LEN];
+        match io.read_exact(&mut hdr).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        let (id, len) = link::parse_frame_header(&hdr)?;
+        let mut blob = vec![0u8; len as usize];
+        io.read_exact(&mut blob).await.context("read frame")?;
+
+        // Replies are cumulative, so a frame that may still be stored on a
+        // later try ends the session unacked: the aircraft sends it again.
+        // Opening and the synced writes block, so they run off the runtime
+        let (store, keys, client) = (store.clone(), keys.clone(), client.clone());
+        let ingested = tokio::task::spawn_blocking(move || store.lock().unwrap().ingest(id, &blob, client.as_deref(), &keys))
+            .await?
+            .with_context(|| format!("frame {} not stored, closing without ack", id))?;
+        match ingested {
+            Ingested::Stored { device, events, gap } => {
+                info!("ground: {} frame {}: {} events from {}", peer, id, events, device);
+                if gap > 0 {
+                    warn!("ground: {} frame {}: {} earlier frames from {} missing", peer, id, gap, device);
+                }
+                io.write_all(&link::ack(id)).await?;
+            }
+            Ingested::Duplicate => {
+                info!("ground: {} frame {}: duplicate, dropped", peer, id);
+                io.write_all(&link::ack(id)).await?;
+            }
+            Ingested::Rejected(reason) => {
+                warn!("ground: {} frame {} rejected: {}", peer, id, reason);
+                io.write_all(&link::reject(id, &reason)).await?;
+            }
+        }
+        io.flush().await?;
+    }
+}
+
+/// Per-device JSONL files plus the replay state of each device.
+pub struct Store {
+    out_dir: PathBuf,
+    accept_legacy: bool,
+    files: HashMap<String, File>,
+    replay: HashMap<String, DeviceReplay>,
+    recent: HashMap<String, Recent>,
+}
+
+pub enum Ingested {
+    /// `gap` frames of the same class were skipped over (lost or evicted on
+    /// the aircraft, unless they still arrive late)
+    Stored { device: String, events: usize, gap: u64 },
+    Duplicate,
+    /// Can never be stored; the aircraft sets it aside
+    Rejected(String),
+}
+
+/// Last boot seen and a replay window per priority class, for one device.
+#[derive(Default)]
+struct DeviceReplay {
+    boot_id: [u8; 16],
+    windows: HashMap<u8, ReplayWindow>,
+}
+
+impl DeviceReplay {
+    // boot id [16] | (stream u8 | window)*
+    fn load(path: &std::path::Path) -> Result<Self> {
+        let b = match fs::read(path) {
+            Ok(b) => b,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(anyhow::Error::new(e).context(format!("read {:?}", path))),
+        };
+        anyhow::ensure!(b.len() >= 16, "{:?} is truncated", path);
+        let mut r = Self { boot_id: b[..16].try_into().unwrap(), windows: HashMap::new() };
+        let entry = 1 + ReplayWindow::new().to_bytes().len();
+        anyhow::ensure!((b.len() - 16) % entry == 0, "{:?} is truncated", path);
+        for e in b[16..].chunks_exact(entry) {
+            r.windows.insert(e[0], ReplayWindow::from_bytes(&e[1..]).with_context(|| format!("{:?}", path))?);
+        }
+        Ok(r)
+    }
+
+    fn save(&self, path: &std::path::Path) -> Result<()> {
+        let mut b = self.boot_id.to_vec();
+        for (stream, w) in &self.windows {
+            b.push(*stream);
+            b.extend_from_slice(&w.to_bytes());
+        }
+        let tmp = path.with_extension("replay.tmp");
+        let mut f = File::create(&tmp).with_context(|| format!("create {:?}", tmp))?;
+        f.write_all(&b)?;
+        f.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Recent {
+    ids: HashSet<u64>,
+    order: VecDeque<u64>,
+}
+
+impl Recent {
+    /// False if `id` was already seen.
+    fn insert(&mut self, id: u64) -> bool {
+        if !self.ids.insert(id) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > RECENT_FRAMES {
+            if let Some(old) = self.order.pop_front() {
+                self.ids.remove(&old);
+            }
+        }
+        true
+    }
+}
+
+impl Store {
+    pub fn new(out_dir: PathBuf, accept_legacy: bool) -> Self {
+        Self { out_dir, accept_legacy, files: HashMap::new(), replay: HashMap::new(), recent: HashMap::new() }
+    }
+
+    /// Open, decode and store one frame. Frames already stored are reported as
+    /// duplicates; frames that fail to open or decode, are too old for the
+    /// replay window, or come from a device other than the `client`
+    /// certificate names are rejected. Errors are failures that may clear on
+    /// retry.
+    pub fn ingest(&mut self, frame_id: u64, blob: &[u8], client: Option<&[String]>, keys: &DeviceKeys) -> Result<Ingested> {
+        if !aead::is_bound(blob) {
+            return self.ingest_legacy(frame_id, blob, client, keys);
+        }
+        let (hdr, envs) = match open_bound(blob, keys) {
+            Ok(f) => f,
+            Err(e) => return Ok(Ingested::Rejected(format!("{:#}", e))),
+        };
+        if let Err(e) = check_client(client, &hdr.device_id) {
+            return Ok(Ingested::Rejected(format!("{:#}", e)));
+        }
+        let device = file_stem(&hdr.device_id);
+
+        let path = self.out_dir.join(format!("{}.replay", device));
+        if !self.replay.contains_key(&device) {
+            self.replay.insert(device.clone(), DeviceReplay::load(&path)?);
+        }
+        let state = self.replay.get_mut(&device).unwrap();
+        match state.windows.get(&hdr.stream).map(|w| w.check(hdr.seq)).unwrap_or(Replay::Fresh) {
+            Replay::Fresh => {}
+            Replay::Duplicate => return Ok(Ingested::Duplicate),
+            Replay::Stale => return Ok(Ingested::Rejected(format!("{} stream {} seq {} is older than the replay window (replayed?)", device, hdr.stream, hdr.seq))),
+        }
+        note_boot(state, &hdr, &device);
+
+        self.append(&device, &envs)?;
+        let state = self.replay.get_mut(&device).unwrap();
+        let gap = state.windows.entry(hdr.stream).or_default().insert(hdr.seq);
+        state.save(&path)?;
+        Ok(Ingested::Stored { device, events: envs.len(), gap })
+    }
+
+    /// Frames sealed without a header: only re-sends are recognised, by frame id.
+    fn ingest_legacy(&mut self, frame_id: u64, blob: &[u8], client: Option<&[String]>, keys: &DeviceKeys) -> Result<Ingested> {
+        if !self.accept_legacy {
+            return Ok(Ingested::Rejected("legacy frame without replay protection (set accept_legacy_frames to accept)".into()));
+        }
+        let envs = match open_legacy(blob, keys) {
+            Ok(envs) => envs,
+            Err(e) => return Ok(Ingested::Rejected(format!("{:#}", e))),
+        };
+        if let Err(e) = envs.iter().try_for_each(|env| check_client(client, &env.device_id)) {
+            return Ok(Ingested::Rejected(format!("{:#}", e)));
+        }
+        let device = file_stem(&envs[0].device_id);
+        if !self.recent.entry(device.clone()).or_default().insert(frame_id) {
+            return Ok(Ingested::Duplicate);
+        }
+        self.append(&device, &envs)?;
+        Ok(Ingested::Stored { device, events: envs.len(), gap: 0 })
+    }
+
+    fn append(&mut self, device: &str, envs: &[Envelope]) -> Result<()> {
+        if !self.files.contains_key(device) {
+            let path = self.out_dir.join(format!("{}.jsonl", device));
+            let f = OpenOptions::new().create(true).append(true).open(&path).with_context(|| format!("open {:?}", path))?;
+            self.files.insert(device.to_string(), f);
+        }
+        let f = self.files.get_mut(device).unwrap();
+        let mut lines = Vec::new();
+        for env in envs {
+            serde_json::to_writer(&mut lines, env)?;
+            lines.push(b'\n');
+        }
+        f.write_all(&lines)?;
+        f.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Authenticate and decode a frame from `seal_bound`. Errors are final: the
+/// frame fails the same way every time (until the ground has its key).
+fn open_bound(blob: &[u8], keys: &DeviceKeys) -> Result<(BlobHeader, Vec<Envelope>)> {
+    let (hdr, frame) = keys.open_bound(link::TELEMETRY_AAD, blob).context("authentication failed (wrong key or tampered)")?;
+    let envs = codec::decode(&frame)?;
+    anyhow::ensure!(!envs.is_empty(), "empty frame");
+    anyhow::ensure!(envs.iter().all(|e| e.device_id == hdr.device_id), "envelopes not from sealing device {}", hdr.device_id);
+    Ok((hdr, envs))
+}
+
+/// `open_bound` for frames sealed without a header.
+fn open_legacy(blob: &[u8], keys: &DeviceKeys) -> Result<Vec<Envelope>> {
+    let frame = keys.open(link::TELEMETRY_AAD, blob).context("authentication failed (wrong key or tampered)")?;
+    let envs = codec::decode(&frame)?;
+    anyhow::ensure!(!envs.is_empty(), "empty frame");
+    Ok(envs)
+}
+
+/// A session authenticated by client certificate may only speak for its own device.
+fn check_client(client: Option<&[String]>, device_id: &str) -> Result<()> {
+    if let Some(names) = client {
+        anyhow::ensure!(names.iter().any(|n| n == device_id), "frame from {} but client certificate is for {}", device_id, names.join(", "));
+    }
+    Ok(())
+}
+
+/// Log when a device shows up with a new boot id. Frames from an earlier boot
+/// (still in the aircraft's spool) do not count as a reboot.
+fn note_boot(state: &mut DeviceReplay, hdr: &BlobHeader, device: &str) {
+    if state.boot_id == hdr.boot_id {
+        return;
+    }
+    let newest = state.windows.get(&hdr.stream).and_then(|w| w.highest());
+    if newest.map(|h| hdr.seq > h).unwrap_or(true) {
+        info!("ground: {} boot {}", device, hex::encode(hdr.boot_id));
+        state.boot_id = hdr.boot_id;
+    }
+}
+
+/// Device id made safe for a file name; legacy frames carry none.
+fn file_stem(device_id: &str) -> String {
+    if device_id.is_empty() {
+        return "unknown".into();
+    }
+    device_id.chars().map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scout_crypto::keys::KeyConfig;
+    use scout_crypto::keywrap::KdfParams;
+    use scout_proto::codec::Format;
+    use scout_proto::link::Reply;
+    use scout_proto::telemetry::{Payload, Shutdown, SCHEMA_VERSION};
+    use std::path::Path;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("navscout-ground-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn keys(dir: &Path) -> Arc<DeviceKeys> {
+        let cfg = KeyConfig {
+            key_path: dir.join("device.key").to_string_lossy().into(),
+            passphrase: String::new().into(),
+            keep_previous: 4,
+            retire_after_days: 30,
+            kdf: KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 },
+        };
+        DeviceKeys::init(&cfg).unwrap();
+        Arc::new(DeviceKeys::load(&cfg).unwrap())
+    }
+
+    /// One link frame carrying a single envelope, sealed as `keys` would seal it.
+    fn frame(keys: &DeviceKeys, id: u64, seq: u64) -> Vec<u8> {
+        let env = Envelope { v: SCHEMA_VERSION, device_id: "drone-1".into(), seq, ts_unix_ms: 1000 + seq as i64,
+            payload: Payload::Shutdown(Shutdown { reason: format!("frame {}", id) }) };
+        let plain = codec::encode(Format::Json, &[env]).unwrap();
+        let blob = keys.telemetry().seal_bound(link::TELEMETRY_AAD, &keys.header("drone-1", [7; 16], 0, seq), &plain).unwrap();
+        [link::frame_header(id, blob.len()).unwrap().to_vec(), blob].concat()
+    }
+
+    /// Run a session over `frames`; returns how it ended and the replies.
+    async fn exchange(frames: &[Vec<u8>], client: Option<&[String]>, keys: &Arc<DeviceKeys>, store: &Arc<Mutex<Store>>) -> (Result<()>, Vec<Reply>) {
+        let (mut aircraft, ground) = tokio::io::duplex(1 << 20);
+        aircraft.write_all(link::HELLO).await.unwrap();
+        for f in frames {
+            aircraft.write_all(f).await.unwrap();
+        }
+        aircraft.shutdown().await.unwrap();
+        let result = session(ground, "127.0.0.1:1".parse().unwrap(), client, keys, store).await;
+        let mut buf = Vec::new();
+        aircraft.read_to_end(&mut buf).await.unwrap();
+        let mut replies = Vec::new();
+        let mut at = 0;
+        while let Some((reply, len)) = link::parse_reply(&buf[at..]).unwrap() {
+            replies.push(reply);
+            at += len;
+        }
+        assert_eq!(at, buf.len());
+        (result, replies)
+    }
+
+    fn lines(dir: &Path) -> usize {
+        fs::read_to_string(dir.join("drone-1.jsonl")).map(|s| s.lines().count()).unwrap_or(0)
+    }
+
+    #[tokio::test]
+    async fn stored_and_duplicate_frames_are_acked() {
+        let dir = scratch("stored");
+        let keys = keys(&dir);
+        let store = Arc::new(Mutex::new(Store::new(dir.clone(), false)));
+        let (result, replies) = exchange(&[frame(&keys, 1, 1), frame(&keys, 2, 2), frame(&keys, 3, 1)], None, &keys, &store).await;
+        result.unwrap();
+        assert_eq!(replies, [Reply::Ack(1), Reply::Ack(2), Reply::Ack(3)]);
+        assert_eq!(lines(&dir), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unknown_key_is_rejected_and_session_carries_on() {
+        let dir = scratch("unknown-key");
+        let keys = keys(&dir);
+        let other_dir = scratch("unknown-key-other");
+        let other = self::keys(&other_dir);
+        let store = Arc::new(Mutex::new(Store::new(dir.clone(), false)));
+        let (result, replies) = exchange(&[frame(&keys, 1, 1), frame(&other, 2, 2), frame(&keys, 3, 3)], None, &keys, &store).await;
+        result.unwrap();
+        assert_eq!(replies[0], Reply::Ack(1));
+        assert!(matches!(&replies[1], Reply::Reject { id: 2, reason } if reason.contains("unknown key")));
+        assert_eq!(replies[2], Reply::Ack(3));
+        assert_eq!(lines(&dir), 2);
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(&other_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn legacy_frames_are_rejected_unless_accepted() {
+        let dir = scratch("legacy");
+        let keys = keys(&dir);
+        let blob = vec![0u8; 40];
+        let legacy = [link::frame_header(5, blob.len()).unwrap().to_vec(), blob].concat();
+        let store = Arc::new(Mutex::new(Store::new(dir.clone(), false)));
+        let (result, replies) = exchange(&[legacy, frame(&keys, 6, 1)], None, &keys, &store).await;
+        result.unwrap();
+        assert!(matches!(&replies[0], Reply::Reject { id: 5, reason } if reason.contains("accept_legacy_frames")));
+        assert_eq!(replies[1], Reply::Ack(6));
+        assert_eq!(lines(&dir), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stale_frames_are_rejected() {
+        let dir = scratch("stale");
+        let keys = keys(&dir);
+        let store = Arc::new(Mutex::new(Store::new(dir.clone(), false)));
+        let newest = aead::REPLAY_WINDOW + 10;
+        let (result, replies) = exchange(&[frame(&keys, 1, newest), frame(&keys, 2, 9), frame(&keys, 3, newest + 1)], None, &keys, &store).await;
+        result.unwrap();
+        assert_eq!(replies[0], Reply::Ack(1));
+        assert!(matches!(&replies[1], Reply::Reject { id: 2, reason } if reason.contains("older than the replay window")));
+        assert_eq!(replies[2], Reply::Ack(3));
+        assert_eq!(lines(&dir), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn frames_must_match_client_certificate() {
+        let dir = scratch("client");
+        let keys = keys(&dir);
+        let store = Arc::new(Mutex::new(Store::new(dir.clone(), false)));
+        let own = ["drone-1".to_string()];
+        let (result, replies) = exchange(&[frame(&keys, 1, 1)], Some(&own), &keys, &store).await;
+        result.unwrap();
+        assert_eq!(replies, [Reply::Ack(1)]);
+        let other = ["drone-2".to_string()];
+        let (result, replies) = exchange(&[frame(&keys, 2, 2), frame(&keys, 3, 3)], Some(&other), &keys, &store).await;
+        result.unwrap();
+        assert!(matches!(&replies[0], Reply::Reject { id: 2, reason } if reason.contains("client certificate is for drone-2")));
+        assert!(matches!(&replies[1], Reply::Reject { id: 3, .. }));
+        assert_eq!(lines(&dir), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn identities_from_cn_and_dns_san() {
+        let key = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(vec!["drone-1.fleet".to_string()]).unwrap();
+        params.distinguished_name = rcgen::DistinguishedName::new();
+        params.distinguished_name.push(rcgen::DnType::CommonName, "drone-1");
+        let cert = params.self_signed(&key).unwrap();
+        assert_eq!(cert_identities(cert.der()).unwrap(), ["drone-1", "drone-1.fleet"]);
+
+        let mut params = rcgen::CertificateParams::new(vec![]).unwrap();
+        params.distinguished_name = rcgen::DistinguishedName::new();
+        let cert = params.self_signed(&key).unwrap();
+        assert!(cert_identities(cert.der()).is_err());
+    }
+
+    #[tokio::test]
+    async fn write_failure_ends_session_unacked() {
+        let dir = scratch("io");
+        let keys = keys(&dir);
+        let store = Arc::new(Mutex::new(Store::new(dir.join("missing"), false)));
+        let (result, replies) = exchange(&[frame(&keys, 1, 1)], None, &keys, &store).await;
+        assert!(result.is_err());
+        assert!(replies.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
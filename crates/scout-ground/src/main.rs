@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+
+use scout_crypto::keys::{self, DeviceKeys, KeyConfig};
+use scout_ground::GroundConfig;
+
+#[derive(Debug, Parser)]
+#[command(name = "scout-ground", version, about = "NAVscout reference ground-station receiver")]
+struct Cli {
+    #[arg(long)]
+    config: String,
+
+    #[command(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Accept uplink sessions, store telemetry per device and ack it.
+    Serve,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Config {
+    crypto: CryptoCfg,
+    ground: GroundConfig,
+}
+
+/// Same key file (and passphrase) as the aircraft's [crypto] section; copy its
+/// `.ring` directory along so data sealed before a rotation still opens.
+#[derive(Debug, serde::Deserialize)]
+struct CryptoCfg {
+    key_path: String,
+    #[serde(flatten)]
+    passphrase: scout_crypto::passphrase::PassphraseConfig,
+    /// mlock key memory and keep it out of core dumps (Linux). Default false
+    lock_memory: Option<bool>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let cli = Cli::parse();
+    let s = std::fs::read_to_string(&cli.config).context("read config")?;
+    let cfg: Config = toml::from_str(&s).context("parse config toml")?;
+    if cfg.crypto.lock_memory.unwrap_or(false) {
+        if let Err(e) = scout_crypto::memory::enable() {
+            tracing::warn!("ground: key memory not locked: {:#}", e);
+        }
+    }
+
+    match cli.cmd {
+        Command::Serve => {
+            let keys = DeviceKeys::load(&KeyConfig {
+                key_path: cfg.crypto.key_path.clone(),
+                passphrase: cfg.crypto.passphrase.resolve(&cfg.crypto.key_path, false)?,
+                keep_previous: keys::DEFAULT_KEEP_PREVIOUS,
+                retire_after_days: keys::DEFAULT_RETIRE_DAYS,
+                kdf: Default::default(),
+            })?;
+            tracing::info!("ground: device key {} ({} previous)", keys.key_id, keys.previous.len());
+            tokio::select! {
+                r = scout_ground::serve(&cfg.ground, keys) => r?,
+                _ = tokio::signal::ctrl_c() => tracing::info!("ground: shutting down"),
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,18 +1,309 @@
 use anyhow::Result;
-use crate::nav::{Home, RouteCfg, ZoneCfg};
+use std::fmt;
+use tracing::warn;
+
+use crate::geo::{self, Corridor, LocalFrame, LocalPolygon};
+use crate::nav::{Home, Point, RouteCfg, ZoneCfg};
+
+/// Two vertices closer than this are considered duplicates.
+const DUPLICATE_EPS_M: f64 = 0.05;
+/// Polygons with less area than this are considered degenerate.
+const MIN_ZONE_AREA_M2: f64 = 1.0;
 
 pub fn check_gnss_thresholds(min_sats: u8, max_hdop: f32, max_fix_age_s: u64) -> Result<()> {
     anyhow::ensure!(min_sats >= 4, "gnss.min_sats too low");
     anyhow::ensure!(max_hdop > 0.5 && max_hdop < 5.0, "gnss.max_hdop out of range");
-    anyhow::ensure!(max_fix_age_s >= 1 && max_fix_age_s <= 10, "gnss.max_fix_age_s should be 1..10");
+    anyhow::ensure!((1..=10).contains(&max_fix_age_s), "gnss.max_fix_age_s should be 1..10");
     Ok(())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeofenceIssue {
+    pub severity: Severity,
+    /// Config path of the offending item, e.g. `nav.zone.garden_polygon[2]`
+    pub item: String,
+    pub msg: String,
+}
+
+impl fmt::Display for GeofenceIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sev = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}: {}", sev, self.item, self.msg)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GeofenceReport {
+    pub issues: Vec<GeofenceIssue>,
+}
+
+impl GeofenceReport {
+    pub fn errors(&self) -> impl Iterator<Item = &GeofenceIssue> {
+        self.issues.iter().filter(|i| i.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &GeofenceIssue> {
+        self.issues.iter().filter(|i| i.severity == Severity::Warning)
+    }
+
+    fn error(&mut self, item: impl Into<String>, msg: impl Into<String>) {
+        self.issues.push(GeofenceIssue { severity: Severity::Error, item: item.into(), msg: msg.into() });
+    }
+
+    fn warning(&mut self, item: impl Into<String>, msg: impl Into<String>) {
+        self.issues.push(GeofenceIssue { severity: Severity::Warning, item: item.into(), msg: msg.into() });
+    }
+}
+
+/// Runs `geofence_report`, logs warnings and fails with every error itemized.
 pub fn check_geofence(home: &Home, route: &RouteCfg, zone: &ZoneCfg, max_radius_m: f64) -> Result<()> {
-    anyhow::ensure!(route.waypoints.len() >= 2, "nav.route.waypoints must have >= 2 points");
-    anyhow::ensure!(zone.garden_polygon.len() >= 3, "nav.zone.garden_polygon must have >= 3 points");
-    anyhow::ensure!(max_radius_m >= 50.0, "nav.max_radius_m too small");
-    // Basic sanity: home not NaN
-    anyhow::ensure!(home.lat.abs() <= 90.0 && home.lon.abs() <= 180.0, "home coordinates invalid");
+    let report = geofence_report(home, route, zone, max_radius_m);
+    for w in report.warnings() {
+        warn!("geofence {}", w);
+    }
+    let errors: Vec<String> = report.errors().map(|e| format!("  {}", e)).collect();
+    anyhow::ensure!(errors.is_empty(), "geofence check failed:\n{}", errors.join("\n"));
     Ok(())
 }
+
+pub fn geofence_report(home: &Home, route: &RouteCfg, zone: &ZoneCfg, max_radius_m: f64) -> GeofenceReport {
+    let mut r = GeofenceReport::default();
+    let wps = &route.waypoints;
+    let poly = &zone.garden_polygon;
+
+    // Basic sanity first: the geometric checks below assume these hold
+    if !(home.lat.abs() <= 90.0 && home.lon.abs() <= 180.0) {
+        r.error("nav.home", "home coordinates invalid");
+        return r;
+    }
+    if max_radius_m < 50.0 {
+        r.error("nav.max_radius_m", "nav.max_radius_m too small");
+    }
+    if route.corridor_width_m <= 0.0 {
+        r.error("nav.route.corridor_width_m", "corridor width must be > 0");
+    }
+    for (i, p) in wps.iter().enumerate() {
+        if !(p.lat.abs() <= 90.0 && p.lon.abs() <= 180.0) {
+            r.error(format!("nav.route.waypoints[{}]", i), "coordinates invalid");
+        }
+    }
+    for (i, p) in poly.iter().enumerate() {
+        if !(p.lat.abs() <= 90.0 && p.lon.abs() <= 180.0) {
+            r.error(format!("nav.zone.garden_polygon[{}]", i), "coordinates invalid");
+        }
+    }
+    if wps.len() < 2 {
+        r.error("nav.route.waypoints", "must have >= 2 points");
+    }
+    if poly.len() < 3 {
+        r.error("nav.zone.garden_polygon", "must have >= 3 points");
+    }
+    if r.errors().next().is_some() {
+        return r;
+    }
+
+    // Everything below works in the mission's local ENU frame (metres) anchored at home
+    let home_pt = Point { lat: home.lat, lon: home.lon };
+    let frame = LocalFrame::new(&home_pt);
+    let corridor = Corridor::new(&frame, wps, route.corridor_width_m);
+    let zone_poly = LocalPolygon::new(&frame, poly);
+    let route_xy = &corridor.centerline;
+    let poly_xy = &zone_poly.vertices;
+    let half_w = corridor.half_width_m;
+
+    // Max radius
+    for (i, p) in wps.iter().enumerate() {
+        let d = geo::distance_m(&home_pt, p);
+        if d > max_radius_m {
+            r.error(format!("nav.route.waypoints[{}]", i), format!("{:.0}m from home exceeds max_radius_m ({:.0}m)", d, max_radius_m));
+        }
+    }
+    for (i, p) in poly.iter().enumerate() {
+        let d = geo::distance_m(&home_pt, p);
+        if d > max_radius_m {
+            r.error(format!("nav.zone.garden_polygon[{}]", i), format!("{:.0}m from home exceeds max_radius_m ({:.0}m)", d, max_radius_m));
+        }
+    }
+
+    // Zone polygon shape
+    let n = poly_xy.len();
+    for i in 0..n {
+        for j in (i + 1)..n {
+            if geo::dist(poly_xy[i], poly_xy[j]) < DUPLICATE_EPS_M {
+                r.error(format!("nav.zone.garden_polygon[{}]", j), format!("duplicate of vertex {}", i));
+            }
+        }
+    }
+    for i in 0..n {
+        for j in (i + 1)..n {
+            // Adjacent edges share a vertex; skip them (including the wrap-around pair)
+            if j == i + 1 || (i == 0 && j == n - 1) { continue; }
+            let (a, b) = (poly_xy[i], poly_xy[(i + 1) % n]);
+            let (c, d) = (poly_xy[j], poly_xy[(j + 1) % n]);
+            if geo::segments_intersect(a, b, c, d) {
+                r.error(
+                    "nav.zone.garden_polygon",
+                    format!("self-intersecting: edge {}-{} crosses edge {}-{}", i, (i + 1) % n, j, (j + 1) % n),
+                );
+            }
+        }
+    }
+    let area = zone_poly.signed_area().abs();
+    if area < MIN_ZONE_AREA_M2 {
+        r.error("nav.zone.garden_polygon", format!("zero-area polygon ({:.2} m2)", area));
+    }
+
+    // Route vs home / zone
+    let home_margin = corridor.signed_margin_m(frame.to_enu(&home_pt));
+    if home_margin < 0.0 {
+        r.error(
+            "nav.route.waypoints[0]",
+            format!("route does not start at home: home is {:.0}m outside the corridor (half-width {:.0}m)", -home_margin, half_w),
+        );
+    }
+
+    let last = route_xy.len() - 1;
+    if !zone_poly.contains(route_xy[last]) {
+        r.error(format!("nav.route.waypoints[{}]", last), "last waypoint does not reach the zone (outside garden_polygon)");
+    }
+
+    let corridor_meets_zone = route_xy.windows(2).any(|s| {
+        zone_poly.edges().any(|(a, b)| geo::segment_distance(s[0], s[1], a, b) <= half_w)
+    }) || route_xy.iter().any(|p| zone_poly.contains(*p));
+    if !corridor_meets_zone {
+        r.error("nav.route", "corridor never intersects the zone");
+    }
+
+    for (i, s) in route_xy.windows(2).enumerate() {
+        let len = geo::dist(s[0], s[1]);
+        if len < route.corridor_width_m {
+            r.warning(
+                format!("nav.route.waypoints[{}..={}]", i, i + 1),
+                format!("segment is {:.1}m, shorter than corridor width ({:.0}m)", len, route.corridor_width_m),
+            );
+        }
+    }
+
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo::Enu;
+
+    /// Mission home; fixtures are laid out in metres east/north of it.
+    const HOME: Point = Point { lat: 48.0, lon: 2.0 };
+
+    fn at(e: f64, n: f64) -> Point {
+        LocalFrame::new(&HOME).to_point(Enu { e, n })
+    }
+
+    fn home() -> Home {
+        Home { lat: HOME.lat, lon: HOME.lon, alt_m: 0.0 }
+    }
+
+    /// Straight north 200 m into a 60 m square zone.
+    fn route() -> RouteCfg {
+        RouteCfg { corridor_width_m: 20.0, waypoints: vec![at(0.0, 0.0), at(0.0, 100.0), at(0.0, 200.0)] }
+    }
+
+    fn zone() -> ZoneCfg {
+        ZoneCfg { garden_polygon: vec![at(-30.0, 170.0), at(30.0, 170.0), at(30.0, 230.0), at(-30.0, 230.0)] }
+    }
+
+    fn errors(r: &GeofenceReport) -> Vec<String> {
+        r.errors().map(|e| e.to_string()).collect()
+    }
+
+    fn has(r: &GeofenceReport, needle: &str) -> bool {
+        r.issues.iter().any(|i| i.to_string().contains(needle))
+    }
+
+    #[test]
+    fn valid_mission_passes() {
+        let r = geofence_report(&home(), &route(), &zone(), 500.0);
+        assert!(r.issues.is_empty(), "{:?}", r.issues);
+        check_geofence(&home(), &route(), &zone(), 500.0).unwrap();
+    }
+
+    #[test]
+    fn basic_sanity() {
+        let bad_home = Home { lat: 91.0, ..home() };
+        assert_eq!(errors(&geofence_report(&bad_home, &route(), &zone(), 500.0)), ["error: nav.home: home coordinates invalid"]);
+
+        let short = RouteCfg { corridor_width_m: 0.0, waypoints: vec![at(0.0, 0.0)] };
+        let tiny = ZoneCfg { garden_polygon: vec![at(0.0, 0.0), Point { lat: 48.0, lon: 200.0 }] };
+        let r = geofence_report(&home(), &short, &tiny, 10.0);
+        assert!(has(&r, "nav.max_radius_m"));
+        assert!(has(&r, "corridor width must be > 0"));
+        assert!(has(&r, "nav.route.waypoints: must have >= 2 points"));
+        assert!(has(&r, "nav.zone.garden_polygon[1]: coordinates invalid"));
+        assert!(has(&r, "nav.zone.garden_polygon: must have >= 3 points"));
+    }
+
+    #[test]
+    fn max_radius_is_per_item() {
+        let r = geofence_report(&home(), &route(), &zone(), 210.0);
+        // Only the far corners of the zone (~232 m) are out of reach
+        assert_eq!(errors(&r).len(), 2, "{:?}", r.issues);
+        assert!(has(&r, "nav.zone.garden_polygon[2]: 232m from home exceeds max_radius_m (210m)"));
+        assert!(has(&r, "nav.zone.garden_polygon[3]"));
+    }
+
+    #[test]
+    fn zone_shape() {
+        // Bow tie: edges 0-1 and 2-3 cross
+        let bow = ZoneCfg { garden_polygon: vec![at(-30.0, 170.0), at(30.0, 230.0), at(30.0, 170.0), at(-30.0, 230.0)] };
+        let r = geofence_report(&home(), &route(), &bow, 500.0);
+        assert!(has(&r, "self-intersecting: edge 0-1 crosses edge 2-3"), "{:?}", r.issues);
+
+        let dup = ZoneCfg { garden_polygon: vec![at(-30.0, 170.0), at(30.0, 170.0), at(30.0, 170.01), at(30.0, 230.0), at(-30.0, 230.0)] };
+        assert!(has(&geofence_report(&home(), &route(), &dup, 500.0), "nav.zone.garden_polygon[2]: duplicate of vertex 1"));
+
+        let flat = ZoneCfg { garden_polygon: vec![at(0.0, 170.0), at(0.0, 200.0), at(0.0, 230.0)] };
+        assert!(has(&geofence_report(&home(), &route(), &flat, 500.0), "zero-area polygon"));
+    }
+
+    #[test]
+    fn route_must_link_home_and_zone() {
+        let offset = RouteCfg { corridor_width_m: 20.0, waypoints: vec![at(50.0, 0.0), at(50.0, 150.0), at(0.0, 200.0)] };
+        let r = geofence_report(&home(), &offset, &zone(), 500.0);
+        assert!(has(&r, "route does not start at home: home is 40m outside the corridor (half-width 10m)"), "{:?}", r.issues);
+
+        let elsewhere = ZoneCfg { garden_polygon: vec![at(100.0, 170.0), at(160.0, 170.0), at(160.0, 230.0), at(100.0, 230.0)] };
+        let r = geofence_report(&home(), &route(), &elsewhere, 500.0);
+        assert!(has(&r, "nav.route.waypoints[2]: last waypoint does not reach the zone"));
+        assert!(has(&r, "nav.route: corridor never intersects the zone"));
+
+        // A corridor that grazes the zone still meets it
+        let near = ZoneCfg { garden_polygon: vec![at(8.0, 170.0), at(60.0, 170.0), at(60.0, 230.0), at(8.0, 230.0)] };
+        assert!(!has(&geofence_report(&home(), &route(), &near, 500.0), "never intersects"));
+    }
+
+    #[test]
+    fn short_segments_only_warn() {
+        let route = RouteCfg { corridor_width_m: 20.0, waypoints: vec![at(0.0, 0.0), at(0.0, 190.0), at(0.0, 200.0)] };
+        let r = geofence_report(&home(), &route, &zone(), 500.0);
+        assert!(errors(&r).is_empty(), "{:?}", r.issues);
+        let w: Vec<_> = r.warnings().map(|w| w.to_string()).collect();
+        assert_eq!(w, ["warning: nav.route.waypoints[1..=2]: segment is 10.0m, shorter than corridor width (20m)"]);
+        check_geofence(&home(), &route, &zone(), 500.0).unwrap();
+    }
+
+    #[test]
+    fn check_lists_every_error() {
+        let err = check_geofence(&home(), &route(), &zone(), 210.0).unwrap_err().to_string();
+        assert!(err.starts_with("geofence check failed:\n"));
+        assert_eq!(err.lines().count(), 3, "{}", err);
+    }
+}
@@ -0,0 +1,488 @@
+// Geodesy for navigation and geofencing.
+//
+// - Distances/bearings on the WGS-84 ellipsoid (Vincenty), with a spherical
+//   fallback for the near-antipodal cases where Vincenty does not converge.
+// - A per-mission local ENU frame (tangent plane at home, via ECEF). Geofence
+//   tests run in this frame, so they are unaffected by the antimeridian and do
+//   not degrade at high latitudes the way raw lat/lon arithmetic does.
+// - Signed distances to the zone boundary and to the corridor edge.
+
+use crate::nav::Point;
+
+pub const WGS84_A: f64 = 6_378_137.0;
+pub const WGS84_F: f64 = 1.0 / 298.257_223_563;
+pub const WGS84_B: f64 = WGS84_A * (1.0 - WGS84_F);
+const WGS84_E2: f64 = WGS84_F * (2.0 - WGS84_F);
+/// IUGG mean radius, used by the spherical fallback
+const MEAN_RADIUS_M: f64 = 6_371_008.8;
+
+const VINCENTY_EPS: f64 = 1e-12;
+const VINCENTY_MAX_ITER: usize = 200;
+
+/// Ellipsoidal distance in metres.
+pub fn distance_m(a: &Point, b: &Point) -> f64 {
+    inverse(a, b).0
+}
+
+/// Initial bearing from `a` to `b`, degrees clockwise from true north in [0, 360).
+pub fn initial_bearing_deg(a: &Point, b: &Point) -> f64 {
+    inverse(a, b).1.to_degrees().rem_euclid(360.0)
+}
+
+/// Point reached by travelling `distance_m` from `a` on `bearing_deg` along the geodesic.
+pub fn destination(a: &Point, bearing_deg: f64, distance_m: f64) -> Point {
+    vincenty_direct(a, bearing_deg.to_radians(), distance_m)
+        .unwrap_or_else(|| spherical_destination(a, bearing_deg.to_radians(), distance_m))
+}
+
+// (distance, initial azimuth in radians)
+fn inverse(a: &Point, b: &Point) -> (f64, f64) {
+    vincenty_inverse(a, b).unwrap_or_else(|| spherical_inverse(a, b))
+}
+
+fn vincenty_inverse(p1: &Point, p2: &Point) -> Option<(f64, f64)> {
+    let f = WGS84_F;
+    let l = wrap_pi((p2.lon - p1.lon).to_radians());
+    let u1 = ((1.0 - f) * p1.lat.to_radians().tan()).atan();
+    let u2 = ((1.0 - f) * p2.lat.to_radians().tan()).atan();
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let (sin_u2, cos_u2) = u2.sin_cos();
+
+    let mut lambda = l;
+    for _ in 0..VINCENTY_MAX_ITER {
+        let (sin_l, cos_l) = lambda.sin_cos();
+        let sin_sigma = ((cos_u2 * sin_l).powi(2) + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_l).powi(2)).sqrt();
+        if sin_sigma == 0.0 {
+            return Some((0.0, 0.0)); // coincident points
+        }
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_l;
+        let sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_l / sin_sigma;
+        let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+        // Equatorial line: cos2_alpha == 0
+        let cos_2sm = if cos2_alpha != 0.0 { cos_sigma - 2.0 * sin_u1 * sin_u2 / cos2_alpha } else { 0.0 };
+        let c = f / 16.0 * cos2_alpha * (4.0 + f * (4.0 - 3.0 * cos2_alpha));
+        let prev = lambda;
+        lambda = l + (1.0 - c) * f * sin_alpha
+            * (sigma + c * sin_sigma * (cos_2sm + c * cos_sigma * (-1.0 + 2.0 * cos_2sm * cos_2sm)));
+
+        if (lambda - prev).abs() < VINCENTY_EPS {
+            let u_sq = cos2_alpha * (WGS84_A * WGS84_A - WGS84_B * WGS84_B) / (WGS84_B * WGS84_B);
+            let (big_a, big_b) = vincenty_ab(u_sq);
+            let d_sigma = big_b * sin_sigma * (cos_2sm + big_b / 4.0
+                * (cos_sigma * (-1.0 + 2.0 * cos_2sm * cos_2sm)
+                    - big_b / 6.0 * cos_2sm * (-3.0 + 4.0 * sin_sigma * sin_sigma) * (-3.0 + 4.0 * cos_2sm * cos_2sm)));
+            let s = WGS84_B * big_a * (sigma - d_sigma);
+            let (sin_l, cos_l) = lambda.sin_cos();
+            let az = (cos_u2 * sin_l).atan2(cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_l);
+            return Some((s, az));
+        }
+    }
+    None
+}
+
+fn vincenty_direct(p: &Point, az: f64, s: f64) -> Option<Point> {
+    let f = WGS84_F;
+    let (sin_a1, cos_a1) = az.sin_cos();
+    let tan_u1 = (1.0 - f) * p.lat.to_radians().tan();
+    let cos_u1 = 1.0 / (1.0 + tan_u1 * tan_u1).sqrt();
+    let sin_u1 = tan_u1 * cos_u1;
+    let sigma1 = tan_u1.atan2(cos_a1);
+    let sin_alpha = cos_u1 * sin_a1;
+    let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+    let u_sq = cos2_alpha * (WGS84_A * WGS84_A - WGS84_B * WGS84_B) / (WGS84_B * WGS84_B);
+    let (big_a, big_b) = vincenty_ab(u_sq);
+
+    let mut sigma = s / (WGS84_B * big_a);
+    let mut converged = false;
+    let mut cos_2sm = 0.0;
+    for _ in 0..VINCENTY_MAX_ITER {
+        cos_2sm = (2.0 * sigma1 + sigma).cos();
+        let (sin_s, cos_s) = sigma.sin_cos();
+        let d_sigma = big_b * sin_s * (cos_2sm + big_b / 4.0
+            * (cos_s * (-1.0 + 2.0 * cos_2sm * cos_2sm)
+                - big_b / 6.0 * cos_2sm * (-3.0 + 4.0 * sin_s * sin_s) * (-3.0 + 4.0 * cos_2sm * cos_2sm)));
+        let prev = sigma;
+        sigma = s / (WGS84_B * big_a) + d_sigma;
+        if (sigma - prev).abs() < VINCENTY_EPS {
+            converged = true;
+            break;
+        }
+    }
+    if !converged {
+        return None;
+    }
+
+    let (sin_s, cos_s) = sigma.sin_cos();
+    let x = sin_u1 * sin_s - cos_u1 * cos_s * cos_a1;
+    let lat = (sin_u1 * cos_s + cos_u1 * sin_s * cos_a1).atan2((1.0 - f) * (sin_alpha * sin_alpha + x * x).sqrt());
+    let lambda = (sin_s * sin_a1).atan2(cos_u1 * cos_s - sin_u1 * sin_s * cos_a1);
+    let c = f / 16.0 * cos2_alpha * (4.0 + f * (4.0 - 3.0 * cos2_alpha));
+    let l = lambda - (1.0 - c) * f * sin_alpha
+        * (sigma + c * sin_s * (cos_2sm + c * cos_s * (-1.0 + 2.0 * cos_2sm * cos_2sm)));
+    let lon = wrap_pi(p.lon.to_radians() + l);
+    Some(Point { lat: lat.to_degrees(), lon: lon.to_degrees() })
+}
+
+fn vincenty_ab(u_sq: f64) -> (f64, f64) {
+    let a = 1.0 + u_sq / 16384.0 * (4096.0 + u_sq * (-768.0 + u_sq * (320.0 - 175.0 * u_sq)));
+    let b = u_sq / 1024.0 * (256.0 + u_sq * (-128.0 + u_sq * (74.0 - 47.0 * u_sq)));
+    (a, b)
+}
+
+fn spherical_inverse(a: &Point, b: &Point) -> (f64, f64) {
+    let (lat1, lat2) = (a.lat.to_radians(), b.lat.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = wrap_pi((b.lon - a.lon).to_radians());
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    let d = 2.0 * MEAN_RADIUS_M * h.sqrt().atan2((1.0 - h).sqrt());
+    let az = (dlon.sin() * lat2.cos()).atan2(lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos());
+    (d, az)
+}
+
+fn spherical_destination(a: &Point, az: f64, s: f64) -> Point {
+    let lat1 = a.lat.to_radians();
+    let d = s / MEAN_RADIUS_M;
+    let lat2 = (lat1.sin() * d.cos() + lat1.cos() * d.sin() * az.cos()).asin();
+    let lon2 = a.lon.to_radians() + (az.sin() * d.sin() * lat1.cos()).atan2(d.cos() - lat1.sin() * lat2.sin());
+    Point { lat: lat2.to_degrees(), lon: wrap_pi(lon2).to_degrees() }
+}
+
+fn wrap_pi(x: f64) -> f64 {
+    let y = (x + std::f64::consts::PI).rem_euclid(std::f64::consts::TAU) - std::f64::consts::PI;
+    if y == -std::f64::consts::PI { std::f64::consts::PI } else { y }
+}
+
+// ----- Local ENU frame -----
+
+/// East/North offset in metres from the frame origin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Enu {
+    pub e: f64,
+    pub n: f64,
+}
+
+/// Tangent-plane (ENU) frame anchored at one point, normally mission home.
+/// Horizontal distortion stays below a centimetre out to ~10 km from the origin.
+#[derive(Debug, Clone)]
+pub struct LocalFrame {
+    origin: Point,
+    ecef0: [f64; 3],
+    sin_lat: f64,
+    cos_lat: f64,
+    sin_lon: f64,
+    cos_lon: f64,
+}
+
+impl LocalFrame {
+    pub fn new(origin: &Point) -> Self {
+        let (sin_lat, cos_lat) = origin.lat.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = origin.lon.to_radians().sin_cos();
+        Self { origin: origin.clone(), ecef0: ecef(origin), sin_lat, cos_lat, sin_lon, cos_lon }
+    }
+
+    pub fn origin(&self) -> &Point {
+        &self.origin
+    }
+
+    pub fn to_enu(&self, p: &Point) -> Enu {
+        let x = ecef(p);
+        let (dx, dy, dz) = (x[0] - self.ecef0[0], x[1] - self.ecef0[1], x[2] - self.ecef0[2]);
+        Enu {
+            e: -self.sin_lon * dx + self.cos_lon * dy,
+            n: -self.sin_lat * self.cos_lon * dx - self.sin_lat * self.sin_lon * dy + self.cos_lat * dz,
+        }
+    }
+
+    /// Inverse of `to_enu` (up component taken as zero, i.e. on the tangent plane).
+    pub fn to_point(&self, p: Enu) -> Point {
+        let x = self.ecef0[0] - self.sin_lon * p.e - self.sin_lat * self.cos_lon * p.n;
+        let y = self.ecef0[1] + self.cos_lon * p.e - self.sin_lat * self.sin_lon * p.n;
+        let z = self.ecef0[2] + self.cos_lat * p.n;
+        geodetic(x, y, z)
+    }
+}
+
+fn ecef(p: &Point) -> [f64; 3] {
+    let (sin_lat, cos_lat) = p.lat.to_radians().sin_cos();
+    let (sin_lon, cos_lon) = p.lon.to_radians().sin_cos();
+    let n = WGS84_A / (1.0 - WGS84_E2 * sin_lat * sin_lat).sqrt();
+    [n * cos_lat * cos_lon, n * cos_lat * sin_lon, n * (1.0 - WGS84_E2) * sin_lat]
+}
+
+fn geodetic(x: f64, y: f64, z: f64) -> Point {
+    let lon = y.atan2(x);
+    let p = (x * x + y * y).sqrt();
+    let mut lat = z.atan2(p * (1.0 - WGS84_E2));
+    for _ in 0..5 {
+        let sin_lat = lat.sin();
+        let n = WGS84_A / (1.0 - WGS84_E2 * sin_lat * sin_lat).sqrt();
+        let h = p / lat.cos() - n;
+        lat = z.atan2(p * (1.0 - WGS84_E2 * n / (n + h)));
+    }
+    Point { lat: lat.to_degrees(), lon: lon.to_degrees() }
+}
+
+// ----- Planar primitives (ENU metres) -----
+
+pub fn dist(a: Enu, b: Enu) -> f64 {
+    (a.e - b.e).hypot(a.n - b.n)
+}
+
+fn cross(o: Enu, a: Enu, b: Enu) -> f64 {
+    (a.e - o.e) * (b.n - o.n) - (a.n - o.n) * (b.e - o.e)
+}
+
+fn within_box(p: Enu, a: Enu, b: Enu) -> bool {
+    p.e >= a.e.min(b.e) && p.e <= a.e.max(b.e) && p.n >= a.n.min(b.n) && p.n <= a.n.max(b.n)
+}
+
+/// True if segments a-b and c-d touch or cross.
+pub fn segments_intersect(a: Enu, b: Enu, c: Enu, d: Enu) -> bool {
+    let d1 = cross(c, d, a);
+    let d2 = cross(c, d, b);
+    let d3 = cross(a, b, c);
+    let d4 = cross(a, b, d);
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0)) && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0)) {
+        return true;
+    }
+    (d1 == 0.0 && within_box(a, c, d))
+        || (d2 == 0.0 && within_box(b, c, d))
+        || (d3 == 0.0 && within_box(c, a, b))
+        || (d4 == 0.0 && within_box(d, a, b))
+}
+
+/// Distance from `p` to segment a-b, and the clamped parameter t in [0, 1] of the foot point.
+pub fn point_segment(p: Enu, a: Enu, b: Enu) -> (f64, f64) {
+    let (ve, vn) = (b.e - a.e, b.n - a.n);
+    let len2 = ve * ve + vn * vn;
+    if len2 == 0.0 {
+        return (dist(p, a), 0.0);
+    }
+    let t = (((p.e - a.e) * ve + (p.n - a.n) * vn) / len2).clamp(0.0, 1.0);
+    (dist(p, Enu { e: a.e + t * ve, n: a.n + t * vn }), t)
+}
+
+/// Minimum distance between segments a-b and c-d.
+pub fn segment_distance(a: Enu, b: Enu, c: Enu, d: Enu) -> f64 {
+    if segments_intersect(a, b, c, d) {
+        return 0.0;
+    }
+    point_segment(a, c, d).0
+        .min(point_segment(b, c, d).0)
+        .min(point_segment(c, a, b).0)
+        .min(point_segment(d, a, b).0)
+}
+
+// ----- Geofence shapes -----
+
+/// Zone polygon in a local frame (implicitly closed).
+#[derive(Debug, Clone)]
+pub struct LocalPolygon {
+    pub vertices: Vec<Enu>,
+}
+
+impl LocalPolygon {
+    pub fn new(frame: &LocalFrame, pts: &[Point]) -> Self {
+        Self { vertices: pts.iter().map(|p| frame.to_enu(p)).collect() }
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = (Enu, Enu)> + '_ {
+        let n = self.vertices.len();
+        (0..n).map(move |i| (self.vertices[i], self.vertices[(i + 1) % n]))
+    }
+
+    // Ray casting
+    pub fn contains(&self, p: Enu) -> bool {
+        if self.vertices.len() < 3 {
+            return false;
+        }
+        let mut inside = false;
+        for (a, b) in self.edges() {
+            if (a.n > p.n) != (b.n > p.n) && p.e < (b.e - a.e) * (p.n - a.n) / (b.n - a.n) + a.e {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    /// Signed area (counter-clockwise positive), m^2.
+    pub fn signed_area(&self) -> f64 {
+        self.edges().map(|(a, b)| a.e * b.n - b.e * a.n).sum::<f64>() / 2.0
+    }
+
+    /// Distance to the nearest edge: positive inside, negative outside.
+    pub fn signed_distance_m(&self, p: Enu) -> f64 {
+        let d = self.edges().map(|(a, b)| point_segment(p, a, b).0).fold(f64::INFINITY, f64::min);
+        if self.contains(p) { d } else { -d }
+    }
+}
+
+/// Nearest point on a corridor centreline.
+#[derive(Debug, Clone, Copy)]
+pub struct CorridorFix {
+    /// Index of the nearest segment (waypoints[i] -> waypoints[i + 1])
+    pub segment: usize,
+    /// Unsigned distance to the centreline
+    pub cross_track_m: f64,
+    /// Distance along the centreline from the first waypoint to the foot point
+    pub along_track_m: f64,
+}
+
+/// Route corridor: `half_width_m` either side of the waypoint polyline.
+#[derive(Debug, Clone)]
+pub struct Corridor {
+    pub centerline: Vec<Enu>,
+    pub half_width_m: f64,
+    seg_start_m: Vec<f64>,
+}
+
+impl Corridor {
+    pub fn new(frame: &LocalFrame, waypoints: &[Point], width_m: f64) -> Self {
+        let centerline: Vec<Enu> = waypoints.iter().map(|p| frame.to_enu(p)).collect();
+        let mut seg_start_m = Vec::with_capacity(centerline.len());
+        let mut acc = 0.0;
+        for w in centerline.windows(2) {
+            seg_start_m.push(acc);
+            acc += dist(w[0], w[1]);
+        }
+        seg_start_m.push(acc);
+        Self { centerline, half_width_m: width_m / 2.0, seg_start_m }
+    }
+
+    pub fn length_m(&self) -> f64 {
+        self.seg_start_m.last().copied().unwrap_or(0.0)
+    }
+
+    pub fn nearest(&self, p: Enu) -> Option<CorridorFix> {
+        self.nearest_from(p, 0)
+    }
+
+    /// Like `nearest`, but only considers segments from index `first` onwards.
+    /// Used to keep the active segment monotonic on routes that fold back on themselves.
+    pub fn nearest_from(&self, p: Enu, first: usize) -> Option<CorridorFix> {
+        let mut best: Option<CorridorFix> = None;
+        for (i, w) in self.centerline.windows(2).enumerate().skip(first) {
+            let (d, t) = point_segment(p, w[0], w[1]);
+            if best.map(|b| d < b.cross_track_m).unwrap_or(true) {
+                best = Some(CorridorFix {
+                    segment: i,
+                    cross_track_m: d,
+                    along_track_m: self.seg_start_m[i] + t * dist(w[0], w[1]),
+                });
+            }
+        }
+        best
+    }
+
+    /// Distance to the corridor edge: positive inside, negative outside.
+    /// A route with fewer than two waypoints has no corridor (always outside).
+    pub fn signed_margin_m(&self, p: Enu) -> f64 {
+        self.nearest(p).map(|f| self.half_width_m - f.cross_track_m).unwrap_or(f64::NEG_INFINITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pt(lat: f64, lon: f64) -> Point {
+        Point { lat, lon }
+    }
+
+    fn dms(d: f64, m: f64, s: f64) -> f64 {
+        d.signum() * (d.abs() + m / 60.0 + s / 3600.0)
+    }
+
+    // Vincenty (1975) / Geoscience Australia worked example, Flinders Peak to
+    // Buninyong: 54 972.271 m, azimuths 306°52'05.37" and 127°10'25.07".
+    // GRS80 and WGS-84 differ by far less than a millimetre on this line.
+    fn flinders_peak() -> Point {
+        pt(dms(-37.0, 57.0, 3.72030), dms(144.0, 25.0, 29.52440))
+    }
+
+    fn buninyong() -> Point {
+        pt(dms(-37.0, 39.0, 10.15610), dms(143.0, 55.0, 35.38390))
+    }
+
+    #[test]
+    fn vincenty_inverse_reference() {
+        let (a, b) = (flinders_peak(), buninyong());
+        assert!((distance_m(&a, &b) - 54_972.271).abs() < 1e-3, "{}", distance_m(&a, &b));
+        assert!((initial_bearing_deg(&a, &b) - dms(306.0, 52.0, 5.37)).abs() < 0.01 / 3600.0);
+        // Reverse azimuth, i.e. from Buninyong back to Flinders Peak
+        assert!((initial_bearing_deg(&b, &a) - dms(127.0, 10.0, 25.07)).abs() < 0.01 / 3600.0);
+    }
+
+    #[test]
+    fn vincenty_direct_reference() {
+        let p = destination(&flinders_peak(), dms(306.0, 52.0, 5.37), 54_972.271);
+        assert!(distance_m(&p, &buninyong()) < 1e-3);
+    }
+
+    #[test]
+    fn ellipsoid_reference_lengths() {
+        // One degree of equator is exactly a·π/180
+        let eq = distance_m(&pt(0.0, 0.0), &pt(0.0, 1.0));
+        assert!((eq - WGS84_A.to_radians()).abs() < 1e-6, "{}", eq);
+        // WGS-84 meridian quadrant
+        let q = distance_m(&pt(0.0, 0.0), &pt(90.0, 0.0));
+        assert!((q - 10_001_965.729).abs() < 1e-3, "{}", q);
+    }
+
+    #[test]
+    fn antimeridian() {
+        let (a, b) = (pt(0.0, 179.5), pt(0.0, -179.5));
+        assert!((distance_m(&a, &b) - WGS84_A.to_radians()).abs() < 1e-6);
+        assert!((initial_bearing_deg(&a, &b) - 90.0).abs() < 1e-9);
+
+        let p = destination(&pt(10.0, 179.99), 90.0, 5_000.0);
+        assert!(p.lon < -179.9, "{:?}", p);
+        assert!((distance_m(&pt(10.0, 179.99), &p) - 5_000.0).abs() < 1e-6);
+
+        // Frame across the antimeridian: east stays east
+        let f = LocalFrame::new(&pt(-16.5, 179.999));
+        let e = f.to_enu(&pt(-16.5, -179.999));
+        assert!(e.e > 200.0 && e.e < 220.0 && e.n.abs() < 0.01, "{:?}", e);
+    }
+
+    #[test]
+    fn near_antipodal_falls_back() {
+        let d = distance_m(&pt(0.0, 0.0), &pt(0.5, 179.7));
+        assert!(d.is_finite() && d > 19_900_000.0 && d < 20_040_000.0, "{}", d);
+    }
+
+    #[test]
+    fn enu_round_trip() {
+        for origin in [pt(48.0, 2.0), pt(-33.9, 151.2), pt(0.0, 179.99), pt(89.9, 0.0), pt(-89.95, 45.0)] {
+            let f = LocalFrame::new(&origin);
+            for bearing in (0..360).step_by(30) {
+                for range in [1.0, 100.0, 2_000.0, 10_000.0] {
+                    let p = destination(&origin, bearing as f64, range);
+                    let enu = f.to_enu(&p);
+                    // Tangent-plane distance matches the geodesic near home
+                    let flat = dist(Enu { e: 0.0, n: 0.0 }, enu);
+                    assert!((flat - range).abs() < 0.01, "{:?} {} {}m: {}", origin, bearing, range, flat);
+                    // `to_point` drops the up component (about -8 m at 10 km)
+                    let back = f.to_point(enu);
+                    assert!(distance_m(&p, &back) < 0.02, "{:?} {} {}m", origin, bearing, range);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn high_latitude_zone() {
+        // 1 km square around a point 500 m from the pole
+        let home = pt(89.995, 0.0);
+        let f = LocalFrame::new(&home);
+        let corners: Vec<Point> = [45.0, 135.0, 225.0, 315.0].iter()
+            .map(|b| destination(&home, *b, 500.0 * std::f64::consts::SQRT_2))
+            .collect();
+        let zone = LocalPolygon::new(&f, &corners);
+        assert!((zone.signed_area().abs() - 1_000_000.0).abs() < 10.0, "{}", zone.signed_area());
+        assert!(zone.contains(f.to_enu(&destination(&home, 10.0, 400.0))));
+        assert!(!zone.contains(f.to_enu(&destination(&home, 90.0, 600.0))));
+        assert!((zone.signed_distance_m(f.to_enu(&home)) - 500.0).abs() < 0.5);
+    }
+}
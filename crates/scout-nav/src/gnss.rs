@@ -5,6 +5,7 @@ use tokio_serial::{SerialPortBuilderExt, SerialStream};
 use time::OffsetDateTime;
 use std::sync::Mutex;
 use once_cell::sync::Lazy;
+use tokio::sync::mpsc;
 
 #[derive(Debug, Clone)]
 pub struct FixQuality {
@@ -24,6 +25,8 @@ pub struct GnssFix {
 pub enum GnssSource {
     Serial(BufReader<SerialStream>),
     File(BufReader<File>),
+    /// Timestamped NMEA lines pushed by an in-process producer (e.g. the simulator).
+    Feed(mpsc::Receiver<(OffsetDateTime, String)>),
 }
 
 impl GnssSource {
@@ -39,12 +42,19 @@ impl GnssSource {
         Ok(Self::File(BufReader::new(f)))
     }
 
+    pub fn feed(rx: mpsc::Receiver<(OffsetDateTime, String)>) -> Self {
+        Self::Feed(rx)
+    }
+
     pub async fn next_fix(&mut self) -> Result<GnssFix> {
         let mut line = String::new();
         loop {
             line.clear();
-            match self {
-                GnssSource::Serial(r) => { r.read_line(&mut line).await?; }
+            let now = match self {
+                GnssSource::Serial(r) => {
+                    r.read_line(&mut line).await?;
+                    OffsetDateTime::now_utc()
+                }
                 GnssSource::File(r) => {
                     let n = r.read_line(&mut line).await?;
                     if n == 0 {
@@ -52,9 +62,16 @@ impl GnssSource {
                         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
                         continue;
                     }
+                    OffsetDateTime::now_utc()
                 }
-            }
-            if let Some(fix) = parse_nmea_line(line.trim())? {
+                // Feed lines carry their own (possibly simulated) timestamp
+                GnssSource::Feed(rx) => {
+                    let (ts, l) = rx.recv().await.context("gnss feed closed")?;
+                    line = l;
+                    ts
+                }
+            };
+            if let Some(fix) = parse_nmea_line(line.trim(), now)? {
                 return Ok(fix);
             }
         }
@@ -68,13 +85,13 @@ impl GnssSource {
 static LAST_GGA: Lazy<Mutex<Option<(u8, f32, OffsetDateTime)>>> =
     Lazy::new(|| Mutex::new(None));
 
-fn parse_nmea_line(s: &str) -> Result<Option<GnssFix>> {
+fn parse_nmea_line(s: &str, now: OffsetDateTime) -> Result<Option<GnssFix>> {
     if s.starts_with("$GNGGA") || s.starts_with("$GPGGA") {
         let parts: Vec<&str> = s.split(',').collect();
         if parts.len() > 9 {
             let sats: u8 = parts[7].parse().unwrap_or(0);
             let hdop: f32 = parts[8].parse().unwrap_or(99.9);
-            *LAST_GGA.lock().unwrap() = Some((sats, hdop, OffsetDateTime::now_utc()));
+            *LAST_GGA.lock().unwrap() = Some((sats, hdop, now));
         }
         return Ok(None);
     }
@@ -85,7 +102,6 @@ fn parse_nmea_line(s: &str) -> Result<Option<GnssFix>> {
             // parts[3]=lat ddmm.mmmm, parts[4]=N/S, parts[5]=lon dddmm.mmmm, parts[6]=E/W
             let lat = parse_deg_min(parts[3], parts[4]).unwrap_or(0.0);
             let lon = parse_deg_min(parts[5], parts[6]).unwrap_or(0.0);
-            let now = OffsetDateTime::now_utc();
 
             let (sats, hdop, gga_ts) = LAST_GGA.lock().unwrap()
                 .unwrap_or((0, 99.9, now));
@@ -1,4 +1,6 @@
 pub mod doctor;
+pub mod geo;
 pub mod gnss;
+pub mod mission;
 pub mod nav;
 pub mod thermal;
@@ -0,0 +1,526 @@
+// Mission import/export: QGroundControl `.plan`, KML and GeoJSON <-> `RouteCfg`/`ZoneCfg`.
+//
+// The nav engine only knows one corridor polyline and one zone polygon, so importers
+// take the first suitable geometry of each kind and report everything else they
+// dropped or approximated in `Mission::notes`.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+
+use crate::geo;
+use crate::nav::{Home, Point, RouteCfg, ZoneCfg};
+
+/// Segments used to approximate a geofence circle as a polygon.
+const CIRCLE_SEGMENTS: usize = 32;
+
+// MAV_CMD ids that carry a route position in QGC mission items
+const MAV_CMD_NAV_WAYPOINT: u64 = 16;
+const MAV_CMD_NAV_SPLINE_WAYPOINT: u64 = 82;
+const MAV_FRAME_GLOBAL_RELATIVE_ALT: u64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissionFormat {
+    QgcPlan,
+    Kml,
+    GeoJson,
+}
+
+impl MissionFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        match ext.as_str() {
+            "plan" => Ok(Self::QgcPlan),
+            "kml" => Ok(Self::Kml),
+            "geojson" | "json" => Ok(Self::GeoJson),
+            other => anyhow::bail!("unknown mission file extension '{}' (expected .plan, .kml or .geojson)", other),
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "plan" | "qgc" => Ok(Self::QgcPlan),
+            "kml" => Ok(Self::Kml),
+            "geojson" => Ok(Self::GeoJson),
+            other => anyhow::bail!("unknown mission format '{}' (plan | kml | geojson)", other),
+        }
+    }
+}
+
+/// Same shape as the `[nav]` config section, so the TOML written from it can be
+/// pasted into (or merged with) a scout config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mission {
+    /// Waypoint altitude above home; .plan items carry it, KML and GeoJSON do not
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cruise_alt_m: Option<f32>,
+    pub home: Option<Home>,
+    pub route: RouteCfg,
+    pub zone: ZoneCfg,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rally_points: Vec<Point>,
+    #[serde(skip)]
+    pub notes: Vec<String>,
+}
+
+/// Read a mission file. `corridor_width_m` is used as-is: none of the
+/// supported formats carry a corridor width.
+pub fn import(path: &Path, corridor_width_m: f64) -> Result<Mission> {
+    let fmt = MissionFormat::from_path(path)?;
+    let s = std::fs::read_to_string(path).with_context(|| format!("read mission file {}", path.display()))?;
+    import_str(&s, fmt, corridor_width_m)
+}
+
+pub fn import_str(s: &str, fmt: MissionFormat, corridor_width_m: f64) -> Result<Mission> {
+    let mut m = Mission {
+        cruise_alt_m: None,
+        home: None,
+        route: RouteCfg { corridor_width_m, waypoints: Vec::new() },
+        zone: ZoneCfg { garden_polygon: Vec::new() },
+        rally_points: Vec::new(),
+        notes: Vec::new(),
+    };
+    match fmt {
+        MissionFormat::QgcPlan => import_plan(s, &mut m)?,
+        MissionFormat::Kml => import_kml(s, &mut m)?,
+        MissionFormat::GeoJson => import_geojson(s, &mut m)?,
+    }
+    anyhow::ensure!(!m.route.waypoints.is_empty(), "mission has no route waypoints");
+    anyhow::ensure!(!m.zone.garden_polygon.is_empty(), "mission has no zone polygon");
+    Ok(m)
+}
+
+pub fn export(m: &Mission, fmt: MissionFormat) -> Result<String> {
+    match fmt {
+        MissionFormat::QgcPlan => Ok(serde_json::to_string_pretty(&export_plan(m)?)?),
+        MissionFormat::GeoJson => Ok(serde_json::to_string_pretty(&export_geojson(m))?),
+        MissionFormat::Kml => Ok(export_kml(m)),
+    }
+}
+
+// ----- QGroundControl .plan -----
+
+fn import_plan(s: &str, m: &mut Mission) -> Result<()> {
+    let v: Value = serde_json::from_str(s).context("parse .plan json")?;
+    anyhow::ensure!(v["fileType"] == "Plan", ".plan fileType is not \"Plan\"");
+
+    let mission = &v["mission"];
+    if let Some(h) = mission["plannedHomePosition"].as_array() {
+        let lat = h.first().and_then(Value::as_f64).context("plannedHomePosition lat")?;
+        let lon = h.get(1).and_then(Value::as_f64).context("plannedHomePosition lon")?;
+        let alt = h.get(2).and_then(Value::as_f64).unwrap_or(0.0);
+        m.home = Some(Home { lat, lon, alt_m: alt as f32 });
+    }
+
+    for (i, item) in mission["items"].as_array().into_iter().flatten().enumerate() {
+        if item["type"] != "SimpleItem" {
+            m.notes.push(format!("mission item {}: skipped {} (only SimpleItem waypoints are imported)",
+                i, item["complexItemType"].as_str().or(item["type"].as_str()).unwrap_or("unknown")));
+            continue;
+        }
+        let cmd = item["command"].as_u64().unwrap_or(0);
+        if cmd != MAV_CMD_NAV_WAYPOINT && cmd != MAV_CMD_NAV_SPLINE_WAYPOINT {
+            m.notes.push(format!("mission item {}: skipped MAV_CMD {}", i, cmd));
+            continue;
+        }
+        let params = item["params"].as_array().with_context(|| format!("mission item {}: params missing", i))?;
+        let lat = params.get(4).and_then(Value::as_f64).with_context(|| format!("mission item {}: lat", i))?;
+        let lon = params.get(5).and_then(Value::as_f64).with_context(|| format!("mission item {}: lon", i))?;
+        let alt = params.get(6).and_then(Value::as_f64);
+        match (item["frame"].as_u64(), alt, m.cruise_alt_m) {
+            (Some(MAV_FRAME_GLOBAL_RELATIVE_ALT), Some(alt), None) => m.cruise_alt_m = Some(alt as f32),
+            (Some(MAV_FRAME_GLOBAL_RELATIVE_ALT), Some(alt), Some(cruise)) if alt as f32 != cruise =>
+                m.notes.push(format!("mission item {}: altitude {}m differs from the first waypoint's {}m, ignored", i, alt, cruise)),
+            (Some(MAV_FRAME_GLOBAL_RELATIVE_ALT), _, _) => {}
+            (frame, _, _) => m.notes.push(format!("mission item {}: altitude in frame {:?} ignored (only relative altitudes are used)", i, frame)),
+        }
+        m.route.waypoints.push(Point { lat, lon });
+    }
+
+    let fence = &v["geoFence"];
+    for (i, p) in fence["polygons"].as_array().into_iter().flatten().enumerate() {
+        if p["inclusion"] == false {
+            m.notes.push(format!("geofence polygon {}: exclusion zones are not supported, skipped", i));
+            continue;
+        }
+        let pts = p["polygon"].as_array().with_context(|| format!("geofence polygon {}: no vertices", i))?
+            .iter()
+            .map(|ll| latlon_pair(ll).with_context(|| format!("geofence polygon {}: bad vertex", i)))
+            .collect::<Result<Vec<_>>>()?;
+        take_zone(m, pts, format!("geofence polygon {}", i));
+    }
+    for (i, c) in fence["circles"].as_array().into_iter().flatten().enumerate() {
+        if c["inclusion"] == false {
+            m.notes.push(format!("geofence circle {}: exclusion zones are not supported, skipped", i));
+            continue;
+        }
+        let center = latlon_pair(&c["circle"]["center"]).with_context(|| format!("geofence circle {}: center", i))?;
+        let radius = c["circle"]["radius"].as_f64().with_context(|| format!("geofence circle {}: radius", i))?;
+        if take_zone(m, circle_polygon(&center, radius), format!("geofence circle {}", i)) {
+            m.notes.push(format!("geofence circle {}: approximated by a {}-gon", i, CIRCLE_SEGMENTS));
+        }
+    }
+
+    for (i, p) in v["rallyPoints"]["points"].as_array().into_iter().flatten().enumerate() {
+        m.rally_points.push(latlon_pair(p).with_context(|| format!("rally point {}: bad coordinates", i))?);
+    }
+    Ok(())
+}
+
+fn latlon_pair(v: &Value) -> Option<Point> {
+    let a = v.as_array()?;
+    Some(Point { lat: a.first()?.as_f64()?, lon: a.get(1)?.as_f64()? })
+}
+
+fn export_plan(m: &Mission) -> Result<Value> {
+    let home = m.home.as_ref();
+    // Items and rally points are relative to home, like `nav.cruise_alt_m`
+    let alt = m.cruise_alt_m.context(".plan export needs a cruise altitude")? as f64;
+    let items: Vec<Value> = m.route.waypoints.iter().enumerate().map(|(i, p)| json!({
+        "type": "SimpleItem",
+        "autoContinue": true,
+        "command": MAV_CMD_NAV_WAYPOINT,
+        "doJumpId": i + 1,
+        "frame": MAV_FRAME_GLOBAL_RELATIVE_ALT,
+        "params": [0, 0, 0, null, p.lat, p.lon, alt],
+    })).collect();
+    let zone: Vec<Value> = m.zone.garden_polygon.iter().map(|p| json!([p.lat, p.lon])).collect();
+    let rally: Vec<Value> = m.rally_points.iter().map(|p| json!([p.lat, p.lon, alt])).collect();
+    Ok(json!({
+        "fileType": "Plan",
+        "version": 1,
+        "groundStation": "NAVscout",
+        "mission": {
+            "version": 2,
+            "firmwareType": 3,
+            "vehicleType": 2,
+            "cruiseSpeed": 15,
+            "hoverSpeed": 5,
+            "plannedHomePosition": home.map(|h| json!([h.lat, h.lon, h.alt_m])).unwrap_or(Value::Null),
+            "items": items,
+        },
+        "geoFence": {
+            "version": 2,
+            "circles": [],
+            "polygons": [{ "inclusion": true, "version": 1, "polygon": zone }],
+        },
+        "rallyPoints": { "version": 2, "points": rally },
+    }))
+}
+
+// ----- GeoJSON -----
+// Coordinates are [lon, lat(, alt)]. LineString -> route, Polygon -> zone,
+// Point -> home (if named/role "home") or rally point.
+
+fn import_geojson(s: &str, m: &mut Mission) -> Result<()> {
+    let v: Value = serde_json::from_str(s).context("parse geojson")?;
+    let features: Vec<&Value> = match v["type"].as_str() {
+        Some("FeatureCollection") => v["features"].as_array().context("features missing")?.iter().collect(),
+        Some("Feature") => vec![&v],
+        other => anyhow::bail!("unsupported GeoJSON root type {:?}", other),
+    };
+
+    for (i, f) in features.into_iter().enumerate() {
+        let g = &f["geometry"];
+        let label = feature_label(&f["properties"]).unwrap_or_else(|| format!("feature {}", i));
+        match g["type"].as_str() {
+            Some("LineString") => {
+                let pts = lonlat_list(&g["coordinates"]).with_context(|| format!("{}: bad coordinates", label))?;
+                take_route(m, pts, label);
+            }
+            Some("Polygon") => {
+                let ring = lonlat_list(&g["coordinates"][0]).with_context(|| format!("{}: bad coordinates", label))?;
+                take_zone(m, ring, label);
+            }
+            Some("MultiPolygon") => {
+                let ring = lonlat_list(&g["coordinates"][0][0]).with_context(|| format!("{}: bad coordinates", label))?;
+                if take_zone(m, ring, label.clone()) {
+                    m.notes.push(format!("{}: MultiPolygon, only the first polygon was used", label));
+                }
+            }
+            Some("Point") => {
+                let p = lonlat(&g["coordinates"]).with_context(|| format!("{}: bad coordinates", label))?;
+                take_point(m, p, &label, g["coordinates"][2].as_f64());
+            }
+            other => m.notes.push(format!("{}: unsupported geometry {:?}, skipped", label, other)),
+        }
+    }
+    Ok(())
+}
+
+fn feature_label(props: &Value) -> Option<String> {
+    props["role"].as_str().or(props["name"].as_str()).map(str::to_string)
+}
+
+fn lonlat(v: &Value) -> Option<Point> {
+    let a = v.as_array()?;
+    Some(Point { lon: a.first()?.as_f64()?, lat: a.get(1)?.as_f64()? })
+}
+
+fn lonlat_list(v: &Value) -> Option<Vec<Point>> {
+    v.as_array()?.iter().map(lonlat).collect()
+}
+
+fn export_geojson(m: &Mission) -> Value {
+    let ll = |p: &Point| json!([p.lon, p.lat]);
+    let mut ring: Vec<Value> = m.zone.garden_polygon.iter().map(ll).collect();
+    if let Some(first) = ring.first().cloned() {
+        ring.push(first); // GeoJSON rings are explicitly closed
+    }
+    let mut features = vec![
+        json!({
+            "type": "Feature",
+            "properties": { "role": "route", "corridor_width_m": m.route.corridor_width_m },
+            "geometry": { "type": "LineString", "coordinates": m.route.waypoints.iter().map(ll).collect::<Vec<_>>() },
+        }),
+        json!({
+            "type": "Feature",
+            "properties": { "role": "zone" },
+            "geometry": { "type": "Polygon", "coordinates": [ring] },
+        }),
+    ];
+    if let Some(h) = &m.home {
+        features.push(json!({
+            "type": "Feature",
+            "properties": { "role": "home" },
+            "geometry": { "type": "Point", "coordinates": [h.lon, h.lat, h.alt_m] },
+        }));
+    }
+    for p in &m.rally_points {
+        features.push(json!({
+            "type": "Feature",
+            "properties": { "role": "rally" },
+            "geometry": { "type": "Point", "coordinates": ll(p) },
+        }));
+    }
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+// ----- KML -----
+// Same mapping as GeoJSON: LineString -> route, Polygon outer ring -> zone,
+// Point placemark named "home" -> home, other points -> rally points.
+
+fn import_kml(s: &str, m: &mut Mission) -> Result<()> {
+    let doc = roxmltree::Document::parse(s).context("parse kml")?;
+    for (i, pm) in doc.descendants().filter(|n| n.has_tag_name("Placemark")).enumerate() {
+        let label = pm.children().find(|n| n.has_tag_name("name"))
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_string())
+            .unwrap_or_else(|| format!("placemark {}", i));
+
+        let mut used = false;
+        for geom in pm.descendants() {
+            if geom.has_tag_name("LineString") {
+                let pts = kml_coords(geom).with_context(|| format!("{}: bad coordinates", label))?;
+                take_route(m, pts, label.clone());
+                used = true;
+            } else if geom.has_tag_name("Polygon") {
+                let outer = geom.descendants().find(|n| n.has_tag_name("outerBoundaryIs"))
+                    .with_context(|| format!("{}: polygon without outerBoundaryIs", label))?;
+                let pts = kml_coords(outer).with_context(|| format!("{}: bad coordinates", label))?;
+                take_zone(m, pts, label.clone());
+                used = true;
+            } else if geom.has_tag_name("Point") {
+                let pts = kml_coords(geom).with_context(|| format!("{}: bad coordinates", label))?;
+                let p = pts.into_iter().next().with_context(|| format!("{}: empty point", label))?;
+                let alt = geom.descendants().find(|n| n.has_tag_name("coordinates"))
+                    .and_then(|n| n.text())
+                    .and_then(|t| t.trim().split(',').nth(2)?.parse().ok());
+                take_point(m, p, &label, alt);
+                used = true;
+            }
+        }
+        if !used {
+            m.notes.push(format!("{}: no supported geometry, skipped", label));
+        }
+    }
+    Ok(())
+}
+
+// <coordinates>lon,lat[,alt] lon,lat[,alt] ...</coordinates>
+fn kml_coords(node: roxmltree::Node) -> Option<Vec<Point>> {
+    let text = node.descendants().find(|n| n.has_tag_name("coordinates"))?.text()?;
+    text.split_whitespace().map(|tuple| {
+        let mut it = tuple.split(',');
+        let lon = it.next()?.parse().ok()?;
+        let lat = it.next()?.parse().ok()?;
+        Some(Point { lat, lon })
+    }).collect()
+}
+
+fn export_kml(m: &Mission) -> String {
+    let coords = |pts: &[Point]| pts.iter().map(|p| format!("{},{}", p.lon, p.lat)).collect::<Vec<_>>().join(" ");
+    let mut ring = m.zone.garden_polygon.clone();
+    if let Some(first) = ring.first().cloned() {
+        ring.push(first);
+    }
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n  <name>NAVscout mission</name>\n");
+    out.push_str(&format!(
+        "  <Placemark>\n    <name>route</name>\n    <description>corridor_width_m={}</description>\n    <LineString><coordinates>{}</coordinates></LineString>\n  </Placemark>\n",
+        m.route.corridor_width_m, coords(&m.route.waypoints)
+    ));
+    out.push_str(&format!(
+        "  <Placemark>\n    <name>zone</name>\n    <Polygon><outerBoundaryIs><LinearRing><coordinates>{}</coordinates></LinearRing></outerBoundaryIs></Polygon>\n  </Placemark>\n",
+        coords(&ring)
+    ));
+    if let Some(h) = &m.home {
+        out.push_str(&format!(
+            "  <Placemark>\n    <name>home</name>\n    <Point><coordinates>{},{},{}</coordinates></Point>\n  </Placemark>\n",
+            h.lon, h.lat, h.alt_m
+        ));
+    }
+    for (i, p) in m.rally_points.iter().enumerate() {
+        out.push_str(&format!(
+            "  <Placemark>\n    <name>rally {}</name>\n    <Point><coordinates>{},{}</coordinates></Point>\n  </Placemark>\n",
+            i, p.lon, p.lat
+        ));
+    }
+    out.push_str("</Document>\n</kml>\n");
+    out
+}
+
+// ----- shared -----
+
+fn take_route(m: &mut Mission, pts: Vec<Point>, label: String) {
+    if m.route.waypoints.is_empty() {
+        m.route.waypoints = pts;
+    } else {
+        m.notes.push(format!("{}: additional line ignored (route already set)", label));
+    }
+}
+
+// Returns true if the polygon became the zone.
+fn take_zone(m: &mut Mission, mut pts: Vec<Point>, label: String) -> bool {
+    // Drop the explicit closing vertex; the nav engine closes rings implicitly
+    if pts.len() > 1 {
+        let (a, b) = (&pts[0], &pts[pts.len() - 1]);
+        if a.lat == b.lat && a.lon == b.lon {
+            pts.pop();
+        }
+    }
+    if m.zone.garden_polygon.is_empty() {
+        m.zone.garden_polygon = pts;
+        true
+    } else {
+        m.notes.push(format!("{}: additional polygon ignored (zone already set)", label));
+        false
+    }
+}
+
+fn take_point(m: &mut Mission, p: Point, label: &str, alt: Option<f64>) {
+    if label.eq_ignore_ascii_case("home") && m.home.is_none() {
+        m.home = Some(Home { lat: p.lat, lon: p.lon, alt_m: alt.unwrap_or(0.0) as f32 });
+    } else {
+        m.rally_points.push(p);
+    }
+}
+
+fn circle_polygon(center: &Point, radius_m: f64) -> Vec<Point> {
+    (0..CIRCLE_SEGMENTS).map(|i| {
+        let bearing = i as f64 / CIRCLE_SEGMENTS as f64 * 360.0;
+        geo::destination(center, bearing, radius_m)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mission() -> Mission {
+        Mission {
+            cruise_alt_m: Some(35.0),
+            home: Some(Home { lat: 48.0, lon: 2.0, alt_m: 112.5 }),
+            route: RouteCfg {
+                corridor_width_m: 30.0,
+                waypoints: vec![
+                    Point { lat: 48.0001, lon: 2.00005 },
+                    Point { lat: 48.0006, lon: 2.001 },
+                    Point { lat: 48.00105, lon: 2.0023 },
+                ],
+            },
+            zone: ZoneCfg {
+                garden_polygon: vec![
+                    Point { lat: 48.0011, lon: 2.0021 },
+                    Point { lat: 48.0012, lon: 2.0024 },
+                    Point { lat: 48.001, lon: 2.0025 },
+                    Point { lat: 48.0009, lon: 2.0022 },
+                ],
+            },
+            rally_points: vec![Point { lat: 48.0003, lon: 2.0004 }],
+            notes: Vec::new(),
+        }
+    }
+
+    fn coords(pts: &[Point]) -> Vec<(f64, f64)> {
+        pts.iter().map(|p| (p.lat, p.lon)).collect()
+    }
+
+    fn round_trip(fmt: MissionFormat) -> Mission {
+        let m = mission();
+        let back = import_str(&export(&m, fmt).unwrap(), fmt, m.route.corridor_width_m).unwrap();
+        assert_eq!(coords(&back.route.waypoints), coords(&m.route.waypoints), "{:?} route", fmt);
+        assert_eq!(coords(&back.zone.garden_polygon), coords(&m.zone.garden_polygon), "{:?} zone", fmt);
+        assert_eq!(coords(&back.rally_points), coords(&m.rally_points), "{:?} rally points", fmt);
+        let (h, h0) = (back.home.as_ref().unwrap(), m.home.as_ref().unwrap());
+        assert_eq!((h.lat, h.lon, h.alt_m), (h0.lat, h0.lon, h0.alt_m), "{:?} home", fmt);
+        assert!(back.notes.is_empty(), "{:?}: {:?}", fmt, back.notes);
+        back
+    }
+
+    #[test]
+    fn plan_round_trip() {
+        assert_eq!(round_trip(MissionFormat::QgcPlan).cruise_alt_m, Some(35.0));
+    }
+
+    #[test]
+    fn kml_round_trip() {
+        round_trip(MissionFormat::Kml);
+    }
+
+    #[test]
+    fn geojson_round_trip() {
+        round_trip(MissionFormat::GeoJson);
+    }
+
+    #[test]
+    fn plan_waypoints_use_relative_cruise_altitude() {
+        let plan: Value = serde_json::from_str(&export(&mission(), MissionFormat::QgcPlan).unwrap()).unwrap();
+        for item in plan["mission"]["items"].as_array().unwrap() {
+            assert_eq!(item["frame"], MAV_FRAME_GLOBAL_RELATIVE_ALT);
+            assert_eq!(item["params"][6], 35.0);
+        }
+        assert_eq!(plan["mission"]["plannedHomePosition"][2], 112.5);
+
+        let m = Mission { cruise_alt_m: None, ..mission() };
+        assert!(export(&m, MissionFormat::QgcPlan).is_err());
+    }
+
+    #[test]
+    fn plan_import_notes_dropped_items() {
+        let plan = json!({
+            "fileType": "Plan",
+            "mission": {
+                "plannedHomePosition": [48.0, 2.0, 100.0],
+                "items": [
+                    { "type": "SimpleItem", "command": 22, "frame": 3, "params": [0, 0, 0, null, 48.0, 2.0, 10.0] },
+                    { "type": "SimpleItem", "command": 16, "frame": 3, "params": [0, 0, 0, null, 48.0001, 2.0001, 40.0] },
+                    { "type": "ComplexItem", "complexItemType": "survey" },
+                    { "type": "SimpleItem", "command": 16, "frame": 0, "params": [0, 0, 0, null, 48.0002, 2.0002, 140.0] },
+                ],
+            },
+            "geoFence": {
+                "circles": [{ "inclusion": true, "circle": { "center": [48.0, 2.0], "radius": 50.0 } }],
+                "polygons": [],
+            },
+        });
+        let m = import_str(&plan.to_string(), MissionFormat::QgcPlan, 20.0).unwrap();
+        assert_eq!(coords(&m.route.waypoints), [(48.0001, 2.0001), (48.0002, 2.0002)]);
+        assert_eq!(m.cruise_alt_m, Some(40.0));
+        assert_eq!(m.zone.garden_polygon.len(), CIRCLE_SEGMENTS);
+        assert_eq!(m.notes.len(), 4, "{:?}", m.notes);
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use crate::geo::{self, Corridor, Enu, LocalFrame, LocalPolygon};
 use crate::gnss::GnssFix;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +30,18 @@ pub struct ZoneCfg {
 pub struct RthPolicy {
     pub grace_link_loss_s: u64,
     pub gnss_bad_fix_s: u64,
+    pub battery_low_pct: u8,
+    pub thermal_soft_c: i32,
+}
+
+/// Non-GNSS failsafe inputs sampled by the caller once per step (`scout sim`).
+/// `None` means "unknown" and never triggers a failsafe.
+#[derive(Debug, Clone, Default)]
+pub struct HealthInputs {
+    pub battery_pct: Option<u8>,
+    pub cpu_temp_c: Option<f32>,
+    /// Seconds since the uplink was last known to be working.
+    pub link_down_s: Option<u64>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -41,33 +54,110 @@ pub enum MissionState {
     Abort,
 }
 
+/// What pushed the mission into RTH or ABORT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failsafe {
+    MaxRadius,
+    Gnss,
+    Battery,
+    Thermal,
+    LinkLoss,
+    Geofence,
+}
+
+/// Structured position metrics, computed on every step.
+#[derive(Debug, Clone, Default)]
+pub struct NavMetrics {
+    pub dist_home_m: f64,
+    /// Distance from the active segment's centreline (always >= 0)
+    pub cross_track_m: Option<f64>,
+    /// Active route segment: waypoints[i] -> waypoints[i + 1]
+    pub active_segment: Option<usize>,
+    /// Distance flown along the route centreline and as a fraction of its length
+    pub along_track_m: Option<f64>,
+    pub route_progress: Option<f64>,
+    /// Distance to the nearest zone edge: positive inside, negative outside
+    pub zone_edge_m: Option<f64>,
+    /// Seconds since the mission entered its current state
+    pub time_in_state_s: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct NavOutput {
     pub state: MissionState,
     pub message: String,
+    pub metrics: NavMetrics,
+    /// Cause of the current RTH/ABORT (latched with the first trigger)
+    pub failsafe: Option<Failsafe>,
+}
+
+impl std::fmt::Display for NavMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "home={:.0}m", self.dist_home_m)?;
+        if let (Some(seg), Some(xt)) = (self.active_segment, self.cross_track_m) {
+            write!(f, " seg={} xtrack={:.1}m", seg, xt)?;
+        }
+        if let Some(p) = self.route_progress {
+            write!(f, " progress={:.0}%", p * 100.0)?;
+        }
+        if let Some(e) = self.zone_edge_m {
+            write!(f, " zone_edge={:+.1}m", e)?;
+        }
+        write!(f, " in_state={}s", self.time_in_state_s)
+    }
 }
 
 pub struct NavEngine {
-    home: Home,
-    route: RouteCfg,
-    zone: ZoneCfg,
+    home: Point,
+    // Route and zone projected once into the mission frame (ENU at home)
+    frame: LocalFrame,
+    corridor: Corridor,
+    zone: LocalPolygon,
     max_radius_m: f64,
     policy: RthPolicy,
 
     state: MissionState,
+    state_since: Option<time::OffsetDateTime>,
+    active_segment: usize,
     gnss_bad_since: Option<time::OffsetDateTime>,
+    rth_reason: Option<String>,
+    failsafe: Option<Failsafe>,
 }
 
 impl NavEngine {
     pub fn new(home: Home, route: RouteCfg, zone: ZoneCfg, max_radius_m: f64, policy: RthPolicy) -> Self {
+        let home = Point { lat: home.lat, lon: home.lon };
+        let frame = LocalFrame::new(&home);
+        let corridor = Corridor::new(&frame, &route.waypoints, route.corridor_width_m);
+        let zone = LocalPolygon::new(&frame, &zone.garden_polygon);
         Self {
-            home, route, zone, max_radius_m, policy,
+            home, frame, corridor, zone, max_radius_m, policy,
             state: MissionState::TransitToZone,
+            state_since: None,
+            active_segment: 0,
             gnss_bad_since: None,
+            rth_reason: None,
+            failsafe: None,
         }
     }
 
     pub fn step(&mut self, fix: GnssFix) -> NavOutput {
+        self.step_with_health(fix, &HealthInputs::default())
+    }
+
+    pub fn step_with_health(&mut self, fix: GnssFix, health: &HealthInputs) -> NavOutput {
+        let prev = self.state;
+        let pos = Point { lat: fix.lat, lon: fix.lon };
+        let p = self.frame.to_enu(&pos);
+        let message = self.evaluate(&fix, health, &pos, p);
+        if self.state != prev || self.state_since.is_none() {
+            self.state_since = Some(fix.ts);
+        }
+        let metrics = self.metrics(&pos, p, fix.ts);
+        NavOutput { state: self.state, message, metrics, failsafe: self.failsafe }
+    }
+
+    fn evaluate(&mut self, fix: &GnssFix, health: &HealthInputs, pos: &Point, p: Enu) -> String {
         let now = fix.ts;
         let q = &fix.quality;
 
@@ -79,24 +169,41 @@ impl NavEngine {
         }
 
         // Absolute max radius cap
-        let d_home = haversine_m(self.home.lat, self.home.lon, fix.lat, fix.lon);
+        let d_home = geo::distance_m(&self.home, pos);
         if d_home > self.max_radius_m {
             self.state = MissionState::Abort;
-            return NavOutput { state: self.state, message: format!("ABORT: exceeded max_radius_m ({}m)", d_home as i64) };
+            self.failsafe = Some(Failsafe::MaxRadius);
+            return format!("ABORT: exceeded max_radius_m ({}m)", d_home as i64);
         }
 
         // GNSS degrade ladder
         if let Some(t0) = self.gnss_bad_since {
             let bad_s = (now - t0).whole_seconds().max(0) as u64;
             if bad_s >= self.policy.gnss_bad_fix_s {
-                self.state = MissionState::Rth;
-                return NavOutput { state: self.state, message: format!("RTH: GNSS bad for {}s (sats={}, hdop={}, age={}s)", bad_s, q.sats, q.hdop, q.fix_age_s) };
+                return self.enter_rth(Failsafe::Gnss, format!("RTH: GNSS bad for {}s (sats={}, hdop={}, age={}s)", bad_s, q.sats, q.hdop, q.fix_age_s));
+            }
+        }
+
+        // Health failsafes (battery / thermal / link)
+        if let Some(pct) = health.battery_pct {
+            if pct <= self.policy.battery_low_pct {
+                return self.enter_rth(Failsafe::Battery, format!("RTH: battery low ({}% <= {}%)", pct, self.policy.battery_low_pct));
+            }
+        }
+        if let Some(t) = health.cpu_temp_c {
+            if t >= self.policy.thermal_soft_c as f32 {
+                return self.enter_rth(Failsafe::Thermal, format!("RTH: thermal {:.1}C >= {}C", t, self.policy.thermal_soft_c));
+            }
+        }
+        if let Some(down_s) = health.link_down_s {
+            if down_s >= self.policy.grace_link_loss_s {
+                return self.enter_rth(Failsafe::LinkLoss, format!("RTH: link lost for {}s", down_s));
             }
         }
 
         // Geofence checks
-        let in_corridor = point_in_corridor(&self.route, fix.lat, fix.lon);
-        let in_zone = point_in_polygon(&self.zone.garden_polygon, fix.lat, fix.lon);
+        let in_corridor = self.corridor.signed_margin_m(p) >= 0.0;
+        let in_zone = self.zone.contains(p);
 
         self.state = match self.state {
             MissionState::TransitToZone => {
@@ -111,83 +218,123 @@ impl NavEngine {
             s => s,
         };
 
-        let msg = match self.state {
+        if self.state == MissionState::Rth && self.rth_reason.is_none() {
+            self.failsafe = Some(Failsafe::Geofence);
+            self.rth_reason = Some(format!("RTH: boundary violated (corridor_ok={}, zone={})", in_corridor, in_zone));
+        }
+
+        match self.state {
             MissionState::TransitToZone => format!("TRANSIT: corridor_ok={}, zone={}", in_corridor, in_zone),
             MissionState::OperateInZone => "OPERATE: inside garden zone".to_string(),
-            MissionState::Rth => format!("RTH: boundary violated (corridor_ok={}, zone={})", in_corridor, in_zone),
+            MissionState::Rth => self.rth_reason.clone().unwrap_or_else(|| "RTH".to_string()),
             MissionState::Abort => "ABORT".to_string(),
             MissionState::Land => "LAND".to_string(),
             MissionState::Idle => "IDLE".to_string(),
+        }
+    }
+
+    fn metrics(&mut self, pos: &Point, p: Enu, now: time::OffsetDateTime) -> NavMetrics {
+        // Prefer staying on (or ahead of) the current segment while inside its corridor;
+        // otherwise snap to whichever segment is nearest (e.g. flying the route back on RTH).
+        let ahead = self.corridor.nearest_from(p, self.active_segment);
+        let on_route = match ahead {
+            Some(f) if f.cross_track_m <= self.corridor.half_width_m => Some(f),
+            _ => self.corridor.nearest(p),
         };
+        if let Some(f) = on_route {
+            self.active_segment = f.segment;
+        }
+        let len = self.corridor.length_m();
+        NavMetrics {
+            dist_home_m: geo::distance_m(&self.home, pos),
+            cross_track_m: on_route.map(|f| f.cross_track_m),
+            active_segment: on_route.map(|f| f.segment),
+            along_track_m: on_route.map(|f| f.along_track_m),
+            route_progress: on_route.filter(|_| len > 0.0).map(|f| f.along_track_m / len),
+            zone_edge_m: (self.zone.vertices.len() >= 3).then(|| self.zone.signed_distance_m(p)),
+            time_in_state_s: self.state_since.map(|t| (now - t).whole_seconds().max(0) as u64).unwrap_or(0),
+        }
+    }
 
-        NavOutput { state: self.state, message: msg }
+    // RTH is latched: the first trigger is kept as the reported reason.
+    fn enter_rth(&mut self, cause: Failsafe, reason: String) -> String {
+        if self.state != MissionState::Rth || self.rth_reason.is_none() {
+            self.state = MissionState::Rth;
+            self.rth_reason = Some(reason);
+            self.failsafe = Some(cause);
+        }
+        self.rth_reason.clone().unwrap_or_default()
     }
 }
 
-// ----- Geometry -----
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gnss::FixQuality;
 
-fn haversine_m(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
-    let r = 6_371_000.0_f64;
-    let dlat = (lat2 - lat1).to_radians();
-    let dlon = (lon2 - lon1).to_radians();
-    let a = (dlat/2.0).sin().powi(2) + lat1.to_radians().cos()*lat2.to_radians().cos()*(dlon/2.0).sin().powi(2);
-    let c = 2.0 * a.sqrt().atan2((1.0-a).sqrt());
-    r * c
-}
+    const HOME: Point = Point { lat: 48.0, lon: 2.0 };
 
-// Corridor = within width/2 of any segment between consecutive waypoints.
-// Distance is approximated by projecting to a local plane (good enough for small areas).
-fn point_in_corridor(route: &RouteCfg, lat: f64, lon: f64) -> bool {
-    let w = route.corridor_width_m / 2.0;
-    if route.waypoints.len() < 2 { return false; }
-    for seg in route.waypoints.windows(2) {
-        let a = &seg[0]; let b = &seg[1];
-        if dist_point_to_segment_m(lat, lon, a.lat, a.lon, b.lat, b.lon) <= w {
-            return true;
-        }
+    fn at(e: f64, n: f64) -> Point {
+        LocalFrame::new(&HOME).to_point(Enu { e, n })
     }
-    false
-}
 
-fn dist_point_to_segment_m(px: f64, py: f64, ax: f64, ay: f64, bx: f64, by: f64) -> f64 {
-    // simple equirectangular projection
-    let (x, y) = to_xy(px, py, ax, ay);
-    let (ax2, ay2) = (0.0, 0.0);
-    let (bx2, by2) = to_xy(bx, by, ax, ay);
-
-    let vx = bx2 - ax2; let vy = by2 - ay2;
-    let wx = x - ax2; let wy = y - ay2;
-
-    let c1 = wx*vx + wy*vy;
-    if c1 <= 0.0 { return (wx*wx + wy*wy).sqrt(); }
-    let c2 = vx*vx + vy*vy;
-    if c2 <= c1 { return ((x-bx2).powi(2) + (y-by2).powi(2)).sqrt(); }
-    let t = c1 / c2;
-    let projx = ax2 + t*vx;
-    let projy = ay2 + t*vy;
-    ((x-projx).powi(2) + (y-projy).powi(2)).sqrt()
-}
+    /// 100 m north, then 100 m east into a 60 m square zone: 200 m of route.
+    fn engine() -> NavEngine {
+        NavEngine::new(
+            Home { lat: HOME.lat, lon: HOME.lon, alt_m: 0.0 },
+            RouteCfg { corridor_width_m: 20.0, waypoints: vec![at(0.0, 0.0), at(0.0, 100.0), at(100.0, 100.0)] },
+            ZoneCfg { garden_polygon: vec![at(70.0, 70.0), at(130.0, 70.0), at(130.0, 130.0), at(70.0, 130.0)] },
+            500.0,
+            RthPolicy { grace_link_loss_s: 20, gnss_bad_fix_s: 8, battery_low_pct: 22, thermal_soft_c: 75 },
+        )
+    }
 
-fn to_xy(lat: f64, lon: f64, lat0: f64, lon0: f64) -> (f64, f64) {
-    let r = 6_371_000.0_f64;
-    let x = (lon - lon0).to_radians() * r * lat0.to_radians().cos();
-    let y = (lat - lat0).to_radians() * r;
-    (x, y)
-}
+    fn fix(e: f64, n: f64, t_s: i64) -> GnssFix {
+        let p = at(e, n);
+        GnssFix { lat: p.lat, lon: p.lon, quality: FixQuality { sats: 12, hdop: 0.8, fix_age_s: 0 },
+                  ts: time::OffsetDateTime::UNIX_EPOCH + time::Duration::seconds(t_s) }
+    }
+
+    fn close(a: Option<f64>, b: f64) -> bool {
+        a.map(|a| (a - b).abs() < 0.01).unwrap_or(false)
+    }
+
+    #[test]
+    fn metrics_along_the_route() {
+        let mut nav = engine();
+        let out = nav.step(fix(4.0, 50.0, 0));
+        let m = &out.metrics;
+        assert_eq!(out.state, MissionState::TransitToZone);
+        assert!((m.dist_home_m - 50.16).abs() < 0.01, "{}", m);
+        assert_eq!(m.active_segment, Some(0));
+        assert!(close(m.cross_track_m, 4.0), "{}", m);
+        assert!(close(m.along_track_m, 50.0) && close(m.route_progress, 0.25), "{}", m);
+        // Nearest zone corner is (70, 70)
+        assert!(close(m.zone_edge_m, -(66.0f64.hypot(20.0))), "{}", m);
+
+        let out = nav.step(fix(60.0, 98.0, 5));
+        let m = &out.metrics;
+        assert_eq!(m.active_segment, Some(1));
+        assert!(close(m.cross_track_m, 2.0) && close(m.along_track_m, 160.0) && close(m.route_progress, 0.8), "{}", m);
+        assert!(close(m.zone_edge_m, -10.0), "{}", m);
+        assert_eq!(m.time_in_state_s, 5);
+
+        let out = nav.step(fix(95.0, 100.0, 7));
+        let m = &out.metrics;
+        assert_eq!(out.state, MissionState::OperateInZone);
+        assert!(close(m.zone_edge_m, 25.0) && close(m.route_progress, 0.975), "{}", m);
+        assert_eq!(m.time_in_state_s, 0);
+        assert_eq!(nav.step(fix(100.0, 110.0, 10)).metrics.time_in_state_s, 3);
+    }
 
-// Ray casting polygon test
-fn point_in_polygon(poly: &[Point], lat: f64, lon: f64) -> bool {
-    let mut inside = false;
-    let n = poly.len();
-    if n < 3 { return false; }
-    let mut j = n - 1;
-    for i in 0..n {
-        let xi = poly[i].lon; let yi = poly[i].lat;
-        let xj = poly[j].lon; let yj = poly[j].lat;
-        let intersect = ((yi > lat) != (yj > lat))
-            && (lon < (xj - xi) * (lat - yi) / (yj - yi + 1e-12) + xi);
-        if intersect { inside = !inside; }
-        j = i;
+    #[test]
+    fn leaving_the_corridor_is_a_geofence_rth() {
+        let mut nav = engine();
+        nav.step(fix(0.0, 20.0, 0));
+        let out = nav.step(fix(15.0, 40.0, 1));
+        assert_eq!(out.state, MissionState::Rth);
+        assert_eq!(out.failsafe, Some(Failsafe::Geofence));
+        assert!(close(out.metrics.cross_track_m, 15.0), "{}", out.metrics);
+        assert_eq!(format!("{}", out.metrics), "home=43m seg=0 xtrack=15.0m progress=20% zone_edge=-62.6m in_state=0s");
     }
-    inside
 }
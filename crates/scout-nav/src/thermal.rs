@@ -54,13 +54,6 @@ impl ThermalMonitor {
         }
     }
 
-    /// Default thresholds for Raspberry Pi 5
-    /// - Warning: 70°C
-    /// - Critical: 80°C (thermal throttling starts at 85°C on RPI5)
-    pub fn default() -> Self {
-        Self::new(70.0, 80.0)
-    }
-
     pub fn check(&self) -> Result<ThermalStatus> {
         let temp = read_cpu_temp()?;
 
@@ -76,6 +69,15 @@ impl ThermalMonitor {
     }
 }
 
+impl Default for ThermalMonitor {
+    /// Default thresholds for Raspberry Pi 5
+    /// - Warning: 70°C
+    /// - Critical: 80°C (thermal throttling starts at 85°C on RPI5)
+    fn default() -> Self {
+        Self::new(70.0, 80.0)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ThermalLevel {
     Normal,
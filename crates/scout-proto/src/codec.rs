@@ -0,0 +1,595 @@
+// Wire encodings for telemetry envelopes.
+//
+// A frame (the plaintext inside one sealed blob) is either
+//   - JSON: a single `Envelope` (or a version 1 `TelemetryEvent`), starting with `{`,
+//     or a batch of them as an array, starting with `[`
+//   - binary: `FORMAT_BINARY` followed by a compact varint layout (below)
+//   - deflate: `FORMAT_DEFLATE` followed by a raw-deflated JSON or binary frame
+// so the first byte identifies the format and old JSON frames need no tag.
+//
+// Binary layout (all integers LEB128 varints, signed ones zigzag-encoded):
+//   tag u8 | schema v | device_id str | count | entries...
+//   entry: seq delta | ts_unix_ms delta | type u8 | body
+// Deltas are against the previous entry in the same frame (the first entry is
+// absolute), and positions are delta-encoded against the previous Position.
+// Floats are fixed-point: lat/lon 1e-7 deg, distances 0.1 m, HDOP 0.01,
+// voltage 1 mV, current 10 mA, temperature 0.1 C, progress 1e-4, conf 1e-3.
+
+use anyhow::{Context, Result};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+use serde::Deserialize;
+use std::io::{Read, Write};
+
+use crate::telemetry::*;
+
+pub const FORMAT_BINARY: u8 = 0xB1;
+pub const FORMAT_DEFLATE: u8 = 0xD1;
+
+/// Upper bound for an inflated frame, so a hostile blob cannot exhaust memory.
+pub const MAX_FRAME_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    Json,
+    #[default]
+    Binary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    None,
+    #[default]
+    Deflate,
+}
+
+/// Encode envelopes into one frame. A single envelope in JSON is a bare object
+/// (the pre-batching layout); binary frames require all envelopes to share
+/// `v` and `device_id`.
+pub fn encode(format: Format, envs: &[Envelope]) -> Result<Vec<u8>> {
+    match format {
+        Format::Json if envs.len() == 1 => Ok(serde_json::to_vec(&envs[0])?),
+        Format::Json => Ok(serde_json::to_vec(envs)?),
+        Format::Binary => encode_binary(envs),
+    }
+}
+
+/// Compress an encoded frame. Falls back to the plain frame when compression
+/// would not make it smaller (tiny frames).
+pub fn compress(compression: Compression, frame: Vec<u8>) -> Result<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(frame),
+        Compression::Deflate => {
+            let mut enc = DeflateEncoder::new(vec![FORMAT_DEFLATE], flate2::Compression::best());
+            enc.write_all(&frame)?;
+            let out = enc.finish()?;
+            Ok(if out.len() < frame.len() { out } else { frame })
+        }
+    }
+}
+
+/// Decode a frame in any supported format.
+pub fn decode(bytes: &[u8]) -> Result<Vec<Envelope>> {
+    match bytes.first() {
+        Some(&FORMAT_DEFLATE) => {
+            let mut inner = Vec::new();
+            DeflateDecoder::new(&bytes[1..]).take(MAX_FRAME_BYTES + 1).read_to_end(&mut inner).context("inflate frame")?;
+            anyhow::ensure!(inner.len() as u64 <= MAX_FRAME_BYTES, "inflated frame exceeds {} bytes", MAX_FRAME_BYTES);
+            anyhow::ensure!(inner.first() != Some(&FORMAT_DEFLATE), "nested compression");
+            decode(&inner)
+        }
+        Some(b'{') => Ok(vec![decode_json(bytes).context("decode JSON frame")?]),
+        Some(b'[') => {
+            let items: Vec<serde_json::Value> = serde_json::from_slice(bytes).context("decode JSON batch")?;
+            items.into_iter()
+                .map(|v| decode_json(&serde_json::to_vec(&v)?).context("decode JSON batch entry"))
+                .collect()
+        }
+        Some(&FORMAT_BINARY) => decode_binary(bytes).context("decode binary frame"),
+        Some(b) => anyhow::bail!("unknown frame format tag 0x{:02x}", b),
+        None => anyhow::bail!("empty frame"),
+    }
+}
+
+// ----- Binary -----
+
+const T_POSITION: u8 = 1;
+const T_STATE_CHANGE: u8 = 2;
+const T_FAILSAFE: u8 = 3;
+const T_FC_COMMAND: u8 = 4;
+const T_FC_ACK: u8 = 5;
+const T_HEALTH: u8 = 6;
+const T_SPOOL_STATS: u8 = 7;
+const T_STARTUP: u8 = 8;
+const T_SHUTDOWN: u8 = 9;
+const T_KEY_ROTATED: u8 = 10;
+const T_LEGACY: u8 = 0xFF;
+
+fn encode_binary(envs: &[Envelope]) -> Result<Vec<u8>> {
+    let first = envs.first().context("empty frame")?;
+    for e in envs {
+        anyhow::ensure!(e.v == first.v && e.device_id == first.device_id, "binary frame mixes schema versions or devices");
+    }
+
+    let mut w = Writer::default();
+    w.u8(FORMAT_BINARY);
+    w.uvar(first.v as u64);
+    w.str(&first.device_id);
+    w.uvar(envs.len() as u64);
+
+    let (mut seq, mut ts) = (0u64, 0i64);
+    let mut pos = (0i64, 0i64);
+    for e in envs {
+        w.ivar(e.seq.wrapping_sub(seq) as i64);
+        w.ivar(e.ts_unix_ms - ts);
+        seq = e.seq;
+        ts = e.ts_unix_ms;
+        match &e.payload {
+            Payload::Position(p) => {
+                w.u8(T_POSITION);
+                let cur = (fixed(p.lat, 1e7), fixed(p.lon, 1e7));
+                w.ivar(cur.0 - pos.0);
+                w.ivar(cur.1 - pos.1);
+                pos = cur;
+                w.mask(&[
+                    p.alt_m.is_some(), p.cross_track_m.is_some(), p.route_segment.is_some(),
+                    p.route_progress.is_some(), p.zone_edge_m.is_some(), p.vision.is_some(),
+                ]);
+                if let Some(a) = p.alt_m { w.ivar(fixed(a as f64, 10.0)); }
+                w.u8(p.sats);
+                w.uvar(ufixed(p.hdop as f64, 100.0));
+                w.u8(state_code(p.state));
+                w.uvar(ufixed(p.dist_home_m as f64, 10.0));
+                if let Some(x) = p.cross_track_m { w.uvar(ufixed(x as f64, 10.0)); }
+                if let Some(s) = p.route_segment { w.uvar(s as u64); }
+                if let Some(r) = p.route_progress { w.uvar(ufixed(r as f64, 1e4)); }
+                if let Some(z) = p.zone_edge_m { w.ivar(fixed(z as f64, 10.0)); }
+                w.uvar(p.time_in_state_s);
+                if let Some(v) = &p.vision {
+                    w.mask(&[v.lock_id.is_some(), v.lock_conf.is_some()]);
+                    w.uvar(v.detections as u64);
+                    if let Some(id) = v.lock_id { w.uvar(id); }
+                    if let Some(c) = v.lock_conf { w.uvar(ufixed(c as f64, 1e3)); }
+                }
+            }
+            Payload::MissionStateChange(c) => {
+                w.u8(T_STATE_CHANGE);
+                w.u8(state_code(c.from));
+                w.u8(state_code(c.to));
+                w.str(&c.reason);
+            }
+            Payload::FailsafeTriggered(f) => {
+                w.u8(T_FAILSAFE);
+                w.u8(f.failsafe as u8);
+                w.str(&f.detail);
+            }
+            Payload::FcCommand(c) => {
+                w.u8(T_FC_COMMAND);
+                w.u8(c.command as u8);
+                w.mask(&[c.error.is_some()]);
+                if let Some(err) = &c.error { w.str(err); }
+            }
+            Payload::FcAck(a) => {
+                w.u8(T_FC_ACK);
+                w.uvar(a.command_id as u64);
+                w.u8(a.result);
+            }
+            Payload::Health(h) => {
+                w.u8(T_HEALTH);
+                w.mask(&[
+                    h.battery_voltage.is_some(), h.battery_percent.is_some(), h.battery_current.is_some(),
+                    h.cpu_temp_c.is_some(), h.link_rtt_ms.is_some(), h.link_quality.is_some(),
+                ]);
+                if let Some(v) = h.battery_voltage { w.uvar(ufixed(v as f64, 1e3)); }
+                if let Some(p) = h.battery_percent { w.u8(p); }
+                if let Some(c) = h.battery_current { w.ivar(fixed(c as f64, 100.0)); }
+                if let Some(t) = h.cpu_temp_c { w.ivar(fixed(t as f64, 10.0)); }
+                if let Some(r) = h.link_rtt_ms { w.uvar(r as u64); }
+                if let Some(q) = h.link_quality { w.u8(q); }
+            }
+            Payload::SpoolStats(s) => {
+                w.u8(T_SPOOL_STATS);
+                w.uvar(s.files);
+                w.uvar(s.bytes);
+                w.uvar(s.evicted);
+            }
+            Payload::Startup(s) => {
+                w.u8(T_STARTUP);
+                w.str(&s.version);
+                w.str(&s.command);
+            }
+            Payload::Shutdown(s) => {
+                w.u8(T_SHUTDOWN);
+                w.str(&s.reason);
+            }
+            Payload::KeyRotated(k) => {
+                w.u8(T_KEY_ROTATED);
+                w.str(&k.key_id);
+            }
+            Payload::Legacy(ev) => {
+                w.u8(T_LEGACY);
+                w.bytes(&serde_json::to_vec(ev)?);
+            }
+        }
+    }
+    Ok(w.buf)
+}
+
+fn decode_binary(bytes: &[u8]) -> Result<Vec<Envelope>> {
+    let mut r = Reader { buf: bytes, pos: 0 };
+    anyhow::ensure!(r.u8()? == FORMAT_BINARY, "not a binary frame");
+    let v = u16::try_from(r.uvar()?).context("schema version out of range")?;
+    let device_id = r.str()?;
+    let count = r.uvar()?;
+    // Every entry takes at least 3 bytes; reject absurd counts before allocating
+    anyhow::ensure!(count <= bytes.len() as u64, "entry count {} exceeds frame size", count);
+
+    let mut out = Vec::with_capacity(count as usize);
+    let (mut seq, mut ts) = (0u64, 0i64);
+    let mut pos = (0i64, 0i64);
+    for _ in 0..count {
+        seq = seq.wrapping_add(r.ivar()? as u64);
+        ts = ts.checked_add(r.ivar()?).context("timestamp overflow")?;
+        let payload = match r.u8()? {
+            T_POSITION => {
+                pos.0 = pos.0.checked_add(r.ivar()?).context("latitude overflow")?;
+                pos.1 = pos.1.checked_add(r.ivar()?).context("longitude overflow")?;
+                let m = r.mask()?;
+                let alt_m = if m[0] { Some(r.ivar()? as f32 / 10.0) } else { None };
+                let sats = r.u8()?;
+                let hdop = r.uvar()? as f32 / 100.0;
+                let state = state_from(r.u8()?)?;
+                let dist_home_m = r.uvar()? as f32 / 10.0;
+                let cross_track_m = if m[1] { Some(r.uvar()? as f32 / 10.0) } else { None };
+                let route_segment = if m[2] { Some(r.uvar()? as u32) } else { None };
+                let route_progress = if m[3] { Some(r.uvar()? as f32 / 1e4) } else { None };
+                let zone_edge_m = if m[4] { Some(r.ivar()? as f32 / 10.0) } else { None };
+                let time_in_state_s = r.uvar()?;
+                let vision = if m[5] {
+                    let vm = r.mask()?;
+                    let detections = u16::try_from(r.uvar()?).unwrap_or(u16::MAX);
+                    let lock_id = if vm[0] { Some(r.uvar()?) } else { None };
+                    let lock_conf = if vm[1] { Some(r.uvar()? as f32 / 1e3) } else { None };
+                    Some(Vision { detections, lock_id, lock_conf })
+                } else {
+                    None
+                };
+                Payload::Position(Position {
+                    lat: pos.0 as f64 / 1e7,
+                    lon: pos.1 as f64 / 1e7,
+                    alt_m, sats, hdop, state, dist_home_m, cross_track_m, route_segment,
+                    route_progress, zone_edge_m, time_in_state_s, vision,
+                })
+            }
+            T_STATE_CHANGE => Payload::MissionStateChange(MissionStateChange {
+                from: state_from(r.u8()?)?,
+                to: state_from(r.u8()?)?,
+                reason: r.str()?,
+            }),
+            T_FAILSAFE => Payload::FailsafeTriggered(FailsafeTriggered {
+                failsafe: failsafe_from(r.u8()?)?,
+                detail: r.str()?,
+            }),
+            T_FC_COMMAND => {
+                let command = match r.u8()? {
+                    0 => FcCommandKind::Rtl,
+                    1 => FcCommandKind::Hold,
+                    c => anyhow::bail!("unknown FC command {}", c),
+                };
+                let m = r.mask()?;
+                let error = if m[0] { Some(r.str()?) } else { None };
+                Payload::FcCommand(FcCommand { command, error })
+            }
+            T_FC_ACK => Payload::FcAck(FcAck {
+                command_id: u16::try_from(r.uvar()?).context("MAV_CMD out of range")?,
+                result: r.u8()?,
+            }),
+            T_HEALTH => {
+                let m = r.mask()?;
+                Payload::Health(Health {
+                    battery_voltage: if m[0] { Some(r.uvar()? as f32 / 1e3) } else { None },
+                    battery_percent: if m[1] { Some(r.u8()?) } else { None },
+                    battery_current: if m[2] { Some(r.ivar()? as f32 / 100.0) } else { None },
+                    cpu_temp_c: if m[3] { Some(r.ivar()? as f32 / 10.0) } else { None },
+                    link_rtt_ms: if m[4] { Some(r.uvar()? as u32) } else { None },
+                    link_quality: if m[5] { Some(r.u8()?) } else { None },
+                })
+            }
+            T_SPOOL_STATS => Payload::SpoolStats(SpoolStats { files: r.uvar()?, bytes: r.uvar()?, evicted: r.uvar()? }),
+            T_STARTUP => Payload::Startup(Startup { version: r.str()?, command: r.str()? }),
+            T_SHUTDOWN => Payload::Shutdown(Shutdown { reason: r.str()? }),
+            T_KEY_ROTATED => Payload::KeyRotated(KeyRotated { key_id: r.str()? }),
+            T_LEGACY => Payload::Legacy(serde_json::from_slice(r.bytes()?)?),
+            t => anyhow::bail!("unknown payload type {}", t),
+        };
+        out.push(Envelope { v, device_id: device_id.clone(), seq, ts_unix_ms: ts, payload });
+    }
+    anyhow::ensure!(r.pos == bytes.len(), "{} trailing bytes after last entry", bytes.len() - r.pos);
+    Ok(out)
+}
+
+fn fixed(x: f64, scale: f64) -> i64 {
+    (x * scale).round() as i64
+}
+
+fn ufixed(x: f64, scale: f64) -> u64 {
+    (x * scale).round().max(0.0) as u64
+}
+
+fn state_code(s: MissionState) -> u8 {
+    s as u8
+}
+
+fn state_from(c: u8) -> Result<MissionState> {
+    Ok(match c {
+        0 => MissionState::Idle,
+        1 => MissionState::TransitToZone,
+        2 => MissionState::OperateInZone,
+        3 => MissionState::Rth,
+        4 => MissionState::Land,
+        5 => MissionState::Abort,
+        _ => anyhow::bail!("unknown mission state {}", c),
+    })
+}
+
+fn failsafe_from(c: u8) -> Result<Failsafe> {
+    Ok(match c {
+        0 => Failsafe::MaxRadius,
+        1 => Failsafe::Gnss,
+        2 => Failsafe::Battery,
+        3 => Failsafe::Thermal,
+        4 => Failsafe::LinkLoss,
+        5 => Failsafe::Geofence,
+        _ => anyhow::bail!("unknown failsafe {}", c),
+    })
+}
+
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, b: u8) {
+        self.buf.push(b);
+    }
+
+    fn uvar(&mut self, mut x: u64) {
+        while x >= 0x80 {
+            self.buf.push((x as u8) | 0x80);
+            x >>= 7;
+        }
+        self.buf.push(x as u8);
+    }
+
+    fn ivar(&mut self, x: i64) {
+        self.uvar(((x << 1) ^ (x >> 63)) as u64);
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.uvar(b.len() as u64);
+        self.buf.extend_from_slice(b);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+
+    /// Presence bits for the optional fields that follow (at most 8).
+    fn mask(&mut self, present: &[bool]) {
+        let m = present.iter().enumerate().fold(0u8, |m, (i, p)| if *p { m | (1 << i) } else { m });
+        self.u8(m);
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn u8(&mut self) -> Result<u8> {
+        let b = *self.buf.get(self.pos).context("truncated frame")?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn uvar(&mut self) -> Result<u64> {
+        let mut x = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.u8()?;
+            x |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(x);
+            }
+        }
+        anyhow::bail!("varint too long")
+    }
+
+    fn ivar(&mut self) -> Result<i64> {
+        let u = self.uvar()?;
+        Ok(((u >> 1) as i64) ^ -((u & 1) as i64))
+    }
+
+    fn bytes(&mut self) -> Result<&[u8]> {
+        let len = usize::try_from(self.uvar()?).context("length out of range")?;
+        let end = self.pos.checked_add(len).filter(|e| *e <= self.buf.len()).context("truncated frame")?;
+        let b = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(b)
+    }
+
+    fn str(&mut self) -> Result<String> {
+        Ok(std::str::from_utf8(self.bytes()?).context("invalid UTF-8")?.to_string())
+    }
+
+    fn mask(&mut self) -> Result<[bool; 8]> {
+        let m = self.u8()?;
+        Ok(std::array::from_fn(|i| m & (1 << i) != 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    const DEVICE: &str = "navscout-field-01";
+
+    fn env(seq: u64, t_s: i64, payload: Payload) -> Envelope {
+        Envelope { v: SCHEMA_VERSION, device_id: DEVICE.into(), seq, ts_unix_ms: 1_760_000_000_000 + t_s * 1000 + 37, payload }
+    }
+
+    /// Ten minutes at 1 Hz: out along a straight route, into the zone, a battery
+    /// RTH and the flight home, with health every 10 s.
+    fn flight() -> Vec<Envelope> {
+        let mut out = Vec::new();
+        let mut seq = 0;
+        let mut push = |out: &mut Vec<Envelope>, t: i64, p: Payload| {
+            out.push(env(seq, t, p));
+            seq += 1;
+        };
+        push(&mut out, 0, Payload::Startup(Startup { version: "2.6.8".into(), command: "run".into() }));
+        let mut state = MissionState::TransitToZone;
+        for t in 0..600i64 {
+            let leg = if t < 420 { t } else { 840 - t };
+            let (lat, lon) = (48.0 + leg as f64 * 7.3e-6, 2.0 + leg as f64 * 1.12e-5 + ((t % 7) as f64 - 3.0) * 1e-7);
+            let next = match t {
+                0..=239 => MissionState::TransitToZone,
+                240..=419 => MissionState::OperateInZone,
+                _ => MissionState::Rth,
+            };
+            if next != state {
+                if next == MissionState::Rth {
+                    push(&mut out, t, Payload::FailsafeTriggered(FailsafeTriggered { failsafe: Failsafe::Battery, detail: "battery low (22% <= 22%)".into() }));
+                    push(&mut out, t, Payload::FcCommand(FcCommand { command: FcCommandKind::Rtl, error: None }));
+                    push(&mut out, t, Payload::FcAck(FcAck { command_id: 20, result: 0 }));
+                }
+                push(&mut out, t, Payload::MissionStateChange(MissionStateChange { from: state, to: next, reason: format!("{:?}", next) }));
+                state = next;
+            }
+            let dist = leg as f32 * 1.3;
+            push(&mut out, t, Payload::Position(Position {
+                lat: (lat * 1e7).round() / 1e7,
+                lon: (lon * 1e7).round() / 1e7,
+                alt_m: Some(35.0 + (t % 5) as f32 / 10.0),
+                sats: 12 + (t % 3) as u8,
+                hdop: 0.8 + (t % 4) as f32 / 100.0,
+                state,
+                dist_home_m: (dist * 10.0).round() / 10.0,
+                cross_track_m: Some(((t % 9) as f32 * 0.3 * 10.0).round() / 10.0),
+                route_segment: Some((leg / 150) as u32),
+                route_progress: Some((leg as f32 / 420.0 * 1e4).round() / 1e4),
+                zone_edge_m: (state == MissionState::OperateInZone).then(|| ((t - 330) as f32 * 0.4 * 10.0).round() / 10.0),
+                time_in_state_s: (t % 240) as u64,
+                vision: (t % 2 == 0).then(|| Vision { detections: (t % 4) as u16, lock_id: (t > 300).then_some(17), lock_conf: Some(0.8) }),
+            }));
+            if t % 10 == 0 {
+                push(&mut out, t, Payload::Health(Health {
+                    battery_voltage: Some(16.6 - t as f32 * 0.004),
+                    battery_percent: Some((95 - t / 8) as u8),
+                    battery_current: Some(11.5),
+                    cpu_temp_c: Some(55.0 + (t % 30) as f32 / 10.0),
+                    link_rtt_ms: Some(120 + (t % 50) as u32),
+                    link_quality: Some(90),
+                }));
+            }
+        }
+        push(&mut out, 600, Payload::SpoolStats(SpoolStats { files: 12, bytes: 48_211, evicted: 0 }));
+        push(&mut out, 600, Payload::Shutdown(Shutdown { reason: "signal".into() }));
+        out
+    }
+
+    // Numbers within the coarsest fixed-point step that applies to them
+    fn assert_close(a: &Value, b: &Value, path: &str) {
+        match (a, b) {
+            (Value::Number(x), Value::Number(y)) => {
+                let (x, y) = (x.as_f64().unwrap(), y.as_f64().unwrap());
+                assert!((x - y).abs() <= 1e-6 * x.abs().max(1.0), "{}: {} != {}", path, x, y);
+            }
+            (Value::Object(x), Value::Object(y)) => {
+                assert_eq!(x.len(), y.len(), "{}", path);
+                for (k, v) in x {
+                    assert_close(v, &y[k], &format!("{}.{}", path, k));
+                }
+            }
+            (Value::Array(x), Value::Array(y)) => {
+                assert_eq!(x.len(), y.len(), "{}", path);
+                for (i, (v, w)) in x.iter().zip(y).enumerate() {
+                    assert_close(v, w, &format!("{}[{}]", path, i));
+                }
+            }
+            _ => assert_eq!(a, b, "{}", path),
+        }
+    }
+
+    fn assert_decodes_to(frame: &[u8], envs: &[Envelope]) {
+        let back = decode(frame).unwrap();
+        assert_close(&serde_json::to_value(&back).unwrap(), &serde_json::to_value(envs).unwrap(), "frame");
+    }
+
+    #[test]
+    fn flight_round_trips_in_every_format() {
+        let envs = flight();
+        for format in [Format::Json, Format::Binary] {
+            let frame = encode(format, &envs).unwrap();
+            assert_eq!(frame[0], if format == Format::Json { b'[' } else { FORMAT_BINARY });
+            assert_decodes_to(&frame, &envs);
+            let deflated = compress(Compression::Deflate, frame).unwrap();
+            assert_eq!(deflated[0], FORMAT_DEFLATE);
+            assert_decodes_to(&deflated, &envs);
+        }
+        // A lone JSON envelope is the bare pre-batching object
+        let one = encode(Format::Json, &envs[..1]).unwrap();
+        assert_eq!(one[0], b'{');
+        assert_decodes_to(&one, &envs[..1]);
+    }
+
+    #[test]
+    fn binary_is_smaller() {
+        let envs = flight();
+        let json = encode(Format::Json, &envs).unwrap();
+        let binary = encode(Format::Binary, &envs).unwrap();
+        let json_deflate = compress(Compression::Deflate, json.clone()).unwrap();
+        let binary_deflate = compress(Compression::Deflate, binary.clone()).unwrap();
+        assert!(binary.len() * 8 < json.len(), "binary {}B, json {}B", binary.len(), json.len());
+        assert!(binary_deflate.len() < json_deflate.len(), "binary+deflate {}B, json+deflate {}B", binary_deflate.len(), json_deflate.len());
+        assert!(binary_deflate.len() < binary.len());
+        // Per-position cost, which dominates a real spool
+        let positions = envs.iter().filter(|e| matches!(e.payload, Payload::Position(_))).count();
+        assert!(binary.len() / positions < 32, "{}B per position", binary.len() / positions);
+    }
+
+    #[test]
+    fn legacy_entries_in_binary() {
+        let ev: TelemetryEvent = serde_json::from_str(r#"{"ts_unix_ms":5,"kind":"Status","lat":1.0,"lon":2.0,"sats":7,
+            "hdop":0.9,"msg":"ok","battery_voltage":null,"battery_percent":null,"battery_current":null,"cpu_temp_c":null}"#).unwrap();
+        let envs = [Envelope { v: 1, device_id: String::new(), seq: 0, ts_unix_ms: 5, payload: Payload::Legacy(ev) }];
+        assert_decodes_to(&encode(Format::Binary, &envs).unwrap(), &envs);
+    }
+
+    #[test]
+    fn binary_rejects_mixed_devices() {
+        let mut envs = flight()[..3].to_vec();
+        envs[2].device_id = "other".into();
+        assert!(encode(Format::Binary, &envs).is_err());
+    }
+
+    #[test]
+    fn damaged_frames_are_errors() {
+        let binary = encode(Format::Binary, &flight()[..50]).unwrap();
+        for len in 0..binary.len() {
+            assert!(decode(&binary[..len]).is_err(), "truncated to {}", len);
+        }
+        let mut trailing = binary.clone();
+        trailing.push(0);
+        assert!(decode(&trailing).is_err());
+
+        let deflated = compress(Compression::Deflate, binary).unwrap();
+        assert!(decode(&deflated[..deflated.len() / 2]).is_err());
+        let nested = compress(Compression::Deflate, [vec![FORMAT_DEFLATE], vec![0; 64]].concat()).unwrap();
+        assert!(decode(&nested).is_err());
+        assert!(decode(&[0x42, 1, 2]).is_err());
+    }
+}
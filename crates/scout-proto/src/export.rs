@@ -0,0 +1,140 @@
+// Telemetry export for post-flight review: JSONL (one envelope per line), CSV
+// (one row per event, position columns empty for non-position events) and GPX
+// (the position track only).
+
+use anyhow::Result;
+use std::path::Path;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::telemetry::{Envelope, Payload};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Jsonl,
+    Csv,
+    Gpx,
+}
+
+impl ExportFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        match ext.as_str() {
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            "csv" => Ok(Self::Csv),
+            "gpx" => Ok(Self::Gpx),
+            other => anyhow::bail!("unknown export file extension '{}' (expected .jsonl, .csv or .gpx)", other),
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "jsonl" => Ok(Self::Jsonl),
+            "csv" => Ok(Self::Csv),
+            "gpx" => Ok(Self::Gpx),
+            other => anyhow::bail!("unknown export format '{}' (jsonl | csv | gpx)", other),
+        }
+    }
+}
+
+pub fn export(envs: &[Envelope], fmt: ExportFormat) -> Result<String> {
+    match fmt {
+        ExportFormat::Jsonl => jsonl(envs),
+        ExportFormat::Csv => csv(envs),
+        ExportFormat::Gpx => Ok(gpx(envs)),
+    }
+}
+
+/// RFC 3339 UTC timestamp; falls back to the raw number if out of range.
+pub fn rfc3339(ts_unix_ms: i64) -> String {
+    OffsetDateTime::from_unix_timestamp_nanos(ts_unix_ms as i128 * 1_000_000)
+        .ok()
+        .and_then(|t| t.format(&Rfc3339).ok())
+        .unwrap_or_else(|| ts_unix_ms.to_string())
+}
+
+/// Short name of the payload variant, as in the JSON `type` tag.
+pub fn payload_type(p: &Payload) -> &'static str {
+    match p {
+        Payload::Position(_) => "position",
+        Payload::MissionStateChange(_) => "mission_state_change",
+        Payload::FailsafeTriggered(_) => "failsafe_triggered",
+        Payload::FcCommand(_) => "fc_command",
+        Payload::FcAck(_) => "fc_ack",
+        Payload::Health(_) => "health",
+        Payload::SpoolStats(_) => "spool_stats",
+        Payload::Startup(_) => "startup",
+        Payload::Shutdown(_) => "shutdown",
+        Payload::KeyRotated(_) => "key_rotated",
+        Payload::Legacy(_) => "legacy",
+    }
+}
+
+fn jsonl(envs: &[Envelope]) -> Result<String> {
+    let mut out = String::new();
+    for e in envs {
+        out.push_str(&serde_json::to_string(e)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn csv(envs: &[Envelope]) -> Result<String> {
+    let mut out = String::from("device_id,seq,time,type,lat,lon,sats,hdop,state,dist_home_m,cross_track_m,route_progress,detail\n");
+    let opt = |v: Option<f32>| v.map(|v| v.to_string()).unwrap_or_default();
+    for e in envs {
+        let pos = match &e.payload {
+            Payload::Position(p) => format!(
+                "{},{},{},{},{},{},{},{}",
+                p.lat, p.lon, p.sats, p.hdop,
+                serde_json::to_value(p.state)?.as_str().unwrap_or_default(),
+                p.dist_home_m, opt(p.cross_track_m), opt(p.route_progress)
+            ),
+            Payload::Legacy(ev) => format!("{},{},{},{},,{},{},{}", ev.lat, ev.lon, ev.sats, ev.hdop, opt(ev.dist_home_m), opt(ev.cross_track_m), opt(ev.route_progress)),
+            _ => ",,,,,,,".to_string(),
+        };
+        // Everything else about the event, as compact JSON
+        let detail = match &e.payload {
+            Payload::Position(_) => String::new(),
+            p => serde_json::to_string(p)?,
+        };
+        out.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            csv_field(&e.device_id), e.seq, rfc3339(e.ts_unix_ms), payload_type(&e.payload), pos, csv_field(&detail)
+        ));
+    }
+    Ok(out)
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn gpx(envs: &[Envelope]) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<gpx version=\"1.1\" creator=\"NAVscout\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n");
+    let device = envs.first().map(|e| e.device_id.as_str()).unwrap_or("");
+    out.push_str(&format!("  <trk>\n    <name>{}</name>\n    <trkseg>\n", xml_escape(device)));
+    for e in envs {
+        let (lat, lon, alt, sats, hdop) = match &e.payload {
+            Payload::Position(p) => (p.lat, p.lon, p.alt_m, p.sats as i32, p.hdop),
+            Payload::Legacy(ev) => (ev.lat, ev.lon, None, ev.sats, ev.hdop),
+            _ => continue,
+        };
+        out.push_str(&format!("      <trkpt lat=\"{}\" lon=\"{}\">", lat, lon));
+        if let Some(a) = alt {
+            out.push_str(&format!("<ele>{}</ele>", a));
+        }
+        out.push_str(&format!("<time>{}</time><sat>{}</sat><hdop>{}</hdop></trkpt>\n", rfc3339(e.ts_unix_ms), sats, hdop));
+    }
+    out.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+    out
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
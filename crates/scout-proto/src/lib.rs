@@ -1 +1,4 @@
+pub mod codec;
+pub mod export;
+pub mod link;
 pub mod telemetry;
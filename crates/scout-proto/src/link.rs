@@ -0,0 +1,142 @@
+// Uplink session framing, spoken inside the TLS stream.
+//
+// client -> server:  HELLO, then frames: id u64 BE | len u32 BE | sealed blob
+// server -> client:  replies, in frame order:
+//                      ACK_TAG | id u64 BE
+//                      REJECT_TAG | id u64 BE | reason len u16 BE | reason (UTF-8)
+//
+// One frame is one spool file and its id is stable across connections, but
+// frames go out in priority order, so ids are not monotonic. An ack is
+// cumulative in send order: the named frame and every frame sent before it on
+// this connection are stored and may be deleted by the client. Unacked frames
+// are re-sent on the next connection, so receivers must tolerate (and drop)
+// duplicates. Within one priority class frames go out oldest first, so the
+// sequence numbers in their blob headers arrive in order.
+//
+// A reject names a frame the server can never accept (e.g. replayed or
+// sealed with a key it does not have). It is cumulative like an ack, except
+// that the client sets the named frame aside instead of deleting it, and the
+// session carries on. Errors that may clear on retry close the session
+// without a reply, so the frame is re-sent.
+
+use anyhow::Result;
+
+/// AEAD label of every sealed telemetry blob, authenticated ahead of its
+/// device/boot/sequence header (`scout_crypto::aead::seal_bound`).
+pub const TELEMETRY_AAD: &[u8] = b"navscout-telemetry-v1";
+
+pub const HELLO: &[u8; 4] = b"NSU2";
+pub const FRAME_HEADER_LEN: usize = 12;
+pub const ACK_TAG: u8 = b'A';
+pub const ACK_LEN: usize = 9;
+pub const REJECT_TAG: u8 = b'R';
+/// Reject reasons longer than this are cut.
+pub const MAX_REASON_LEN: usize = 1024;
+/// Receivers reject frames larger than this.
+pub const MAX_BLOB_BYTES: u32 = 8 * 1024 * 1024;
+
+pub fn frame_header(id: u64, blob_len: usize) -> Result<[u8; FRAME_HEADER_LEN]> {
+    let len = u32::try_from(blob_len).ok().filter(|l| *l <= MAX_BLOB_BYTES);
+    let len = len.ok_or_else(|| anyhow::anyhow!("frame of {} bytes exceeds {}", blob_len, MAX_BLOB_BYTES))?;
+    let mut h = [0u8; FRAME_HEADER_LEN];
+    h[..8].copy_from_slice(&id.to_be_bytes());
+    h[8..].copy_from_slice(&len.to_be_bytes());
+    Ok(h)
+}
+
+/// Returns (frame id, blob length).
+pub fn parse_frame_header(h: &[u8; FRAME_HEADER_LEN]) -> Result<(u64, u32)> {
+    let id = u64::from_be_bytes(h[..8].try_into().unwrap());
+    let len = u32::from_be_bytes(h[8..].try_into().unwrap());
+    anyhow::ensure!(len <= MAX_BLOB_BYTES, "frame {} announces {} bytes (max {})", id, len, MAX_BLOB_BYTES);
+    Ok((id, len))
+}
+
+pub fn ack(id: u64) -> [u8; ACK_LEN] {
+    let mut a = [0u8; ACK_LEN];
+    a[0] = ACK_TAG;
+    a[1..].copy_from_slice(&id.to_be_bytes());
+    a
+}
+
+pub fn reject(id: u64, reason: &str) -> Vec<u8> {
+    let mut end = reason.len().min(MAX_REASON_LEN);
+    while !reason.is_char_boundary(end) {
+        end -= 1;
+    }
+    let mut r = Vec::with_capacity(ACK_LEN + 2 + end);
+    r.push(REJECT_TAG);
+    r.extend_from_slice(&id.to_be_bytes());
+    r.extend_from_slice(&(end as u16).to_be_bytes());
+    r.extend_from_slice(&reason.as_bytes()[..end]);
+    r
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    Ack(u64),
+    Reject { id: u64, reason: String },
+}
+
+/// The first reply in `buf` and its length in bytes; `None` until `buf`
+/// holds a whole reply.
+pub fn parse_reply(buf: &[u8]) -> Result<Option<(Reply, usize)>> {
+    let Some(&tag) = buf.first() else { return Ok(None) };
+    anyhow::ensure!(tag == ACK_TAG || tag == REJECT_TAG, "bad reply tag 0x{:02x}", tag);
+    if buf.len() < ACK_LEN {
+        return Ok(None);
+    }
+    let id = u64::from_be_bytes(buf[1..ACK_LEN].try_into().unwrap());
+    if tag == ACK_TAG {
+        return Ok(Some((Reply::Ack(id), ACK_LEN)));
+    }
+    let Some(len) = buf.get(ACK_LEN..ACK_LEN + 2) else { return Ok(None) };
+    let len = u16::from_be_bytes(len.try_into().unwrap()) as usize;
+    anyhow::ensure!(len <= MAX_REASON_LEN, "reject reason of {} bytes (max {})", len, MAX_REASON_LEN);
+    let Some(reason) = buf.get(ACK_LEN + 2..ACK_LEN + 2 + len) else { return Ok(None) };
+    let reason = String::from_utf8_lossy(reason).into_owned();
+    Ok(Some((Reply::Reject { id, reason }, ACK_LEN + 2 + len)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies_parse_incrementally() {
+        let stream = [ack(7).to_vec(), reject(9, "seq 3 is older than the replay window"), ack(11).to_vec()].concat();
+        let mut buf = Vec::new();
+        let mut got = Vec::new();
+        // One byte at a time, as a slow link may deliver them
+        for b in stream {
+            buf.push(b);
+            while let Some((reply, n)) = parse_reply(&buf).unwrap() {
+                buf.drain(..n);
+                got.push(reply);
+            }
+        }
+        assert!(buf.is_empty());
+        assert_eq!(got, [
+            Reply::Ack(7),
+            Reply::Reject { id: 9, reason: "seq 3 is older than the replay window".into() },
+            Reply::Ack(11),
+        ]);
+    }
+
+    #[test]
+    fn long_reasons_are_cut_on_a_char_boundary() {
+        let r = reject(1, &"é".repeat(MAX_REASON_LEN));
+        let (reply, n) = parse_reply(&r).unwrap().unwrap();
+        assert_eq!(n, r.len());
+        let Reply::Reject { reason, .. } = reply else { panic!("not a reject") };
+        assert_eq!(reason, "é".repeat(MAX_REASON_LEN / 2));
+    }
+
+    #[test]
+    fn bad_replies_are_errors() {
+        assert!(parse_reply(b"X").is_err());
+        let mut r = reject(1, "");
+        r[ACK_LEN..ACK_LEN + 2].copy_from_slice(&(MAX_REASON_LEN as u16 + 1).to_be_bytes());
+        assert!(parse_reply(&r).is_err());
+    }
+}
@@ -1,5 +1,225 @@
 use serde::{Deserialize, Serialize};
 
+/// Version of the `Envelope` schema. Legacy `TelemetryEvent` frames are version 1.
+pub const SCHEMA_VERSION: u16 = 2;
+
+/// One telemetry message as sent over the uplink.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Schema version (`SCHEMA_VERSION` for frames produced by this build)
+    pub v: u16,
+    pub device_id: String,
+    /// Monotonic per device; gaps mean lost or evicted frames
+    pub seq: u64,
+    pub ts_unix_ms: i64,
+    pub payload: Payload,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Payload {
+    Position(Position),
+    MissionStateChange(MissionStateChange),
+    FailsafeTriggered(FailsafeTriggered),
+    FcCommand(FcCommand),
+    FcAck(FcAck),
+    Health(Health),
+    SpoolStats(SpoolStats),
+    Startup(Startup),
+    Shutdown(Shutdown),
+    KeyRotated(KeyRotated),
+    /// A version 1 `TelemetryEvent`, as decoded from an old spool or sender
+    Legacy(TelemetryEvent),
+}
+
+/// Delivery class: the spool flushes higher classes first and evicts lower ones first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// Position reports
+    Routine = 0,
+    /// Health, spool stats and lifecycle events
+    Health = 1,
+    /// Failsafes, state changes and flight controller commands/acks
+    Critical = 2,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 3] = [Priority::Routine, Priority::Health, Priority::Critical];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Priority::Routine => "routine",
+            Priority::Health => "health",
+            Priority::Critical => "critical",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == s)
+    }
+}
+
+impl Payload {
+    pub fn priority(&self) -> Priority {
+        match self {
+            Payload::FailsafeTriggered(_) | Payload::MissionStateChange(_) | Payload::FcCommand(_) | Payload::FcAck(_) => Priority::Critical,
+            Payload::Health(_) | Payload::SpoolStats(_) | Payload::Startup(_) | Payload::Shutdown(_) | Payload::KeyRotated(_) => Priority::Health,
+            Payload::Position(_) => Priority::Routine,
+            Payload::Legacy(ev) => match ev.kind {
+                EventKind::Rth | EventKind::Abort => Priority::Critical,
+                EventKind::Status => Priority::Routine,
+            },
+        }
+    }
+}
+
+// Discriminants of the enums below are their binary wire codes (see `codec`).
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissionState {
+    Idle = 0,
+    TransitToZone = 1,
+    OperateInZone = 2,
+    Rth = 3,
+    Land = 4,
+    Abort = 5,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Failsafe {
+    MaxRadius = 0,
+    Gnss = 1,
+    Battery = 2,
+    Thermal = 3,
+    LinkLoss = 4,
+    Geofence = 5,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FcCommandKind {
+    Rtl = 0,
+    Hold = 1,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Position {
+    pub lat: f64,
+    pub lon: f64,
+    pub alt_m: Option<f32>,
+    pub sats: u8,
+    pub hdop: f32,
+    pub state: MissionState,
+    // Navigation metrics (see scout_nav::nav::NavMetrics)
+    pub dist_home_m: f32,
+    pub cross_track_m: Option<f32>,
+    pub route_segment: Option<u32>,
+    pub route_progress: Option<f32>,
+    pub zone_edge_m: Option<f32>,
+    pub time_in_state_s: u64,
+    pub vision: Option<Vision>,
+}
+
+/// Vision summary for the frame inferred closest to this position.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Vision {
+    pub detections: u16,
+    pub lock_id: Option<u64>,
+    pub lock_conf: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MissionStateChange {
+    pub from: MissionState,
+    pub to: MissionState,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailsafeTriggered {
+    pub failsafe: Failsafe,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FcCommand {
+    pub command: FcCommandKind,
+    /// Why the command was not sent (gated, no FC link, ...); `None` if it went out
+    pub error: Option<String>,
+}
+
+/// MAVLink COMMAND_ACK as received from the autopilot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FcAck {
+    /// MAV_CMD id
+    pub command_id: u16,
+    /// MAV_RESULT value
+    pub result: u8,
+}
+
+impl FcAck {
+    pub fn accepted(&self) -> bool {
+        self.result == 0 // MAV_RESULT_ACCEPTED
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Health {
+    pub battery_voltage: Option<f32>,
+    pub battery_percent: Option<u8>,
+    pub battery_current: Option<f32>,
+    pub cpu_temp_c: Option<f32>,
+    pub link_rtt_ms: Option<u32>,
+    pub link_quality: Option<u8>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpoolStats {
+    pub files: u64,
+    pub bytes: u64,
+    /// Files evicted by the size limit since startup
+    pub evicted: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Startup {
+    pub version: String,
+    pub command: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Shutdown {
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyRotated {
+    /// Fingerprint of the new device key (never the key itself)
+    pub key_id: String,
+}
+
+/// Decode a JSON frame of either schema version. Version 1 frames
+/// (flat `TelemetryEvent`, no `v` field) come back as `Payload::Legacy`.
+pub fn decode_json(bytes: &[u8]) -> serde_json::Result<Envelope> {
+    let value: serde_json::Value = serde_json::from_slice(bytes)?;
+    if value.get("v").is_some() {
+        return serde_json::from_value(value);
+    }
+    let ev: TelemetryEvent = serde_json::from_value(value)?;
+    Ok(Envelope {
+        v: 1,
+        device_id: String::new(),
+        seq: 0,
+        ts_unix_ms: ev.ts_unix_ms,
+        payload: Payload::Legacy(ev),
+    })
+}
+
+// ----- Version 1 (legacy) -----
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum EventKind {
     Status,
@@ -7,6 +227,7 @@ pub enum EventKind {
     Abort,
 }
 
+/// Version 1 flat event. No longer produced; kept so old frames stay decodable.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TelemetryEvent {
     pub ts_unix_ms: i64,
@@ -25,4 +246,95 @@ pub struct TelemetryEvent {
     // Link health
     pub link_rtt_ms: Option<u32>,
     pub link_quality: Option<u8>,
+    // Navigation metrics (see scout_nav::nav::NavMetrics)
+    pub dist_home_m: Option<f32>,
+    pub cross_track_m: Option<f32>,
+    pub route_segment: Option<u32>,
+    pub route_progress: Option<f32>,
+    pub zone_edge_m: Option<f32>,
+    pub time_in_state_s: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One of each payload variant, with optional fields both set and unset.
+    fn every_payload() -> Vec<Payload> {
+        vec![
+            Payload::Position(Position {
+                lat: 48.000123, lon: 2.000456, alt_m: Some(35.5), sats: 14, hdop: 0.8,
+                state: MissionState::TransitToZone,
+                dist_home_m: 42.5, cross_track_m: Some(1.25), route_segment: Some(1),
+                route_progress: Some(0.25), zone_edge_m: None, time_in_state_s: 12,
+                vision: Some(Vision { detections: 3, lock_id: Some(7), lock_conf: Some(0.75) }),
+            }),
+            Payload::MissionStateChange(MissionStateChange {
+                from: MissionState::OperateInZone, to: MissionState::Rth, reason: "RTH: battery low".into(),
+            }),
+            Payload::FailsafeTriggered(FailsafeTriggered { failsafe: Failsafe::Battery, detail: "21% <= 22%".into() }),
+            Payload::FcCommand(FcCommand { command: FcCommandKind::Rtl, error: None }),
+            Payload::FcCommand(FcCommand { command: FcCommandKind::Hold, error: Some("gated".into()) }),
+            Payload::FcAck(FcAck { command_id: 20, result: 0 }),
+            Payload::Health(Health {
+                battery_voltage: Some(15.1), battery_percent: Some(64), battery_current: None,
+                cpu_temp_c: Some(61.5), link_rtt_ms: Some(180), link_quality: Some(80),
+            }),
+            Payload::SpoolStats(SpoolStats { files: 3, bytes: 4096, evicted: 1 }),
+            Payload::Startup(Startup { version: "2.6.8".into(), command: "run".into() }),
+            Payload::Shutdown(Shutdown { reason: "signal".into() }),
+            Payload::KeyRotated(KeyRotated { key_id: "35c364a2".into() }),
+            Payload::Legacy(legacy_event()),
+        ]
+    }
+
+    fn legacy_event() -> TelemetryEvent {
+        serde_json::from_str(LEGACY_JSON).unwrap()
+    }
+
+    // As written by version 1 senders
+    const LEGACY_JSON: &str = r#"{"ts_unix_ms":1760000000000,"kind":"Rth","lat":48.0001,"lon":2.0002,"sats":9,"hdop":1.2,
+        "msg":"RTH: GNSS bad for 8s","battery_voltage":14.8,"battery_percent":55,"battery_current":11.5,
+        "cpu_temp_c":null,"link_rtt_ms":null,"link_quality":null,"dist_home_m":120.5,"cross_track_m":2.5,
+        "route_segment":1,"route_progress":0.4,"zone_edge_m":null,"time_in_state_s":3}"#;
+
+    #[test]
+    fn legacy_json_decodes_as_legacy() {
+        let env = decode_json(LEGACY_JSON.as_bytes()).unwrap();
+        assert_eq!((env.v, env.seq, env.ts_unix_ms), (1, 0, 1_760_000_000_000));
+        let Payload::Legacy(ev) = &env.payload else { panic!("{:?}", env.payload) };
+        assert!(matches!(ev.kind, EventKind::Rth));
+        assert_eq!((ev.lat, ev.lon, ev.sats), (48.0001, 2.0002, 9));
+        assert_eq!(ev.msg, "RTH: GNSS bad for 8s");
+        assert_eq!(ev.battery_percent, Some(55));
+        assert_eq!(env.payload.priority(), Priority::Critical);
+    }
+
+    #[test]
+    fn legacy_json_without_newer_fields() {
+        // Version 1 senders before nav metrics and link health omit those fields
+        let old = r#"{"ts_unix_ms":1,"kind":"Status","lat":1.0,"lon":2.0,"sats":7,"hdop":0.9,"msg":"ok",
+            "battery_voltage":null,"battery_percent":null,"battery_current":null,"cpu_temp_c":50.0}"#;
+        let env = decode_json(old.as_bytes()).unwrap();
+        let Payload::Legacy(ev) = &env.payload else { panic!("{:?}", env.payload) };
+        assert_eq!((ev.cpu_temp_c, ev.link_rtt_ms, ev.dist_home_m), (Some(50.0), None, None));
+        assert_eq!(env.payload.priority(), Priority::Routine);
+    }
+
+    #[test]
+    fn every_payload_round_trips() {
+        for (seq, payload) in every_payload().into_iter().enumerate() {
+            let env = Envelope { v: SCHEMA_VERSION, device_id: "dev-1".into(), seq: seq as u64, ts_unix_ms: 1_760_000_000_000, payload };
+            let json = serde_json::to_vec(&env).unwrap();
+            let back = decode_json(&json).unwrap();
+            assert_eq!(serde_json::to_value(&back).unwrap(), serde_json::to_value(&env).unwrap());
+            assert_eq!(back.payload.priority(), env.payload.priority());
+        }
+    }
+
+    #[test]
+    fn unknown_payload_type_is_an_error() {
+        let json = r#"{"v":2,"device_id":"d","seq":0,"ts_unix_ms":0,"payload":{"type":"teleport"}}"#;
+        assert!(decode_json(json.as_bytes()).is_err());
+    }
 }
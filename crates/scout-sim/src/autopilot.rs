@@ -0,0 +1,79 @@
+use mavlink::{
+    common::{MavMessage, HEARTBEAT_DATA, MavAutopilot, MavModeFlag, MavState, MavType, SYS_STATUS_DATA},
+    error::{MessageReadError, MessageWriteError},
+    MavConnection, MavHeader, MavlinkVersion,
+};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// In-memory MAVLink "wire" between the companion (`FcLink`) and the simulated autopilot.
+#[derive(Debug, Default)]
+pub struct Mailbox {
+    /// Messages the companion sent, waiting for the autopilot.
+    pub to_fc: VecDeque<MavMessage>,
+    /// Messages the autopilot produced, waiting for the companion.
+    pub to_companion: VecDeque<(MavHeader, MavMessage)>,
+}
+
+pub type SharedMailbox = Arc<Mutex<Mailbox>>;
+
+/// `MavConnection` backed by a `Mailbox`, so `FcLink` can talk to the simulator
+/// exactly as it talks to a serial port. `recv` never blocks: an empty mailbox
+/// reads as `WouldBlock`, which `FcLink::poll_once_nonblocking` maps to `None`.
+pub struct SimMavConnection {
+    mailbox: SharedMailbox,
+    version: MavlinkVersion,
+}
+
+impl SimMavConnection {
+    pub fn new(mailbox: SharedMailbox) -> Self {
+        Self { mailbox, version: MavlinkVersion::V2 }
+    }
+}
+
+impl MavConnection<MavMessage> for SimMavConnection {
+    fn recv(&self) -> Result<(MavHeader, MavMessage), MessageReadError> {
+        self.mailbox.lock().unwrap().to_companion.pop_front()
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::WouldBlock).into())
+    }
+
+    fn send(&self, _header: &MavHeader, data: &MavMessage) -> Result<usize, MessageWriteError> {
+        self.mailbox.lock().unwrap().to_fc.push_back(data.clone());
+        Ok(0)
+    }
+
+    fn set_protocol_version(&mut self, version: MavlinkVersion) {
+        self.version = version;
+    }
+
+    fn get_protocol_version(&self) -> MavlinkVersion {
+        self.version
+    }
+}
+
+pub fn fc_header(sequence: u8) -> MavHeader {
+    MavHeader { system_id: 1, component_id: 1, sequence }
+}
+
+pub fn heartbeat() -> MavMessage {
+    MavMessage::HEARTBEAT(HEARTBEAT_DATA {
+        custom_mode: 0,
+        mavtype: MavType::MAV_TYPE_QUADROTOR,
+        autopilot: MavAutopilot::MAV_AUTOPILOT_ARDUPILOTMEGA,
+        base_mode: MavModeFlag::MAV_MODE_FLAG_CUSTOM_MODE_ENABLED,
+        system_status: MavState::MAV_STATE_ACTIVE,
+        mavlink_version: 3,
+    })
+}
+
+/// 4S pack: 13.2V empty .. 16.8V full, linear in remaining charge.
+pub fn sys_status(battery_pct: f32, current_a: f32) -> MavMessage {
+    let pct = battery_pct.clamp(0.0, 100.0);
+    let volts = 13.2 + (16.8 - 13.2) * pct / 100.0;
+    MavMessage::SYS_STATUS(SYS_STATUS_DATA {
+        voltage_battery: (volts * 1000.0) as u16,
+        current_battery: (current_a * 100.0) as i16,
+        battery_remaining: pct.round() as i8,
+        ..Default::default()
+    })
+}
@@ -0,0 +1,59 @@
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultKind {
+    /// GGA reports no fix (0 sats); RMC keeps the last position flagged invalid.
+    GnssDropout,
+    /// HDOP jumps to `value` (default 8.0) with a reduced satellite count.
+    HdopSpike,
+    /// Reported battery drops by `value` percent (default 20) for the window.
+    BatterySag,
+    /// CPU temperature rises by `value` degrees C (default 25) for the window.
+    ThermalRise,
+    /// Uplink unreachable for the window.
+    LinkLoss,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FaultCfg {
+    pub kind: FaultKind,
+    /// Simulated seconds after start
+    pub at_s: f64,
+    /// Window length; omitted means "until the end of the run"
+    pub duration_s: Option<f64>,
+    pub value: Option<f32>,
+}
+
+impl FaultCfg {
+    pub fn active_at(&self, t_s: f64) -> bool {
+        t_s >= self.at_s && self.duration_s.map(|d| t_s < self.at_s + d).unwrap_or(true)
+    }
+}
+
+/// Combined effect of all faults active at one instant.
+#[derive(Debug, Clone, Default)]
+pub struct FaultEffects {
+    pub gnss_dropout: bool,
+    pub hdop_override: Option<f32>,
+    pub battery_sag_pct: f32,
+    pub thermal_rise_c: f32,
+    pub link_down: bool,
+}
+
+pub fn effects_at(faults: &[FaultCfg], t_s: f64) -> FaultEffects {
+    let mut fx = FaultEffects::default();
+    for f in faults.iter().filter(|f| f.active_at(t_s)) {
+        match f.kind {
+            FaultKind::GnssDropout => fx.gnss_dropout = true,
+            FaultKind::HdopSpike => {
+                let h = f.value.unwrap_or(8.0);
+                fx.hdop_override = Some(fx.hdop_override.map_or(h, |o| o.max(h)));
+            }
+            FaultKind::BatterySag => fx.battery_sag_pct += f.value.unwrap_or(20.0),
+            FaultKind::ThermalRise => fx.thermal_rise_c += f.value.unwrap_or(25.0),
+            FaultKind::LinkLoss => fx.link_down = true,
+        }
+    }
+    fx
+}
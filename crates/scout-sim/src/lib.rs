@@ -0,0 +1,405 @@
+pub mod autopilot;
+pub mod faults;
+pub mod nmea;
+pub mod vehicle;
+
+use anyhow::Result;
+use mavlink::common::{MavCmd, MavMessage, MavResult, COMMAND_ACK_DATA};
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+
+use scout_nav::nav::{Home, RouteCfg};
+
+use autopilot::{Mailbox, SharedMailbox, SimMavConnection};
+use faults::{FaultCfg, FaultKind};
+use vehicle::{FlightMode, Vehicle, VehicleEvent};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SimConfig {
+    /// Ground speed along the route. Default 8 m/s.
+    pub speed_mps: Option<f64>,
+    /// Simulation step. Default 200 ms (5 Hz GNSS).
+    pub step_ms: Option<u64>,
+    /// Hard stop in simulated seconds. Default 900.
+    pub max_duration_s: Option<f64>,
+
+    /// Battery model: linear drain from the starting charge.
+    pub battery_start_pct: Option<f32>,
+    pub battery_drain_pct_per_min: Option<f32>,
+
+    /// CPU temperature baseline (before thermal faults).
+    pub cpu_temp_c: Option<f32>,
+
+    /// Nominal GNSS quality when no fault is active.
+    pub sats: Option<u8>,
+    pub hdop: Option<f32>,
+
+    /// Fault schedule, e.g.
+    /// faults = [{ kind = "gnss_dropout", at_s = 60, duration_s = 12 }]
+    #[serde(default)]
+    pub faults: Vec<FaultCfg>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelineKind {
+    Fault,
+    Vehicle,
+    Fc,
+    Nav,
+}
+
+#[derive(Debug, Clone)]
+pub struct TimelineEntry {
+    pub t_s: f64,
+    pub kind: TimelineKind,
+    pub text: String,
+}
+
+/// What the companion sees for one simulation step.
+#[derive(Debug, Clone)]
+pub struct SimTick {
+    pub ts: OffsetDateTime,
+    pub t_s: f64,
+    /// One GGA + one RMC sentence, in that order
+    pub nmea: Vec<String>,
+    pub cpu_temp_c: f32,
+    /// Seconds the uplink has been down, if it is down
+    pub link_down_s: Option<u64>,
+}
+
+pub struct Simulator {
+    cfg: SimConfig,
+    vehicle: Vehicle,
+    mailbox: SharedMailbox,
+    start: OffsetDateTime,
+    t_s: f64,
+    step_s: f64,
+    last_status_s: Option<f64>,
+    fc_seq: u8,
+    link_down_since: Option<f64>,
+    active: Vec<bool>,
+    timeline: Vec<TimelineEntry>,
+}
+
+impl Simulator {
+    pub fn new(cfg: SimConfig, home: Home, route: &RouteCfg, cruise_alt_m: f32) -> Result<Self> {
+        let speed = cfg.speed_mps.unwrap_or(8.0);
+        anyhow::ensure!(speed > 0.0, "sim.speed_mps must be > 0");
+        let step_ms = cfg.step_ms.unwrap_or(200);
+        anyhow::ensure!((10..=1000).contains(&step_ms), "sim.step_ms should be 10..1000");
+        for (i, f) in cfg.faults.iter().enumerate() {
+            anyhow::ensure!(f.at_s >= 0.0, "sim.faults[{}].at_s must be >= 0", i);
+            anyhow::ensure!(f.duration_s.map(|d| d > 0.0).unwrap_or(true), "sim.faults[{}].duration_s must be > 0", i);
+        }
+
+        let vehicle = Vehicle::new(home, route.waypoints.clone(), cruise_alt_m, speed);
+        let active = vec![false; cfg.faults.len()];
+        Ok(Self {
+            cfg,
+            vehicle,
+            mailbox: Arc::new(Mutex::new(Mailbox::default())),
+            start: OffsetDateTime::now_utc(),
+            t_s: 0.0,
+            step_s: step_ms as f64 / 1000.0,
+            last_status_s: None,
+            fc_seq: 0,
+            link_down_since: None,
+            active,
+            timeline: Vec::new(),
+        })
+    }
+
+    /// MAVLink connection for `FcLink::from_connection`.
+    pub fn mav_connection(&self) -> Box<SimMavConnection> {
+        Box::new(SimMavConnection::new(self.mailbox.clone()))
+    }
+
+    pub fn vehicle(&self) -> &Vehicle {
+        &self.vehicle
+    }
+
+    pub fn elapsed_s(&self) -> f64 {
+        self.t_s
+    }
+
+    pub fn finished(&self) -> bool {
+        self.vehicle.mode == FlightMode::Landed
+            || self.t_s >= self.cfg.max_duration_s.unwrap_or(900.0)
+    }
+
+    pub fn note(&mut self, kind: TimelineKind, text: impl Into<String>) {
+        self.timeline.push(TimelineEntry { t_s: self.t_s, kind, text: text.into() });
+    }
+
+    pub fn timeline(&self) -> &[TimelineEntry] {
+        &self.timeline
+    }
+
+    /// Advance the world by one step and return the sensor outputs for it.
+    pub fn step(&mut self) -> SimTick {
+        self.t_s += self.step_s;
+        let t = self.t_s;
+        let ts = self.start + time::Duration::seconds_f64(t);
+
+        self.handle_fc_commands();
+        self.track_fault_windows();
+
+        if let Some(ev) = self.vehicle.step(self.step_s) {
+            let text = match ev {
+                VehicleEvent::WaypointReached(i) => format!("waypoint {} reached", i),
+                VehicleEvent::RouteComplete => "route complete, loitering".to_string(),
+                VehicleEvent::HomeReached => "home reached, descending".to_string(),
+                VehicleEvent::Landed => "landed".to_string(),
+            };
+            self.note(TimelineKind::Vehicle, text);
+        }
+
+        let fx = faults::effects_at(&self.cfg.faults, t);
+
+        // Autopilot telemetry at 1 Hz
+        if self.last_status_s.map(|s| t - s >= 1.0).unwrap_or(true) {
+            self.last_status_s = Some(t);
+            let drain = self.cfg.battery_drain_pct_per_min.unwrap_or(1.5) * (t / 60.0) as f32;
+            let pct = self.cfg.battery_start_pct.unwrap_or(100.0) - drain - fx.battery_sag_pct;
+            let current = if self.vehicle.mode == FlightMode::Landed { 0.5 } else { 12.0 };
+            self.push_to_companion(autopilot::heartbeat());
+            self.push_to_companion(autopilot::sys_status(pct, current));
+        }
+
+        let v = &self.vehicle;
+        let (sats, hdop) = if fx.gnss_dropout {
+            (0, 99.9)
+        } else if let Some(h) = fx.hdop_override {
+            (5, h)
+        } else {
+            (self.cfg.sats.unwrap_or(12), self.cfg.hdop.unwrap_or(0.9))
+        };
+        let nmea = vec![
+            nmea::gga(ts, v.lat, v.lon, v.alt_m, sats, hdop),
+            nmea::rmc(ts, v.lat, v.lon, v.ground_speed_mps(), v.course_deg, !fx.gnss_dropout),
+        ];
+
+        if fx.link_down {
+            self.link_down_since.get_or_insert(t);
+        } else {
+            self.link_down_since = None;
+        }
+
+        SimTick {
+            ts,
+            t_s: t,
+            nmea,
+            cpu_temp_c: self.cfg.cpu_temp_c.unwrap_or(55.0) + fx.thermal_rise_c,
+            link_down_s: self.link_down_since.map(|s| (t - s) as u64),
+        }
+    }
+
+    fn push_to_companion(&mut self, msg: MavMessage) {
+        self.fc_seq = self.fc_seq.wrapping_add(1);
+        let hdr = autopilot::fc_header(self.fc_seq);
+        self.mailbox.lock().unwrap().to_companion.push_back((hdr, msg));
+    }
+
+    // Only RTL and HOLD are accepted, mirroring what FcLink is allowed to send.
+    fn handle_fc_commands(&mut self) {
+        let inbox: Vec<MavMessage> = self.mailbox.lock().unwrap().to_fc.drain(..).collect();
+        for msg in inbox {
+            let MavMessage::COMMAND_LONG(cmd) = msg else { continue };
+            let result = match cmd.command {
+                MavCmd::MAV_CMD_NAV_RETURN_TO_LAUNCH => {
+                    self.vehicle.set_mode(FlightMode::Rtl);
+                    MavResult::MAV_RESULT_ACCEPTED
+                }
+                MavCmd::MAV_CMD_NAV_LOITER_UNLIM => {
+                    self.vehicle.set_mode(FlightMode::Loiter);
+                    MavResult::MAV_RESULT_ACCEPTED
+                }
+                _ => MavResult::MAV_RESULT_UNSUPPORTED,
+            };
+            self.note(TimelineKind::Fc, format!("recv {:?} -> {:?} (mode={:?})", cmd.command, result, self.vehicle.mode));
+            self.push_to_companion(MavMessage::COMMAND_ACK(COMMAND_ACK_DATA { command: cmd.command, result }));
+        }
+    }
+
+    fn track_fault_windows(&mut self) {
+        for i in 0..self.cfg.faults.len() {
+            let f = &self.cfg.faults[i];
+            let now_active = f.active_at(self.t_s);
+            if now_active == self.active[i] { continue; }
+            self.active[i] = now_active;
+            let text = format!(
+                "{} {}{}",
+                fault_name(f.kind),
+                if now_active { "start" } else { "end" },
+                f.value.filter(|_| now_active).map(|v| format!(" (value={})", v)).unwrap_or_default(),
+            );
+            self.note(TimelineKind::Fault, text);
+        }
+    }
+}
+
+fn fault_name(kind: FaultKind) -> &'static str {
+    match kind {
+        FaultKind::GnssDropout => "gnss_dropout",
+        FaultKind::HdopSpike => "hdop_spike",
+        FaultKind::BatterySag => "battery_sag",
+        FaultKind::ThermalRise => "thermal_rise",
+        FaultKind::LinkLoss => "link_loss",
+    }
+}
+
+impl std::fmt::Display for TimelineKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            TimelineKind::Fault => "fault",
+            TimelineKind::Vehicle => "vehicle",
+            TimelineKind::Fc => "fc",
+            TimelineKind::Nav => "nav",
+        };
+        f.pad(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use faults::FaultCfg;
+    use scout_fc::mav::FcLink;
+    use scout_nav::gnss::{FixQuality, GnssFix};
+    use scout_nav::nav::{Failsafe, HealthInputs, MissionState, NavEngine, Point, RthPolicy, ZoneCfg};
+
+    // The route and zone of configs/field_drone.toml
+    fn home() -> Home {
+        Home { lat: 48.0, lon: 2.0, alt_m: 35.0 }
+    }
+
+    fn route() -> RouteCfg {
+        RouteCfg {
+            corridor_width_m: 30.0,
+            waypoints: vec![
+                Point { lat: 48.000100, lon: 2.000050 },
+                Point { lat: 48.000600, lon: 2.001000 },
+                Point { lat: 48.001050, lon: 2.002300 },
+            ],
+        }
+    }
+
+    fn zone() -> ZoneCfg {
+        ZoneCfg {
+            garden_polygon: vec![
+                Point { lat: 48.001100, lon: 2.002100 },
+                Point { lat: 48.001200, lon: 2.002400 },
+                Point { lat: 48.001000, lon: 2.002500 },
+                Point { lat: 48.000900, lon: 2.002200 },
+            ],
+        }
+    }
+
+    fn vehicle_events(sim: &Simulator) -> Vec<String> {
+        sim.timeline().iter().filter(|e| e.kind == TimelineKind::Vehicle).map(|e| e.text.clone()).collect()
+    }
+
+    struct Flight {
+        sim: Simulator,
+        states: Vec<MissionState>,
+        failsafe: Option<Failsafe>,
+        rth_at_s: Option<f64>,
+    }
+
+    /// The closed loop of `scout sim`: nav engine on the simulated position,
+    /// battery from SYS_STATUS over MAVLink, RTL sent through `FcLink`.
+    fn fly(cfg: SimConfig) -> Flight {
+        let mut sim = Simulator::new(cfg, home(), &route(), 35.0).unwrap();
+        let mut link = FcLink::from_connection(sim.mav_connection(), 254, 190, 1, 1, true, false, true);
+        let policy = RthPolicy { grace_link_loss_s: 20, gnss_bad_fix_s: 8, battery_low_pct: 22, thermal_soft_c: 75 };
+        let mut nav = NavEngine::new(home(), route(), zone(), 1200.0, policy);
+        let (mut states, mut failsafe, mut rth_at_s) = (Vec::new(), None, None);
+
+        while !sim.finished() {
+            let tick = sim.step();
+            while let Ok(Some(_)) = link.poll_once_nonblocking() {}
+            let v = sim.vehicle();
+            let fix = GnssFix {
+                lat: v.lat,
+                lon: v.lon,
+                quality: FixQuality { sats: 12, hdop: 0.9, fix_age_s: 0 },
+                ts: tick.ts,
+            };
+            let health = HealthInputs {
+                battery_pct: link.battery_status().remaining,
+                cpu_temp_c: Some(tick.cpu_temp_c),
+                link_down_s: tick.link_down_s,
+            };
+            let out = nav.step_with_health(fix, &health);
+            if states.last() != Some(&out.state) {
+                states.push(out.state);
+                if out.state == MissionState::Rth {
+                    link.cmd_rtl().unwrap();
+                    rth_at_s = Some(tick.t_s);
+                }
+            }
+            failsafe = out.failsafe;
+            if sim.vehicle().mode == FlightMode::Loiter {
+                break;
+            }
+        }
+        Flight { sim, states, failsafe, rth_at_s }
+    }
+
+    #[test]
+    fn mission_completes() {
+        let f = fly(SimConfig::default());
+        assert_eq!(
+            vehicle_events(&f.sim),
+            ["waypoint 0 reached", "waypoint 1 reached", "waypoint 2 reached", "route complete, loitering"]
+        );
+        assert_eq!(f.states, [MissionState::TransitToZone, MissionState::OperateInZone]);
+        assert_eq!(f.failsafe, None);
+        let v = f.sim.vehicle();
+        assert_eq!((v.lat, v.lon), (48.001050, 2.002300));
+
+        // Simulated time only: a second run is identical
+        let again = fly(SimConfig::default());
+        assert_eq!(again.sim.elapsed_s(), f.sim.elapsed_s());
+        assert_eq!(vehicle_events(&again.sim), vehicle_events(&f.sim));
+    }
+
+    #[test]
+    fn battery_sag_triggers_rth() {
+        let cfg = SimConfig {
+            battery_start_pct: Some(95.0),
+            faults: vec![FaultCfg { kind: FaultKind::BatterySag, at_s: 20.0, duration_s: None, value: Some(80.0) }],
+            ..Default::default()
+        };
+        let f = fly(cfg);
+        assert_eq!(f.failsafe, Some(Failsafe::Battery));
+        assert_eq!(f.states.last(), Some(&MissionState::Rth));
+        // SYS_STATUS is 1 Hz, so the sag is seen within a second
+        let rth = f.rth_at_s.unwrap();
+        assert!((20.0..=21.0).contains(&rth), "RTH at {}s", rth);
+
+        // The autopilot accepted RTL, flew home and landed there
+        let v = f.sim.vehicle();
+        assert_eq!(v.mode, FlightMode::Landed);
+        assert_eq!((v.lat, v.lon, v.alt_m), (48.0, 2.0, 0.0));
+        let events = vehicle_events(&f.sim);
+        assert_eq!(events[events.len() - 2..], ["home reached, descending", "landed"]);
+        assert!(f.sim.timeline().iter().any(|e| e.kind == TimelineKind::Fc && e.text.contains("MAV_RESULT_ACCEPTED")));
+    }
+
+    #[test]
+    fn fault_windows() {
+        let faults = vec![
+            FaultCfg { kind: FaultKind::HdopSpike, at_s: 10.0, duration_s: Some(3.0), value: None },
+            FaultCfg { kind: FaultKind::HdopSpike, at_s: 11.0, duration_s: Some(1.0), value: Some(12.0) },
+            FaultCfg { kind: FaultKind::LinkLoss, at_s: 12.0, duration_s: None, value: None },
+        ];
+        assert_eq!(faults::effects_at(&faults, 9.9).hdop_override, None);
+        assert_eq!(faults::effects_at(&faults, 10.0).hdop_override, Some(8.0));
+        assert_eq!(faults::effects_at(&faults, 11.5).hdop_override, Some(12.0));
+        assert!(!faults::effects_at(&faults, 11.9).link_down);
+        let late = faults::effects_at(&faults, 1e6);
+        assert!(late.link_down && late.hdop_override.is_none());
+    }
+}
@@ -0,0 +1,52 @@
+use time::OffsetDateTime;
+
+// Minimal NMEA 0183 sentence generation, matching what scout-nav's parser reads:
+// - GGA: satellites + hdop
+// - RMC: lat/lon
+
+pub fn gga(ts: OffsetDateTime, lat: f64, lon: f64, alt_m: f32, sats: u8, hdop: f32) -> String {
+    let (lat_s, ns) = deg_min(lat, 2, 'N', 'S');
+    let (lon_s, ew) = deg_min(lon, 3, 'E', 'W');
+    let quality = if sats == 0 { 0 } else { 1 };
+    let body = format!(
+        "GPGGA,{},{},{},{},{},{},{:02},{:.1},{:.1},M,0.0,M,,",
+        hhmmss(ts), lat_s, ns, lon_s, ew, quality, sats, hdop, alt_m
+    );
+    sentence(&body)
+}
+
+/// `valid = false` emits status `V`: the receiver keeps reporting the last
+/// known position but flags it as unusable.
+pub fn rmc(ts: OffsetDateTime, lat: f64, lon: f64, speed_mps: f64, course_deg: f64, valid: bool) -> String {
+    let (lat_s, ns) = deg_min(lat, 2, 'N', 'S');
+    let (lon_s, ew) = deg_min(lon, 3, 'E', 'W');
+    let knots = speed_mps * 1.943_844;
+    let body = format!(
+        "GPRMC,{},{},{},{},{},{},{:.1},{:.1},{:02}{:02}{:02},,,{}",
+        hhmmss(ts),
+        if valid { 'A' } else { 'V' },
+        lat_s, ns, lon_s, ew,
+        knots, course_deg,
+        ts.day(), u8::from(ts.month()), ts.year() % 100,
+        if valid { 'A' } else { 'N' },
+    );
+    sentence(&body)
+}
+
+fn sentence(body: &str) -> String {
+    let cs = body.bytes().fold(0u8, |acc, b| acc ^ b);
+    format!("${}*{:02X}", body, cs)
+}
+
+fn hhmmss(ts: OffsetDateTime) -> String {
+    format!("{:02}{:02}{:02}.{:02}", ts.hour(), ts.minute(), ts.second(), ts.millisecond() / 10)
+}
+
+// lat: ddmm.mmmm, lon: dddmm.mmmm
+fn deg_min(v: f64, deg_width: usize, pos: char, neg: char) -> (String, char) {
+    let hemi = if v < 0.0 { neg } else { pos };
+    let a = v.abs();
+    let deg = a.trunc();
+    let min = (a - deg) * 60.0;
+    (format!("{:0width$}{:07.4}", deg as u32, min, width = deg_width), hemi)
+}
@@ -0,0 +1,119 @@
+use scout_nav::geo;
+use scout_nav::nav::{Home, Point};
+
+const ARRIVE_M: f64 = 1.0;
+const DESCENT_MPS: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlightMode {
+    /// Flying the route waypoints in order.
+    Auto,
+    /// Holding position (route finished or HOLD commanded).
+    Loiter,
+    /// Returning to home, then descending.
+    Rtl,
+    Landed,
+}
+
+/// Point-mass kinematic model: constant ground speed towards the current
+/// target, instantaneous turns, constant descent rate when landing.
+#[derive(Debug, Clone)]
+pub struct Vehicle {
+    pub lat: f64,
+    pub lon: f64,
+    pub alt_m: f32,
+    pub mode: FlightMode,
+    pub speed_mps: f64,
+    /// Course over ground of the last step (degrees, 0 = north)
+    pub course_deg: f64,
+
+    home: Home,
+    waypoints: Vec<Point>,
+    wp_idx: usize,
+}
+
+/// Something worth putting on the timeline, produced by `Vehicle::step`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VehicleEvent {
+    WaypointReached(usize),
+    RouteComplete,
+    HomeReached,
+    Landed,
+}
+
+impl Vehicle {
+    pub fn new(home: Home, waypoints: Vec<Point>, cruise_alt_m: f32, speed_mps: f64) -> Self {
+        Self {
+            lat: home.lat,
+            lon: home.lon,
+            alt_m: cruise_alt_m,
+            mode: FlightMode::Auto,
+            speed_mps,
+            course_deg: 0.0,
+            home,
+            waypoints,
+            wp_idx: 0,
+        }
+    }
+
+    pub fn ground_speed_mps(&self) -> f64 {
+        match self.mode {
+            FlightMode::Auto | FlightMode::Rtl => self.speed_mps,
+            FlightMode::Loiter | FlightMode::Landed => 0.0,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: FlightMode) {
+        if self.mode != FlightMode::Landed {
+            self.mode = mode;
+        }
+    }
+
+    pub fn step(&mut self, dt_s: f64) -> Option<VehicleEvent> {
+        match self.mode {
+            FlightMode::Auto => {
+                let Some(wp) = self.waypoints.get(self.wp_idx).cloned() else {
+                    self.mode = FlightMode::Loiter;
+                    return Some(VehicleEvent::RouteComplete);
+                };
+                if self.move_towards(wp.lat, wp.lon, dt_s) {
+                    self.wp_idx += 1;
+                    return Some(VehicleEvent::WaypointReached(self.wp_idx - 1));
+                }
+                None
+            }
+            FlightMode::Rtl => {
+                let (lat, lon) = (self.home.lat, self.home.lon);
+                let at_home = (self.lat, self.lon) == (lat, lon);
+                if !at_home {
+                    return self.move_towards(lat, lon, dt_s).then_some(VehicleEvent::HomeReached);
+                }
+                self.alt_m = (self.alt_m - DESCENT_MPS * dt_s as f32).max(0.0);
+                if self.alt_m <= 0.0 {
+                    self.mode = FlightMode::Landed;
+                    return Some(VehicleEvent::Landed);
+                }
+                None
+            }
+            FlightMode::Loiter | FlightMode::Landed => None,
+        }
+    }
+
+    // Returns true once the target is reached (position snaps onto it).
+    fn move_towards(&mut self, lat: f64, lon: f64, dt_s: f64) -> bool {
+        let here = Point { lat: self.lat, lon: self.lon };
+        let target = Point { lat, lon };
+        let dist = geo::distance_m(&here, &target);
+        let step = self.speed_mps * dt_s;
+        if dist <= step.max(ARRIVE_M) {
+            self.lat = lat;
+            self.lon = lon;
+            return true;
+        }
+        self.course_deg = geo::initial_bearing_deg(&here, &target);
+        let next = geo::destination(&here, self.course_deg, step);
+        self.lat = next.lat;
+        self.lon = next.lon;
+        false
+    }
+}
@@ -0,0 +1,9 @@
+// Server certificates reach `spki_sha256` during the TLS handshake before
+// anything has validated them; it must return an error, never panic.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = scout_uplink::cert_pin::spki_sha256(data);
+});
@@ -1,135 +1,115 @@
-use anyhow::Result;
+// SPKI pinning: the server's end-entity certificate must pass standard
+// validation and its SubjectPublicKeyInfo SHA-256 must match one of the
+// configured pins that has not expired. Listing the next server key as a
+// backup pin lets the ground station rotate keys without stranding aircraft.
+
+use anyhow::{Context, Result};
 use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
 use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
 use rustls::{DigitallySignedStruct, Error, SignatureScheme};
+use serde::Deserialize;
 use std::sync::Arc;
+use time::{macros::format_description, Date};
 
-/// Custom certificate verifier that pins to a specific SPKI SHA256 hash
-#[derive(Debug)]
-pub struct PinnedCertVerifier {
-    /// Expected SPKI SHA256 hash (32 bytes)
-    pinned_spki_sha256: Vec<u8>,
-    /// Fallback verifier for standard validation
-    fallback: Arc<dyn ServerCertVerifier>,
+/// One pin as configured: a hex SHA-256, or a table with an expiry date.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum PinConfig {
+    Sha256(String),
+    Entry {
+        sha256: String,
+        /// Last day (UTC, YYYY-MM-DD) the pin is accepted
+        expires: Option<String>,
+    },
 }
 
-impl PinnedCertVerifier {
-    pub fn new(pinned_hex: &str, fallback: Arc<dyn ServerCertVerifier>) -> Result<Self> {
-        if pinned_hex.is_empty() {
-            return Err(anyhow::anyhow!("Empty SPKI pin - use fallback verifier"));
-        }
+#[derive(Debug, Clone)]
+struct Pin {
+    sha256: [u8; 32],
+    /// Unix seconds from which the pin is no longer accepted
+    expires_at: Option<u64>,
+}
 
-        let decoded = hex::decode(pinned_hex)
-            .map_err(|e| anyhow::anyhow!("Invalid SPKI hex: {}", e))?;
-
-        anyhow::ensure!(decoded.len() == 32, "SPKI hash must be 32 bytes (SHA256)");
-
-        Ok(Self {
-            pinned_spki_sha256: decoded,
-            fallback,
-        })
-    }
-
-    /// Extract SPKI (SubjectPublicKeyInfo) from X.509 certificate and compute SHA256
-    ///
-    /// X.509 structure (simplified):
-    /// SEQUENCE {
-    ///   tbsCertificate SEQUENCE {
-    ///     version [0] EXPLICIT INTEGER OPTIONAL,
-    ///     serialNumber INTEGER,
-    ///     signature AlgorithmIdentifier,
-    ///     issuer Name,
-    ///     validity Validity,
-    ///     subject Name,
-    ///     subjectPublicKeyInfo SEQUENCE { ... },  <-- This is what we want
-    ///     ...
-    ///   },
-    ///   signatureAlgorithm AlgorithmIdentifier,
-    ///   signatureValue BIT STRING
-    /// }
-    fn extract_spki_hash(cert: &CertificateDer<'_>) -> Result<Vec<u8>> {
-        let der = cert.as_ref();
-
-        // Basic DER parsing to find SPKI
-        // Skip outer SEQUENCE tag and length
-        let (_, rest) = Self::parse_der_header(der)?;
-
-        // Skip tbsCertificate SEQUENCE header
-        let (tbs_len, tbs_content) = Self::parse_der_header(rest)?;
-        let tbs = &tbs_content[..tbs_len];
-
-        // Parse tbsCertificate fields to find SPKI
-        let mut pos = 0;
-
-        // Skip version [0] if present (context tag 0xA0)
-        if tbs.get(pos) == Some(&0xA0) {
-            let (field_len, _) = Self::parse_der_header(&tbs[pos..])?;
-            pos += Self::der_header_len(&tbs[pos..])? + field_len;
-        }
+/// Accepted SPKI hashes of one endpoint.
+#[derive(Debug, Clone)]
+pub struct PinSet {
+    pins: Vec<Pin>,
+}
 
-        // Skip serialNumber INTEGER
-        let (field_len, _) = Self::parse_der_header(&tbs[pos..])?;
-        pos += Self::der_header_len(&tbs[pos..])? + field_len;
-
-        // Skip signature AlgorithmIdentifier SEQUENCE
-        let (field_len, _) = Self::parse_der_header(&tbs[pos..])?;
-        pos += Self::der_header_len(&tbs[pos..])? + field_len;
-
-        // Skip issuer Name SEQUENCE
-        let (field_len, _) = Self::parse_der_header(&tbs[pos..])?;
-        pos += Self::der_header_len(&tbs[pos..])? + field_len;
-
-        // Skip validity SEQUENCE
-        let (field_len, _) = Self::parse_der_header(&tbs[pos..])?;
-        pos += Self::der_header_len(&tbs[pos..])? + field_len;
-
-        // Skip subject Name SEQUENCE
-        let (field_len, _) = Self::parse_der_header(&tbs[pos..])?;
-        pos += Self::der_header_len(&tbs[pos..])? + field_len;
-
-        // Now we're at subjectPublicKeyInfo SEQUENCE
-        let header_len = Self::der_header_len(&tbs[pos..])?;
-        let (spki_len, _) = Self::parse_der_header(&tbs[pos..])?;
-        let spki = &tbs[pos..pos + header_len + spki_len];
-
-        // Hash SPKI with SHA256 (standard for certificate pinning)
-        use sha2::{Sha256, Digest};
-        let mut hasher = Sha256::new();
-        hasher.update(spki);
-        Ok(hasher.finalize().to_vec())
-    }
-
-    fn parse_der_header(data: &[u8]) -> Result<(usize, &[u8])> {
-        anyhow::ensure!(data.len() >= 2, "DER too short");
-        let _tag = data[0];
-        let len_byte = data[1];
-
-        if len_byte < 0x80 {
-            // Short form: length is directly in second byte
-            Ok((len_byte as usize, &data[2..]))
-        } else {
-            // Long form: len_byte & 0x7f gives number of length bytes
-            let num_len_bytes = (len_byte & 0x7f) as usize;
-            anyhow::ensure!(data.len() >= 2 + num_len_bytes, "DER length truncated");
-            let mut len = 0usize;
-            for i in 0..num_len_bytes {
-                len = (len << 8) | (data[2 + i] as usize);
-            }
-            Ok((len, &data[2 + num_len_bytes..]))
+impl PinSet {
+    /// Any malformed pin is an error; so is a set whose pins have all expired.
+    pub fn parse(cfgs: &[PinConfig]) -> Result<Self> {
+        anyhow::ensure!(!cfgs.is_empty(), "empty pin set");
+        let mut pins = Vec::with_capacity(cfgs.len());
+        for c in cfgs {
+            let (hex_str, expires) = match c {
+                PinConfig::Sha256(h) => (h, None),
+                PinConfig::Entry { sha256, expires } => (sha256, expires.as_ref()),
+            };
+            let decoded = hex::decode(hex_str.trim()).with_context(|| format!("SPKI pin '{}' is not hex", hex_str))?;
+            let sha256: [u8; 32] = decoded.try_into().map_err(|_| anyhow::anyhow!("SPKI pin '{}' is not a SHA-256 (64 hex chars)", hex_str))?;
+            let expires_at = match expires {
+                Some(d) => {
+                    let date = Date::parse(d, format_description!("[year]-[month]-[day]"))
+                        .with_context(|| format!("pin expiry '{}' is not YYYY-MM-DD", d))?;
+                    let end = date.next_day().context("pin expiry out of range")?.midnight().assume_utc();
+                    Some(end.unix_timestamp().max(0) as u64)
+                }
+                None => None,
+            };
+            pins.push(Pin { sha256, expires_at });
         }
+        let now = UnixTime::now().as_secs();
+        anyhow::ensure!(pins.iter().any(|p| p.valid_at(now)), "all SPKI pins have expired");
+        Ok(Self { pins })
     }
 
-    fn der_header_len(data: &[u8]) -> Result<usize> {
-        anyhow::ensure!(data.len() >= 2, "DER too short");
-        let len_byte = data[1];
-        if len_byte < 0x80 {
-            Ok(2)
-        } else {
-            Ok(2 + (len_byte & 0x7f) as usize)
-        }
+    /// Pins still accepted at `now` (Unix seconds), as hex.
+    pub fn active(&self, now: u64) -> Vec<String> {
+        self.pins.iter().filter(|p| p.valid_at(now)).map(|p| hex::encode(p.sha256)).collect()
+    }
+
+    /// When the last pin accepted at `now` expires; `None` if one never does.
+    pub fn expires_at(&self, now: u64) -> Option<u64> {
+        let active: Vec<_> = self.pins.iter().filter(|p| p.valid_at(now)).collect();
+        active.iter().map(|p| p.expires_at).collect::<Option<Vec<_>>>()?.into_iter().max()
+    }
+
+    fn matches(&self, spki_sha256: &[u8], now: u64) -> bool {
+        self.pins.iter().any(|p| p.valid_at(now) && p.sha256 == spki_sha256)
+    }
+}
+
+impl Pin {
+    fn valid_at(&self, now: u64) -> bool {
+        self.expires_at.map(|t| now < t).unwrap_or(true)
+    }
+}
+
+/// Certificate verifier that requires standard validation plus an SPKI pin match
+#[derive(Debug)]
+pub struct PinnedCertVerifier {
+    pins: PinSet,
+    /// Standard validation, run first
+    fallback: Arc<dyn ServerCertVerifier>,
+}
+
+impl PinnedCertVerifier {
+    pub fn new(pins: PinSet, fallback: Arc<dyn ServerCertVerifier>) -> Self {
+        Self { pins, fallback }
     }
 }
 
+/// SHA-256 of the DER SubjectPublicKeyInfo of an X.509 certificate. Malformed
+/// input is an error, never a panic: this runs inside the TLS handshake on
+/// whatever the server sent.
+pub fn spki_sha256(cert_der: &[u8]) -> Result<[u8; 32]> {
+    use sha2::{Digest, Sha256};
+    let (rest, cert) = x509_parser::parse_x509_certificate(cert_der).map_err(|e| anyhow::anyhow!("invalid certificate: {}", e))?;
+    anyhow::ensure!(rest.is_empty(), "{} trailing bytes after certificate", rest.len());
+    Ok(Sha256::digest(cert.tbs_certificate.subject_pki.raw).into())
+}
+
 impl ServerCertVerifier for PinnedCertVerifier {
     fn verify_server_cert(
         &self,
@@ -149,14 +129,14 @@ impl ServerCertVerifier for PinnedCertVerifier {
         )?;
 
         // Then, check SPKI pin
-        let spki_hash = Self::extract_spki_hash(end_entity)
-            .map_err(|_| Error::General("Failed to extract SPKI".to_string()))?;
+        let spki_hash = spki_sha256(end_entity)
+            .map_err(|e| Error::General(format!("Failed to extract SPKI: {:#}", e)))?;
 
-        if spki_hash != self.pinned_spki_sha256 {
+        if !self.pins.matches(&spki_hash, now.as_secs()) {
             return Err(Error::General(format!(
-                "Certificate SPKI mismatch. Expected: {}, Got: {}",
-                hex::encode(&self.pinned_spki_sha256),
-                hex::encode(&spki_hash)
+                "Certificate SPKI mismatch. Expected one of: {}, Got: {}",
+                self.pins.active(now.as_secs()).join(", "),
+                hex::encode(spki_hash)
             )));
         }
 
@@ -185,3 +165,196 @@ impl ServerCertVerifier for PinnedCertVerifier {
         self.fallback.supported_verify_schemes()
     }
 }
+
+/// SPKI SHA-256 (hex) of every certificate in a PEM file, in file order.
+pub fn pins_from_pem(path: &str) -> Result<Vec<String>> {
+    use rustls::pki_types::pem::PemObject;
+    let certs = CertificateDer::pem_file_iter(path)
+        .with_context(|| format!("read {}", path))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("parse {}", path))?;
+    anyhow::ensure!(!certs.is_empty(), "no certificates in {}", path);
+    certs.iter().map(|c| spki_sha256(c).map(hex::encode)).collect()
+}
+
+/// Connect to `host`:`port` and return the SPKI SHA-256 (hex) of each
+/// certificate the server presents, leaf first. Nothing is validated: compare
+/// the result out of band before trusting it.
+pub async fn pins_from_server(host: &str, port: u16, server_name: &str) -> Result<Vec<String>> {
+    let name = ServerName::try_from(server_name.to_string()).with_context(|| format!("invalid server name '{}'", server_name))?;
+    let capture = Arc::new(CaptureVerifier {
+        chain: std::sync::Mutex::new(Vec::new()),
+        algs: rustls::crypto::aws_lc_rs::default_provider().signature_verification_algorithms,
+    });
+    let cfg = rustls::ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(capture.clone())
+        .with_no_client_auth();
+    let tcp = tokio::net::TcpStream::connect((host, port)).await.with_context(|| format!("connect {}:{}", host, port))?;
+    // The handshake may still fail afterwards (e.g. the server wants a client
+    // certificate); the chain has been seen by then
+    let handshake = tokio_rustls::TlsConnector::from(Arc::new(cfg)).connect(name, tcp).await;
+    let chain = capture.chain.lock().unwrap().clone();
+    if chain.is_empty() {
+        handshake.context("TLS handshake")?;
+        anyhow::bail!("server presented no certificate");
+    }
+    chain.iter().map(|c| spki_sha256(c).map(hex::encode)).collect()
+}
+
+/// Accepts any server certificate and keeps the chain, for `pins_from_server`.
+#[derive(Debug)]
+struct CaptureVerifier {
+    chain: std::sync::Mutex<Vec<CertificateDer<'static>>>,
+    algs: rustls::crypto::WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for CaptureVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let mut chain = self.chain.lock().unwrap();
+        chain.push(end_entity.clone().into_owned());
+        chain.extend(intermediates.iter().map(|c| c.clone().into_owned()));
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algs)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algs)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algs.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// SPKI SHA-256 of each seed certificate, computed with
+    /// `openssl x509 -pubkey -noout | openssl pkey -pubin -outform der | sha256sum`.
+    const CORPUS: &[(&str, &str)] = &[
+        ("ed25519-ext.der", "194c269f4392d8e6f3279700721cbadedc10986b6c734f59e1c3714e22451659"),
+        ("ed25519-v1.der", "3f93738295a0f7bcb8301db2970f34b0fd178366f06795e1f49ec30f5e8dfa55"),
+        ("p256-ext.der", "40e1a7331705c88361252bc420add202927da103ae91937f83f1f57df8a1bafc"),
+        ("p384-v1.der", "43e90c2ee353cb8214fea24b70cafd188de25f4c4cad2ca02ee3dcad11b99594"),
+        ("rsa2048-ext.der", "caeed3c01fab30176077b8391e07490a813498e202dc0c8f74ce8d79f293c6e5"),
+        ("rsa2048-v1.der", "726635045a079ea366bbd74fd2b9187f714a3370ee1dc9d787a1ec04c3888db0"),
+        ("rsa4096-ext.der", "90e6ad91612fabcb54e0eea7b0c98066e6f5be01f1ddad391bdb54b87312c8b5"),
+    ];
+
+    fn corpus() -> Vec<(String, Vec<u8>)> {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/spki");
+        let mut certs: Vec<_> = std::fs::read_dir(&dir).unwrap()
+            .map(|e| e.unwrap().path())
+            .map(|p| (p.file_name().unwrap().to_string_lossy().into_owned(), std::fs::read(&p).unwrap()))
+            .collect();
+        certs.sort();
+        certs
+    }
+
+    #[test]
+    fn corpus_hashes() {
+        let certs = corpus();
+        let names: Vec<_> = certs.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, CORPUS.iter().map(|(n, _)| *n).collect::<Vec<_>>(), "corpus and expected hashes differ");
+        for ((name, der), (_, want)) in certs.iter().zip(CORPUS) {
+            assert_eq!(hex::encode(spki_sha256(der).unwrap()), *want, "{}", name);
+        }
+    }
+
+    #[test]
+    fn truncated_certificates_are_errors() {
+        for (name, der) in corpus() {
+            for len in 0..der.len() {
+                assert!(spki_sha256(&der[..len]).is_err(), "{} truncated to {}", name, len);
+            }
+            assert!(spki_sha256(&[der.as_slice(), &[0]].concat()).is_err(), "{} with a trailing byte", name);
+        }
+    }
+
+    #[test]
+    fn garbled_certificates_do_not_panic() {
+        for (name, der) in corpus() {
+            for i in 0..der.len() {
+                for mask in [0x01, 0x80, 0xff] {
+                    let mut bad = der.clone();
+                    bad[i] ^= mask;
+                    // Garbling the signature or a name still parses; the result
+                    // matters only in that it comes back at all
+                    let _ = spki_sha256(&bad);
+                }
+                let mut bad = der.clone();
+                bad[i] = 0x84; // a long length form where a tag or short length was
+                let _ = spki_sha256(&bad);
+            }
+            // The outer SEQUENCE claiming more than there is
+            let mut bad = der.clone();
+            bad[1] = 0x83;
+            assert!(spki_sha256(&bad).is_err(), "{} with an overlong outer length", name);
+        }
+        assert!(spki_sha256(b"-----BEGIN CERTIFICATE-----").is_err());
+    }
+
+    fn entry(sha256: &str, expires: Option<&str>) -> PinConfig {
+        PinConfig::Entry { sha256: sha256.into(), expires: expires.map(String::from) }
+    }
+
+    #[test]
+    fn backup_pins_expire_at_the_end_of_their_day() {
+        let (current, backup, old) = (CORPUS[0].1, CORPUS[1].1, CORPUS[2].1);
+        let pins = PinSet::parse(&[
+            PinConfig::Sha256(format!(" {} ", current)),
+            entry(backup, Some("2099-12-31")),
+            entry(old, Some("2000-01-01")),
+        ]).unwrap();
+        let now = UnixTime::now().as_secs();
+        assert_eq!(pins.active(now), [current, backup]);
+        // A pin without expiry keeps the set alive forever
+        assert_eq!(pins.expires_at(now), None);
+
+        let pins = PinSet::parse(&[entry(backup, Some("2099-12-31")), entry(old, None)]).unwrap();
+        let day_end = 4102444800; // 2100-01-01T00:00:00Z
+        assert_eq!(pins.expires_at(0), None);
+        let pins = PinSet::parse(&[entry(backup, Some("2099-12-31"))]).unwrap();
+        assert_eq!(pins.expires_at(now), Some(day_end));
+        assert!(pins.matches(&hex::decode(backup).unwrap(), day_end - 1));
+        assert!(!pins.matches(&hex::decode(backup).unwrap(), day_end));
+        assert!(!pins.matches(&hex::decode(current).unwrap(), day_end - 1));
+        assert!(pins.active(day_end).is_empty());
+    }
+
+    #[test]
+    fn bad_pin_sets_are_errors() {
+        let pin = CORPUS[0].1;
+        let err = |cfgs: &[PinConfig]| format!("{:#}", PinSet::parse(cfgs).unwrap_err());
+        assert!(err(&[]).contains("empty"));
+        assert!(err(&[entry(pin, Some("2000-01-01")), entry(CORPUS[1].1, Some("2001-06-30"))]).contains("expired"));
+        // One malformed pin fails the set even next to a good one
+        assert!(err(&[PinConfig::Sha256(pin.into()), PinConfig::Sha256("zz".repeat(32))]).contains("not hex"));
+        assert!(err(&[PinConfig::Sha256(pin.into()), PinConfig::Sha256(pin[..62].into())]).contains("64 hex chars"));
+        assert!(err(&[PinConfig::Sha256(pin.into()), entry(pin, Some("31/12/2099"))]).contains("YYYY-MM-DD"));
+        assert!(err(&[PinConfig::Sha256(pin.into()), entry(pin, Some("2099-02-30"))]).contains("YYYY-MM-DD"));
+    }
+}
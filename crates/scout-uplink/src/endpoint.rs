@@ -0,0 +1,193 @@
+// Ground-station endpoints, in order of preference. Each has its own TLS
+// config (and so its own SPKI pin) and its own reconnect backoff; a failed
+// session moves on to the next endpoint that is due, so the primary is tried
+// again as soon as its backoff expires.
+
+use anyhow::{Context, Result};
+use rustls::client::{Resumption, WebPkiServerVerifier};
+use rustls::{ClientConfig, RootCertStore};
+use rustls_pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_rustls::TlsConnector;
+use tracing::{info, warn};
+use url::{Host, Url};
+
+use crate::cert_pin::{self, PinConfig, PinSet};
+
+/// Warn at startup when the last accepted pin expires sooner than this.
+const PIN_EXPIRY_WARNING: Duration = Duration::from_secs(30 * 24 * 3600);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EndpointConfig {
+    /// tls://host:port; IPv6 literals in brackets, e.g. tls://[2001:db8::10]:8443
+    pub url: String,
+    /// Name sent as SNI and checked against the certificate, when the URL
+    /// holds an address or an alias the certificate does not cover
+    pub server_name: Option<String>,
+    /// Accepted SHA-256 hashes of the server certificate's SPKI (hex): the
+    /// current key plus backups, optionally as `{ sha256, expires = "YYYY-MM-DD" }`
+    #[serde(default)]
+    pub pins: Vec<PinConfig>,
+}
+
+/// Client certificate chain and key, presented to every endpoint (mutual TLS).
+pub struct ClientAuth {
+    certs: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+}
+
+impl ClientAuth {
+    pub fn load(cert_path: &str, key_path: &str) -> Result<Self> {
+        let certs = CertificateDer::pem_file_iter(cert_path)
+            .with_context(|| format!("read {}", cert_path))?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("parse {}", cert_path))?;
+        anyhow::ensure!(!certs.is_empty(), "no certificates in {}", cert_path);
+        let key = PrivateKeyDer::from_pem_file(key_path).with_context(|| format!("read {}", key_path))?;
+        Ok(Self { certs, key })
+    }
+}
+
+/// Connect host and port of a `tls://` URL. The host has no brackets.
+pub fn parse_url(url: &str) -> Result<(String, u16)> {
+    let u = Url::parse(url).with_context(|| format!("invalid endpoint URL '{}'", url))?;
+    anyhow::ensure!(u.scheme() == "tls", "endpoint '{}' must start with tls://", url);
+    anyhow::ensure!(u.username().is_empty() && u.password().is_none(), "endpoint '{}' must not carry credentials", url);
+    anyhow::ensure!(matches!(u.path(), "" | "/") && u.query().is_none() && u.fragment().is_none(), "endpoint '{}' must be tls://host:port only", url);
+    let host = match u.host().with_context(|| format!("endpoint '{}' has no host", url))? {
+        Host::Domain(d) => d.to_string(),
+        Host::Ipv4(a) => a.to_string(),
+        Host::Ipv6(a) => a.to_string(),
+    };
+    let port = u.port().with_context(|| format!("endpoint '{}' has no port", url))?;
+    Ok((host, port))
+}
+
+pub(crate) struct Endpoint {
+    pub url: String,
+    pub host: String,
+    pub port: u16,
+    pub server_name: ServerName<'static>,
+    pub tls: TlsConnector,
+    pub failures: u32,
+    pub retry_at: Option<Instant>,
+}
+
+impl Endpoint {
+    pub fn new(cfg: &EndpointConfig, client: Option<&ClientAuth>) -> Result<Self> {
+        let (host, port) = parse_url(&cfg.url)?;
+        let name = cfg.server_name.clone().unwrap_or_else(|| host.clone());
+        let server_name = ServerName::try_from(name.clone()).with_context(|| format!("invalid server name '{}' for {}", name, cfg.url))?;
+        let tls = connector(&cfg.url, &cfg.pins, client)?;
+        Ok(Self { url: cfg.url.clone(), host, port, server_name, tls, failures: 0, retry_at: None })
+    }
+
+    pub fn due(&self, now: Instant) -> bool {
+        self.retry_at.map(|t| now >= t).unwrap_or(true)
+    }
+
+    /// Record a failed session; returns the backoff until the next attempt.
+    pub fn failed(&mut self, min: Duration, max: Duration) -> Duration {
+        self.failures += 1;
+        let backoff = (min * (1 << self.failures.min(6))).min(max);
+        self.retry_at = Some(Instant::now() + backoff);
+        backoff
+    }
+
+    pub fn succeeded(&mut self) {
+        self.failures = 0;
+        self.retry_at = None;
+    }
+}
+
+/// The most preferred endpoint whose backoff has expired at `now`.
+pub(crate) fn next_due(endpoints: &[Endpoint], now: Instant) -> Option<usize> {
+    endpoints.iter().position(|e| e.due(now))
+}
+
+fn connector(url: &str, pins: &[PinConfig], client: Option<&ClientAuth>) -> Result<TlsConnector> {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+    let builder = if !pins.is_empty() {
+        let total = pins.len();
+        let pins = PinSet::parse(pins).with_context(|| format!("{}: invalid SPKI pins", url))?;
+        let fallback_verifier = WebPkiServerVerifier::builder(roots.into())
+            .build()
+            .map_err(|e| anyhow::anyhow!("Failed to build fallback verifier: {:?}", e))?;
+
+        let now = UnixTime::now().as_secs();
+        info!("uplink: {}: certificate pinning enabled ({} of {} pins active)", url, pins.active(now).len(), total);
+        if let Some(t) = pins.expires_at(now).filter(|t| t - now < PIN_EXPIRY_WARNING.as_secs()) {
+            warn!("uplink: {}: every SPKI pin expires within {} days; add the next server key as a backup pin", url, (t - now) / 86400 + 1);
+        }
+        ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(cert_pin::PinnedCertVerifier::new(pins, fallback_verifier)))
+    } else {
+        warn!("uplink: {}: certificate pinning NOT enabled (no SPKI pin) - vulnerable to MITM on slow links!", url);
+        ClientConfig::builder().with_root_certificates(roots)
+    };
+    let mut cfg = match client {
+        Some(c) => builder.with_client_auth_cert(c.certs.clone(), c.key.clone_key()).context("client certificate")?,
+        None => builder.with_no_client_auth(),
+    };
+
+    // Reusing one config keeps its session cache, so reconnects resume
+    cfg.resumption = Resumption::in_memory_sessions(8);
+    Ok(TlsConnector::from(Arc::new(cfg)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(url: &str) -> Endpoint {
+        Endpoint::new(&EndpointConfig { url: url.into(), server_name: None, pins: vec![] }, None).unwrap()
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(parse_url("tls://ground.example:8443").unwrap(), ("ground.example".to_string(), 8443));
+        assert_eq!(parse_url("tls://ground.example:8443/").unwrap(), ("ground.example".to_string(), 8443));
+        assert_eq!(parse_url("tls://10.0.0.2:443").unwrap(), ("10.0.0.2".to_string(), 443));
+        assert_eq!(parse_url("tls://[2001:db8::10]:8443").unwrap(), ("2001:db8::10".to_string(), 8443));
+        // tls has no default port, so it is never implied or dropped
+        assert!(format!("{:#}", parse_url("tls://ground.example").unwrap_err()).contains("no port"));
+        assert!(parse_url("tls://2001:db8::10:8443").is_err());
+        assert!(parse_url("tls://ground.example:8443/ingest").is_err());
+        assert!(parse_url("tls://ground.example:8443?x=1").is_err());
+        assert!(parse_url("tls://ground.example:8443#f").is_err());
+        assert!(parse_url("tls://user:pw@ground.example:8443").is_err());
+        assert!(parse_url("tls://user@ground.example:8443").is_err());
+        assert!(format!("{:#}", parse_url("https://ground.example:8443").unwrap_err()).contains("tls://"));
+        assert!(parse_url("ground.example:8443").is_err());
+    }
+
+    #[test]
+    fn failover_follows_preference_and_backoff() {
+        let mut eps = vec![endpoint("tls://primary:8443"), endpoint("tls://[2001:db8::10]:8443")];
+        assert_eq!(eps[1].server_name, ServerName::try_from("2001:db8::10").unwrap());
+        let now = Instant::now();
+        assert_eq!(next_due(&eps, now), Some(0));
+
+        let min = Duration::from_secs(1);
+        let max = Duration::from_secs(10);
+        assert_eq!(eps[0].failed(min, max), Duration::from_secs(2));
+        assert_eq!(next_due(&eps, Instant::now()), Some(1));
+        assert_eq!(eps[1].failed(min, max), Duration::from_secs(2));
+        assert_eq!(next_due(&eps, Instant::now()), None);
+        // The primary is preferred again once its backoff expires
+        assert_eq!(next_due(&eps, Instant::now() + Duration::from_secs(3)), Some(0));
+
+        assert_eq!(eps[0].failed(min, max), Duration::from_secs(4));
+        assert_eq!(eps[0].failed(min, max), Duration::from_secs(8));
+        assert_eq!(eps[0].failed(min, max), max);
+        eps[0].succeeded();
+        assert_eq!(eps[0].failures, 0);
+        assert!(eps[0].due(Instant::now()));
+        assert_eq!(eps[0].failed(min, max), Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,463 @@
+// Link quality estimation from what the uplink actually observes: RTT of
+// application acks (EWMA), frame loss over the last LOSS_WINDOW frames sent,
+// acknowledged throughput, and optionally the modem's signal report from
+// ModemManager. `[uplink.health]` thresholds classify the link:
+//
+// - degraded: RTT above `max_rtt_ms`, loss above `max_loss_pct` or RSRP below
+//   `min_rsrp_dbm`; telemetry is sampled less often, nothing else changes
+// - lost: the session cannot be (re)established, or the oldest frame in flight
+//   has waited STALL_FACTOR x `max_rtt_ms` for its ack; link-down time counts
+//   from the last ack (`LinkHealth::down_for`)
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Frames over which loss is measured.
+const LOSS_WINDOW: usize = 50;
+/// Window over which acknowledged throughput is averaged.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(30);
+/// EWMA weight of a new RTT sample (RFC 6298 uses 1/8).
+const RTT_ALPHA: f64 = 0.125;
+/// A frame unacked for this many times `max_rtt_ms` means the link is lost.
+const STALL_FACTOR: u32 = 10;
+const MMCLI_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HealthConfig {
+    /// RTT EWMA above this marks the link degraded. Default 900 ms.
+    pub max_rtt_ms: Option<u32>,
+    /// Frame loss above this marks the link degraded. Default 8%.
+    pub max_loss_pct: Option<f32>,
+    /// Modem RSRP below this marks the link degraded. Default -115 dBm.
+    pub min_rsrp_dbm: Option<f32>,
+    /// Poll ModemManager (`mmcli`) for signal metrics. Default false.
+    pub modem: Option<bool>,
+    /// Modem poll interval. Default 10 s.
+    pub modem_poll_s: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStatus {
+    Good,
+    Degraded,
+    Lost,
+}
+
+/// Signal report of the cellular modem, as far as ModemManager knows it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModemSignal {
+    /// e.g. "lte", "5gnr", "umts", "gsm"
+    pub access_tech: Option<String>,
+    pub rsrp_dbm: Option<f32>,
+    pub rsrq_db: Option<f32>,
+    pub sinr_db: Option<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LinkHealth {
+    pub status: LinkStatus,
+    /// EWMA of application ack round trips
+    pub rtt_ms: Option<u32>,
+    /// Unacknowledged share of the last frames sent
+    pub loss_pct: Option<f32>,
+    /// Acknowledged payload bits per second
+    pub throughput_bps: Option<u64>,
+    pub quality: u8,           // 0-100
+    pub consecutive_failures: u32,
+    pub modem: Option<ModemSignal>,
+    /// Last time the link was known to work, while it is lost
+    pub down_since: Option<Instant>,
+}
+
+impl Default for LinkHealth {
+    fn default() -> Self {
+        Self {
+            status: LinkStatus::Good,
+            rtt_ms: None,
+            loss_pct: None,
+            throughput_bps: None,
+            quality: 100,
+            consecutive_failures: 0,
+            modem: None,
+            down_since: None,
+        }
+    }
+}
+
+impl LinkHealth {
+    /// How long the link has been lost, `None` while it works.
+    pub fn down_for(&self) -> Option<Duration> {
+        self.down_since.map(|t| t.elapsed())
+    }
+
+    /// Position sampling interval on this link, as a multiple of `base`
+    /// - High quality (80-100%): 1x (frequent updates)
+    /// - Medium quality (50-79%): 2x (moderate updates)
+    /// - Low quality (20-49%): 4x (reduced updates)
+    /// - Poor quality (0-19%): 10x (minimal updates)
+    /// - After consecutive failures: exponential backoff up to 20x
+    pub fn sample_interval(&self, base: Duration) -> Duration {
+        // Apply exponential backoff for consecutive failures
+        if self.consecutive_failures > 0 {
+            return (base * (1u32 << self.consecutive_failures.min(4))).min(base * 20);
+        }
+
+        // Adaptive rate based on link quality
+        match self.quality {
+            80..=100 => base,    // High quality: frequent updates
+            50..=79 => base * 2, // Medium quality: moderate updates
+            20..=49 => base * 4, // Low quality: reduced updates
+            _ => base * 10,      // Poor quality: minimal updates
+        }
+    }
+}
+
+/// Accumulates link observations and derives `LinkHealth` from them.
+pub(crate) struct LinkEstimator {
+    max_rtt_ms: u32,
+    max_loss_pct: f32,
+    min_rsrp_dbm: f32,
+    srtt_ms: Option<f64>,
+    /// Per frame sent: true once acked, false if its session died first
+    outcomes: VecDeque<bool>,
+    acked: VecDeque<(Instant, u64)>,
+    last_ok: Option<Instant>,
+    health: LinkHealth,
+}
+
+impl LinkEstimator {
+    pub(crate) fn new(cfg: &HealthConfig) -> Result<Self> {
+        let max_rtt_ms = cfg.max_rtt_ms.unwrap_or(900);
+        anyhow::ensure!(max_rtt_ms > 0, "uplink.health.max_rtt_ms must be > 0");
+        let max_loss_pct = cfg.max_loss_pct.unwrap_or(8.0);
+        anyhow::ensure!(max_loss_pct > 0.0 && max_loss_pct < 100.0, "uplink.health.max_loss_pct must be in (0, 100)");
+        Ok(Self {
+            max_rtt_ms,
+            max_loss_pct,
+            min_rsrp_dbm: cfg.min_rsrp_dbm.unwrap_or(-115.0),
+            srtt_ms: None,
+            outcomes: VecDeque::with_capacity(LOSS_WINDOW),
+            acked: VecDeque::new(),
+            last_ok: None,
+            health: LinkHealth::default(),
+        })
+    }
+
+    pub(crate) fn health(&self) -> &LinkHealth {
+        &self.health
+    }
+
+    pub(crate) fn on_connect(&mut self) {
+        self.last_ok = Some(Instant::now());
+    }
+
+    /// An ack covering `frames` frames of `bytes` in total; `rtt` is that of
+    /// the newest frame it covers.
+    pub(crate) fn on_ack(&mut self, frames: usize, bytes: u64, rtt: Duration, at: Instant) {
+        let sample = rtt.as_secs_f64() * 1000.0;
+        self.srtt_ms = Some(match self.srtt_ms {
+            Some(s) => s + RTT_ALPHA * (sample - s),
+            None => sample,
+        });
+        for _ in 0..frames {
+            self.outcome(true);
+        }
+        self.acked.push_back((at, bytes));
+        self.last_ok = Some(at);
+        self.health.consecutive_failures = 0;
+    }
+
+    /// Frames that were in flight on a session that failed; they will be re-sent.
+    pub(crate) fn on_lost(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.outcome(false);
+        }
+    }
+
+    pub(crate) fn on_failure(&mut self) {
+        self.health.consecutive_failures += 1;
+    }
+
+    pub(crate) fn set_modem(&mut self, modem: Option<ModemSignal>) {
+        self.health.modem = modem;
+    }
+
+    fn outcome(&mut self, delivered: bool) {
+        if self.outcomes.len() == LOSS_WINDOW {
+            self.outcomes.pop_front();
+        }
+        self.outcomes.push_back(delivered);
+    }
+
+    /// Recompute the published health. `oldest_unacked` is how long the oldest
+    /// frame in flight has waited, if any.
+    pub(crate) fn refresh(&mut self, oldest_unacked: Option<Duration>) -> &LinkHealth {
+        let now = Instant::now();
+        while self.acked.front().map(|(t, _)| now.duration_since(*t) > THROUGHPUT_WINDOW).unwrap_or(false) {
+            self.acked.pop_front();
+        }
+
+        let h = &mut self.health;
+        h.rtt_ms = self.srtt_ms.map(|s| s.round() as u32);
+        h.loss_pct = (!self.outcomes.is_empty())
+            .then(|| self.outcomes.iter().filter(|d| !**d).count() as f32 * 100.0 / self.outcomes.len() as f32);
+        h.throughput_bps = (!self.acked.is_empty())
+            .then(|| self.acked.iter().map(|(_, b)| b).sum::<u64>() * 8 / THROUGHPUT_WINDOW.as_secs());
+        let rsrp = h.modem.as_ref().and_then(|m| m.rsrp_dbm);
+
+        let stalled = oldest_unacked.map(|w| w > Duration::from_millis(self.max_rtt_ms as u64) * STALL_FACTOR).unwrap_or(false);
+        let degraded = h.rtt_ms.map(|r| r > self.max_rtt_ms).unwrap_or(false)
+            || h.loss_pct.map(|l| l > self.max_loss_pct).unwrap_or(false)
+            || rsrp.map(|r| r < self.min_rsrp_dbm).unwrap_or(false);
+        h.status = if h.consecutive_failures > 0 || stalled {
+            LinkStatus::Lost
+        } else if degraded {
+            LinkStatus::Degraded
+        } else {
+            LinkStatus::Good
+        };
+
+        // Each metric scores 1.0 at half its threshold, 0.5 at the threshold and
+        // 0 at one and a half times it; the worst one decides
+        let mut q = 1.0f32;
+        if let Some(r) = h.rtt_ms {
+            q = q.min(score(r as f32, self.max_rtt_ms as f32));
+        }
+        if let Some(l) = h.loss_pct {
+            q = q.min(score(l, self.max_loss_pct));
+        }
+        if let Some(r) = rsrp {
+            // 20 dB either side of the threshold
+            q = q.min(((r - self.min_rsrp_dbm) / 40.0 + 0.5).clamp(0.0, 1.0));
+        }
+        h.quality = if h.status == LinkStatus::Lost { 0 } else { (q * 100.0).round() as u8 };
+
+        match h.status {
+            LinkStatus::Lost => {
+                if h.down_since.is_none() {
+                    h.down_since = Some(self.last_ok.unwrap_or(now));
+                    warn!("uplink: link lost (failures: {}, stalled: {})", h.consecutive_failures, stalled);
+                }
+            }
+            _ => {
+                if h.down_since.take().is_some() {
+                    info!("uplink: link restored ({:?})", h.status);
+                }
+            }
+        }
+        &self.health
+    }
+}
+
+/// 1.0 at or below half the threshold, linearly down to 0 at 1.5x.
+fn score(value: f32, threshold: f32) -> f32 {
+    (1.5 - value / threshold).clamp(0.0, 1.0)
+}
+
+/// Poll ModemManager every `every` and publish the latest signal report.
+pub(crate) fn spawn_modem_poll(every: Duration) -> (JoinHandle<()>, watch::Receiver<Option<ModemSignal>>) {
+    let (tx, rx) = watch::channel(None);
+    let task = tokio::spawn(async move {
+        // Signal metrics are only reported once a refresh rate is set
+        if let Err(e) = mmcli(&["--signal-setup", &every.as_secs().max(1).to_string()]).await {
+            warn!("uplink: modem signal setup failed: {:#}", e);
+        }
+        let mut failing = false;
+        loop {
+            match read_modem().await {
+                Ok(m) => {
+                    if failing {
+                        info!("uplink: modem metrics available again");
+                        failing = false;
+                    }
+                    tx.send_replace(Some(m));
+                }
+                Err(e) => {
+                    if !failing {
+                        warn!("uplink: reading modem metrics failed: {:#}", e);
+                        failing = true;
+                    }
+                    tx.send_replace(None);
+                }
+            }
+            tokio::time::sleep(every).await;
+        }
+    });
+    (task, rx)
+}
+
+/// Access technology and signal of the first modem ModemManager knows about.
+pub async fn read_modem() -> Result<ModemSignal> {
+    let status = mmcli(&[]).await?;
+    let signal = mmcli(&["--signal-get"]).await?;
+    Ok(modem_signal(&status, &signal))
+}
+
+async fn mmcli(args: &[&str]) -> Result<HashMap<String, String>> {
+    let out = tokio::time::timeout(
+        MMCLI_TIMEOUT,
+        Command::new("mmcli").args(["-m", "any", "--output-keyvalue"]).args(args).kill_on_drop(true).output(),
+    )
+    .await
+    .context("mmcli timed out")?
+    .context("run mmcli")?;
+    anyhow::ensure!(out.status.success(), "mmcli {}: {}", out.status, String::from_utf8_lossy(&out.stderr).trim());
+    Ok(parse_keyvalue(&String::from_utf8_lossy(&out.stdout)))
+}
+
+/// `key : value` lines of `mmcli --output-keyvalue`; "--" means unknown.
+fn parse_keyvalue(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .filter_map(|l| l.split_once(" : "))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .filter(|(_, v)| !v.is_empty() && v != "--")
+        .collect()
+}
+
+fn modem_signal(status: &HashMap<String, String>, signal: &HashMap<String, String>) -> ModemSignal {
+    let access_tech = status.get("modem.generic.access-technologies.value[1]").cloned();
+    // The newest technology with a report wins
+    let tech = ["5g", "lte"].into_iter().find(|t| signal.contains_key(&format!("modem.signal.{}.rsrp", t)));
+    let metric = |name: &str| tech.and_then(|t| signal.get(&format!("modem.signal.{}.{}", t, name))).and_then(|v| v.parse().ok());
+    ModemSignal { access_tech, rsrp_dbm: metric("rsrp"), rsrq_db: metric("rsrq"), sinr_db: metric("snr") }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimator(max_rtt_ms: u32) -> LinkEstimator {
+        LinkEstimator::new(&HealthConfig { max_rtt_ms: Some(max_rtt_ms), ..Default::default() }).unwrap()
+    }
+
+    fn ack(e: &mut LinkEstimator, frames: usize, rtt_ms: u64) {
+        e.on_ack(frames, 1000, Duration::from_millis(rtt_ms), Instant::now());
+    }
+
+    #[test]
+    fn rtt_ewma_converges() {
+        let mut e = estimator(300);
+        ack(&mut e, 1, 100);
+        assert_eq!(e.refresh(None).rtt_ms, Some(100));
+        ack(&mut e, 1, 500);
+        assert_eq!(e.refresh(None).rtt_ms, Some(150));
+        assert_eq!(e.health().status, LinkStatus::Good);
+        for _ in 0..60 {
+            ack(&mut e, 1, 500);
+        }
+        let h = e.refresh(None);
+        assert!((499..=500).contains(&h.rtt_ms.unwrap()), "{:?}", h.rtt_ms);
+        assert_eq!(h.status, LinkStatus::Degraded);
+        assert_eq!(h.quality, 0);
+    }
+
+    #[test]
+    fn loss_and_throughput() {
+        let mut e = estimator(900);
+        ack(&mut e, 46, 100);
+        e.on_lost(4);
+        let h = e.refresh(None);
+        assert_eq!(h.loss_pct, Some(8.0));
+        assert_eq!(h.status, LinkStatus::Good);
+        // 1000 bytes acked over the 30 s window
+        assert_eq!(h.throughput_bps, Some(266));
+
+        e.on_lost(1);
+        let h = e.refresh(None);
+        assert_eq!(h.loss_pct, Some(10.0));
+        assert_eq!(h.status, LinkStatus::Degraded);
+
+        // Only the last LOSS_WINDOW frames count
+        ack(&mut e, LOSS_WINDOW, 100);
+        assert_eq!(e.refresh(None).loss_pct, Some(0.0));
+        assert_eq!(e.health().status, LinkStatus::Good);
+    }
+
+    #[test]
+    fn stalled_frames_and_failures_lose_the_link() {
+        let mut e = estimator(100);
+        e.on_connect();
+        ack(&mut e, 1, 50);
+        assert_eq!(e.refresh(Some(Duration::from_millis(1000))).status, LinkStatus::Good);
+        let h = e.refresh(Some(Duration::from_millis(1001)));
+        assert_eq!(h.status, LinkStatus::Lost);
+        assert_eq!(h.quality, 0);
+        assert!(h.down_for().is_some());
+        assert!(e.refresh(None).down_for().is_none());
+
+        e.on_failure();
+        assert_eq!(e.refresh(None).status, LinkStatus::Lost);
+        ack(&mut e, 1, 50);
+        assert_eq!(e.refresh(None).status, LinkStatus::Good);
+    }
+
+    #[test]
+    fn modem_signal_degrades_only_when_known() {
+        let mut e = estimator(900);
+        ack(&mut e, 1, 100);
+        e.set_modem(Some(ModemSignal { rsrp_dbm: None, ..Default::default() }));
+        assert_eq!(e.refresh(None).status, LinkStatus::Good);
+        e.set_modem(Some(ModemSignal { rsrp_dbm: Some(-95.0), ..Default::default() }));
+        let h = e.refresh(None);
+        assert_eq!(h.status, LinkStatus::Good);
+        assert_eq!(h.quality, 100);
+        e.set_modem(Some(ModemSignal { rsrp_dbm: Some(-120.0), ..Default::default() }));
+        let h = e.refresh(None);
+        assert_eq!(h.status, LinkStatus::Degraded);
+        assert_eq!(h.quality, 38);
+        e.set_modem(None);
+        assert_eq!(e.refresh(None).status, LinkStatus::Good);
+    }
+
+    #[test]
+    fn scores() {
+        assert_eq!(score(100.0, 900.0), 1.0);
+        assert_eq!(score(450.0, 900.0), 1.0);
+        assert_eq!(score(900.0, 900.0), 0.5);
+        assert_eq!(score(1350.0, 900.0), 0.0);
+        assert_eq!(score(5000.0, 900.0), 0.0);
+    }
+
+    #[test]
+    fn mmcli_keyvalue_output() {
+        let status = parse_keyvalue("\
+modem.dbus-path                                 : /org/freedesktop/ModemManager1/Modem/0
+modem.generic.manufacturer                      : Quectel
+modem.generic.access-technologies.length        : 1
+modem.generic.access-technologies.value[1]      : lte
+modem.generic.signal-quality.value              : 67
+modem.generic.signal-quality.recent             : yes
+modem.3gpp.operator-name                        : --
+");
+        assert!(!status.contains_key("modem.3gpp.operator-name"));
+        let signal = parse_keyvalue("\
+modem.signal.refresh.rate                       : 10
+modem.signal.gsm.rssi                           : --
+modem.signal.umts.rscp                          : --
+modem.signal.lte.rssi                           : -71.00
+modem.signal.lte.rsrq                           : -11.00
+modem.signal.lte.rsrp                           : -98.00
+modem.signal.lte.snr                            : 9.40
+modem.signal.5g.rsrq                            : --
+modem.signal.5g.rsrp                            : --
+modem.signal.5g.snr                             : --
+");
+        assert_eq!(modem_signal(&status, &signal), ModemSignal {
+            access_tech: Some("lte".into()),
+            rsrp_dbm: Some(-98.0),
+            rsrq_db: Some(-11.0),
+            sinr_db: Some(9.4),
+        });
+
+        // No signal report yet (refresh rate not set up)
+        let m = modem_signal(&status, &parse_keyvalue("modem.signal.refresh.rate : 0\n"));
+        assert_eq!(m, ModemSignal { access_tech: Some("lte".into()), ..Default::default() });
+        assert_eq!(modem_signal(&HashMap::new(), &HashMap::new()), ModemSignal::default());
+    }
+}
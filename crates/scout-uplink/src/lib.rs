@@ -1,252 +1,637 @@
 pub mod doctor;
-mod cert_pin;
+pub mod endpoint;
+pub mod health;
+pub mod spool;
+pub mod scheduler;
+pub mod cert_pin;
 
 use anyhow::{Context, Result};
-use rustls::{ClientConfig, RootCertStore};
-use rustls_pki_types::ServerName;
+use rustls::HandshakeKind;
 use scout_crypto::{aead, keys::DeviceKeys};
-use scout_proto::telemetry::TelemetryEvent;
-use std::{path::Path, sync::Arc};
-use tokio::{fs, io::AsyncWriteExt, net::TcpStream};
-use tokio_rustls::TlsConnector;
+use scout_proto::codec::{self, Compression, Format};
+use scout_proto::link;
+use scout_proto::telemetry::{Envelope, Payload, Priority, SpoolStats, SCHEMA_VERSION};
+use serde::Deserialize;
+use std::{collections::VecDeque, path::{Path, PathBuf}, time::{Duration, Instant}};
+use tokio::{fs, net::TcpStream, sync::mpsc, task::JoinHandle, time::timeout};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tracing::{info, warn};
 
-#[derive(Debug, Clone)]
-pub struct LinkHealth {
-    pub rtt_ms: Option<u32>,
-    pub quality: u8,           // 0-100
-    pub consecutive_failures: u32,
+use endpoint::{ClientAuth, Endpoint, EndpointConfig};
+use health::{HealthConfig, LinkEstimator, LinkHealth};
+
+/// How envelopes are sampled, encoded, batched and compressed before sealing.
+/// One sealed spool file holds one batch.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FrameConfig {
+    /// Position sampling interval on a good link; stretched as the link degrades
+    /// (see `Uplink::recommended_interval`). Default 1000 ms.
+    pub telemetry_interval_ms: Option<u64>,
+    /// binary (default, compact) | json
+    pub encoding: Option<Format>,
+    /// deflate (default) | none
+    pub compression: Option<Compression>,
+    /// Seal the batch once its encoded size reaches this. Default 4096 bytes.
+    pub batch_max_bytes: Option<usize>,
+    /// Seal the batch once its oldest event is this old. Default 5000 ms.
+    pub batch_max_ms: Option<u64>,
 }
 
-impl Default for LinkHealth {
-    fn default() -> Self {
-        Self {
-            rtt_ms: None,
-            quality: 100,
-            consecutive_failures: 0,
+/// Frames sent but not yet acknowledged, bounding what a dead link can hold up.
+const MAX_INFLIGHT: usize = 16;
+const IO_TIMEOUT: Duration = Duration::from_secs(15);
+/// Drop the session if the oldest in-flight frame waits longer than this.
+const ACK_TIMEOUT: Duration = Duration::from_secs(30);
+const RECONNECT_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_MAX: Duration = Duration::from_secs(60);
+
+struct Session {
+    tx: Box<dyn AsyncWrite + Send + Sync + Unpin>,
+    /// Replies as timestamped by `read_replies` on arrival; an error ends the session
+    replies: mpsc::UnboundedReceiver<Result<(link::Reply, Instant)>>,
+    reader: JoinHandle<()>,
+    /// In send order, which is what replies are cumulative over
+    inflight: VecDeque<Inflight>,
+}
+
+impl Session {
+    /// Session over an established stream that has sent `link::HELLO`.
+    fn new<S: AsyncRead + AsyncWrite + Send + Sync + 'static>(io: S) -> Self {
+        let (rx, tx) = tokio::io::split(io);
+        let (reply_tx, replies) = mpsc::unbounded_channel();
+        let reader = tokio::spawn(read_replies(rx, reply_tx));
+        Self { tx: Box::new(tx), replies, reader, inflight: VecDeque::new() }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+async fn read_replies(mut rx: impl AsyncRead + Unpin, replies: mpsc::UnboundedSender<Result<(link::Reply, Instant)>>) {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 512];
+    loop {
+        let n = match rx.read(&mut chunk).await {
+            Ok(0) => Err(anyhow::anyhow!("session closed")),
+            Ok(n) => Ok(n),
+            Err(e) => Err(anyhow::Error::new(e).context("session closed")),
+        };
+        let n = match n {
+            Ok(n) => n,
+            Err(e) => {
+                let _ = replies.send(Err(e));
+                return;
+            }
+        };
+        buf.extend_from_slice(&chunk[..n]);
+        let at = Instant::now();
+        loop {
+            let r = match link::parse_reply(&buf) {
+                Ok(Some((reply, len))) => {
+                    buf.drain(..len);
+                    Ok((reply, at))
+                }
+                Ok(None) => break,
+                Err(e) => Err(e),
+            };
+            let done = r.is_err();
+            if replies.send(r).is_err() || done {
+                return;
+            }
         }
     }
 }
 
+/// Open batch of one priority class, not yet sealed or on disk.
+#[derive(Default)]
+struct Batch {
+    envs: Vec<Envelope>,
+    bytes: usize,
+    since: Option<Instant>,
+}
+
+struct Inflight {
+    id: u64,
+    path: PathBuf,
+    bytes: u64,
+    sent: Instant,
+}
+
 pub struct Uplink {
-    endpoint: String,
+    /// In order of preference
+    endpoints: Vec<Endpoint>,
+    /// Endpoint of the current (or last attempted) session
+    current: usize,
     spool_dir: String,
     spool_max_bytes: u64,
     keys: DeviceKeys,
-    tls: TlsConnector,
-    health: LinkHealth,
+    link: LinkEstimator,
+    /// ModemManager poll interval, if enabled
+    modem_poll: Option<Duration>,
+    device_id: String,
+    encoding: Format,
+    compression: Compression,
+    batch_max_bytes: usize,
+    batch_max_age: Duration,
+    telemetry_interval: Duration,
+    next_seq: u64,
+    /// Bound into every sealed blob (see `aead::BlobHeader`)
+    boot_id: [u8; 16],
+    /// Next blob sequence number per priority class, persisted at `sequence_path`
+    blob_seq: [u64; 3],
+    sequence_path: PathBuf,
+    evicted: u64,
+    /// Newest spool record id, at startup the newest one on disk; ids are
+    /// nanosecond timestamps made strictly increasing
+    last_record_id: u64,
+
+    // Persistent TLS session; reopened on error once an endpoint's backoff expires
+    session: Option<Session>,
+
+    // Open batches, indexed by `Priority as usize`
+    pending: [Batch; 3],
 }
 
 impl Uplink {
-    pub fn new(endpoint: String, pin_spki_hex: String, spool_dir: String, spool_max_mb: u64, keys: DeviceKeys) -> Result<Self> {
-        let mut roots = RootCertStore::empty();
-        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-
-        let cfg = if !pin_spki_hex.is_empty() {
-            // Use certificate pinning
-            use rustls::client::WebPkiServerVerifier;
-
-            let fallback_verifier = WebPkiServerVerifier::builder(roots.clone().into())
-                .build()
-                .map_err(|e| anyhow::anyhow!("Failed to build fallback verifier: {:?}", e))?;
-
-            match cert_pin::PinnedCertVerifier::new(&pin_spki_hex, fallback_verifier) {
-                Ok(pinned_verifier) => {
-                    info!("uplink: certificate pinning enabled (SPKI SHA256: {}...)", &pin_spki_hex[..16]);
-                    ClientConfig::builder()
-                        .dangerous()
-                        .with_custom_certificate_verifier(Arc::new(pinned_verifier))
-                        .with_no_client_auth()
-                }
-                Err(e) => {
-                    warn!("uplink: certificate pinning failed to initialize: {:#} - falling back to standard validation", e);
-                    ClientConfig::builder().with_root_certificates(roots.clone()).with_no_client_auth()
-                }
-            }
-        } else {
-            warn!("uplink: certificate pinning NOT enabled (pin_spki_hex empty) - vulnerable to MITM on slow links!");
-            ClientConfig::builder().with_root_certificates(roots).with_no_client_auth()
-        };
+    /// `client` is the certificate presented for mutual TLS, if any.
+    /// `sequence_path` must be outside `spool_dir` (see `spool`).
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(endpoints: Vec<EndpointConfig>, client: Option<ClientAuth>, spool_dir: String, sequence_path: PathBuf, spool_max_mb: u64, keys: DeviceKeys, device_id: String, frames: FrameConfig, health: HealthConfig) -> Result<Self> {
+        anyhow::ensure!(!endpoints.is_empty(), "uplink: no endpoint configured");
+        let endpoints = endpoints.iter().map(|e| Endpoint::new(e, client.as_ref())).collect::<Result<Vec<_>>>()?;
+
+        let batch_max_bytes = frames.batch_max_bytes.unwrap_or(4096);
+        anyhow::ensure!((256..=codec::MAX_FRAME_BYTES as usize).contains(&batch_max_bytes), "uplink.batch_max_bytes out of range");
+        let batch_max_ms = frames.batch_max_ms.unwrap_or(5000);
+        anyhow::ensure!(batch_max_ms > 0, "uplink.batch_max_ms must be > 0");
+        let telemetry_interval_ms = frames.telemetry_interval_ms.unwrap_or(1000);
+        anyhow::ensure!(telemetry_interval_ms > 0, "uplink.telemetry_interval_ms must be > 0");
+        let link = LinkEstimator::new(&health)?;
+        let modem_poll = health.modem.unwrap_or(false).then(|| Duration::from_secs(health.modem_poll_s.unwrap_or(10).max(1)));
+
+        let rec = spool::recover(Path::new(&spool_dir)).context("spool recovery")?;
+        if rec.removed_tmp + rec.quarantined > 0 {
+            warn!("uplink: spool recovery removed {} partial writes, quarantined {} corrupt records", rec.removed_tmp, rec.quarantined);
+        }
+        anyhow::ensure!(!sequence_path.starts_with(&spool_dir), "uplink.sequence_path must be outside the spool directory");
+        let blob_seq = spool::migrate_sequence(&sequence_path, Path::new(&spool_dir)).context("uplink sequence")?;
+        let last_record_id = spool::list(Path::new(&spool_dir))?.iter().map(|f| f.id).max().unwrap_or(0);
 
-        let tls = TlsConnector::from(Arc::new(cfg));
         Ok(Self {
-            endpoint,
+            endpoints,
+            current: 0,
             spool_dir,
             spool_max_bytes: spool_max_mb * 1024 * 1024,
             keys,
-            tls,
-            health: LinkHealth::default(),
+            link,
+            modem_poll,
+            device_id,
+            encoding: frames.encoding.unwrap_or_default(),
+            compression: frames.compression.unwrap_or_default(),
+            batch_max_bytes,
+            batch_max_age: Duration::from_millis(batch_max_ms),
+            telemetry_interval: Duration::from_millis(telemetry_interval_ms),
+            next_seq: 0,
+            boot_id: aead::boot_id(),
+            blob_seq,
+            sequence_path,
+            evicted: 0,
+            last_record_id,
+            session: None,
+            pending: Default::default(),
         })
     }
 
     pub fn link_health(&self) -> &LinkHealth {
-        &self.health
+        self.link.health()
+    }
+
+    /// Recommended position sampling interval, as a multiple of
+    /// `telemetry_interval_ms` (see `LinkHealth::sample_interval`).
+    pub fn recommended_interval(&self) -> Duration {
+        self.link.health().sample_interval(self.telemetry_interval)
+    }
+
+    /// False while every endpoint is in its reconnect backoff.
+    pub fn should_attempt_flush(&self) -> bool {
+        self.next_endpoint().is_some()
+    }
+
+    /// The most preferred endpoint whose backoff has expired.
+    fn next_endpoint(&self) -> Option<usize> {
+        endpoint::next_due(&self.endpoints, Instant::now())
     }
 
-    /// Returns recommended telemetry interval in seconds based on link quality
-    /// - High quality (80-100%): 30s (frequent updates)
-    /// - Medium quality (50-79%): 60s (moderate updates)
-    /// - Low quality (20-49%): 120s (reduced updates)
-    /// - Poor quality (0-19%): 300s (minimal updates)
-    /// - After consecutive failures: exponential backoff up to 600s
-    pub fn recommended_interval_secs(&self) -> u64 {
-        // Apply exponential backoff for consecutive failures
-        if self.health.consecutive_failures > 0 {
-            let backoff = 30u64 << self.health.consecutive_failures.min(4);
-            return backoff.min(600); // Cap at 10 minutes
+    /// Drop the session after an interrupted flush; unacked files are re-sent.
+    pub fn abandon_session(&mut self) {
+        if let Some(s) = self.session.take() {
+            self.link.on_lost(s.inflight.len());
+            self.link.refresh(None);
         }
+    }
 
-        // Adaptive rate based on link quality
-        match self.health.quality {
-            80..=100 => 30,   // High quality: frequent updates
-            50..=79 => 60,    // Medium quality: moderate updates
-            20..=49 => 120,   // Low quality: reduced updates
-            _ => 300,         // Poor quality: minimal updates
+    /// Skip the reconnect backoff on the next flush (failsafe events).
+    pub fn expedite(&mut self) {
+        for e in &mut self.endpoints {
+            e.retry_at = None;
         }
     }
 
-    /// Returns whether we should attempt flush based on backoff state
-    pub fn should_attempt_flush(&self) -> bool {
-        // Always allow first attempt
-        if self.health.consecutive_failures == 0 {
-            return true;
+    /// Wrap `payload` in an envelope (device id, next sequence number, timestamp)
+    /// and add it to the open batch of its priority class. A batch is sealed into
+    /// the spool once it reaches `batch_max_bytes` or `batch_max_ms`; critical
+    /// batches are sealed on the next flush.
+    pub async fn send(&mut self, payload: Payload) -> Result<()> {
+        let env = Envelope {
+            v: SCHEMA_VERSION,
+            device_id: self.device_id.clone(),
+            seq: self.next_seq,
+            ts_unix_ms: (time::OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000) as i64,
+            payload,
+        };
+        self.next_seq += 1;
+        let prio = env.payload.priority();
+        // Standalone size is an upper bound: inside a binary batch deltas shrink it
+        let bytes = codec::encode(self.encoding, std::slice::from_ref(&env))?.len();
+        let batch = &mut self.pending[prio as usize];
+        batch.bytes += bytes;
+        batch.envs.push(env);
+        batch.since.get_or_insert_with(Instant::now);
+        if batch.bytes >= self.batch_max_bytes || self.batch_due(prio) {
+            self.seal_class(prio).await?;
+        }
+        Ok(())
+    }
+
+    fn batch_due(&self, prio: Priority) -> bool {
+        let batch = &self.pending[prio as usize];
+        match prio {
+            Priority::Critical => !batch.envs.is_empty(),
+            _ => batch.since.map(|t| t.elapsed() >= self.batch_max_age).unwrap_or(false),
         }
+    }
 
-        // For failures, use exponential backoff
-        // This prevents hammering a dead link
-        true // Actual backoff timing should be handled by caller with timers
+    /// Encode, compress and seal every open batch into spool files.
+    /// Call before shutdown so buffered events are not lost.
+    pub async fn seal_batch(&mut self) -> Result<()> {
+        for prio in Priority::ALL.into_iter().rev() {
+            self.seal_class(prio).await?;
+        }
+        Ok(())
     }
 
-    pub async fn send_event(&mut self, ev: &TelemetryEvent) -> Result<()> {
-        let payload = serde_json::to_vec(ev)?;
-        let blob = aead::seal(&self.keys.aead, b"navscout-telemetry-v1", &payload)?;
-        self.spool_write(&blob).await?;
+    async fn seal_class(&mut self, prio: Priority) -> Result<()> {
+        let batch = std::mem::take(&mut self.pending[prio as usize]);
+        if batch.envs.is_empty() {
+            return Ok(());
+        }
+        let frame = codec::encode(self.encoding, &batch.envs)?;
+        let frame = codec::compress(self.compression, frame)?;
+        let header = self.reserve_seq(prio).await?;
+        let blob = self.keys.telemetry().seal_bound(link::TELEMETRY_AAD, &header, &frame)?;
+        self.spool_write(&blob, prio).await?;
+        info!("uplink: sealed {} batch of {} events ({}B)", prio.name(), batch.envs.len(), blob.len());
         Ok(())
     }
 
-    async fn spool_write(&self, blob: &[u8]) -> Result<()> {
+    /// Header for the next blob of `prio`. The sequence number is persisted
+    /// before it is used, so a restart never reuses one.
+    async fn reserve_seq(&mut self, prio: Priority) -> Result<aead::BlobHeader> {
+        let seq = self.blob_seq[prio as usize];
+        let mut next = self.blob_seq;
+        next[prio as usize] += 1;
+        let path = self.sequence_path.clone();
+        tokio::task::spawn_blocking(move || spool::store_sequence(&path, &next)).await??;
+        self.blob_seq = next;
+        Ok(self.keys.header(&self.device_id, self.boot_id, prio as u8, seq))
+    }
+
+    /// Current spool occupancy.
+    pub async fn spool_stats(&self) -> Result<SpoolStats> {
+        let files = self.spool_files().await?;
+        Ok(SpoolStats {
+            files: files.len() as u64,
+            bytes: files.iter().map(|f| f.bytes).sum(),
+            evicted: self.evicted,
+        })
+    }
+
+    async fn spool_write(&mut self, blob: &[u8], prio: Priority) -> Result<()> {
         fs::create_dir_all(&self.spool_dir).await?;
 
         // Check spool size and evict old files if needed
         self.enforce_spool_limit().await?;
 
-        let name = format!("{}/{}.bin", self.spool_dir, time::OffsetDateTime::now_utc().unix_timestamp_nanos());
-        let mut f = fs::File::create(&name).await?;
-        f.write_all(blob).await?;
+        let now = time::OffsetDateTime::now_utc().unix_timestamp_nanos() as u64;
+        let min_id = now.max(self.last_record_id + 1);
+        let dir = PathBuf::from(&self.spool_dir);
+        let record = spool::encode_record(blob);
+        self.last_record_id = tokio::task::spawn_blocking(move || spool::write_record(&dir, min_id, prio, &record)).await??;
+        Ok(())
+    }
+
+    /// Enforce the spool size limit, evicting the lowest priority class first and
+    /// the oldest files within it. Critical files go only once nothing else is left.
+    async fn enforce_spool_limit(&mut self) -> Result<()> {
+        let (dir, max) = (PathBuf::from(&self.spool_dir), self.spool_max_bytes);
+        self.evicted += tokio::task::spawn_blocking(move || spool::enforce_limit(&dir, max)).await??;
         Ok(())
     }
 
-    /// Enforce spool size limit by evicting oldest files when over capacity
-    async fn enforce_spool_limit(&self) -> Result<()> {
+    /// Seal a due batch, then push spool files over the persistent session and
+    /// collect any acks already received. Does not wait for acks; files are
+    /// deleted only once the server has acknowledged them.
+    pub async fn flush_spool(&mut self) -> Result<()> {
+        for prio in Priority::ALL.into_iter().rev() {
+            if self.batch_due(prio) {
+                self.seal_class(prio).await?;
+            }
+        }
+        self.pump(Duration::ZERO).await.map(|_| ())
+    }
+
+    /// Flush and wait up to `wait` for every spool file to be acknowledged.
+    /// Used at shutdown; ignores the reconnect backoff.
+    pub async fn drain(&mut self, wait: Duration) -> Result<()> {
+        self.seal_batch().await?;
+        self.expedite();
+        let deadline = Instant::now() + wait;
+        loop {
+            if self.pump(Duration::from_millis(200)).await? {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                let left = self.spool_files().await?.len();
+                anyhow::bail!("{} spool files still unacknowledged after {:?}", left, wait);
+            }
+        }
+    }
+
+    /// Spool files in flush order (see `spool::sort_for_flush`).
+    async fn spool_files(&self) -> Result<Vec<spool::SpoolFile>> {
         let dir = Path::new(&self.spool_dir);
+        let mut files = Vec::new();
         if !dir.exists() {
-            return Ok(());
+            return Ok(files);
         }
-
-        // Collect all spool files with their sizes and modification times
         let mut entries = fs::read_dir(dir).await?;
-        let mut files: Vec<(std::path::PathBuf, u64, std::time::SystemTime)> = Vec::new();
-        let mut total_size: u64 = 0;
-
         while let Some(ent) = entries.next_entry().await? {
             let path = ent.path();
-            if !path.is_file() { continue; }
-            if let Ok(meta) = ent.metadata().await {
-                let size = meta.len();
-                let mtime = meta.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
-                files.push((path, size, mtime));
-                total_size += size;
+            let Ok(meta) = ent.metadata().await else { continue };
+            if !meta.is_file() || ent.file_name() == spool::LEGACY_SEQUENCE_FILE { continue; }
+            match spool::parse_name(&path) {
+                Some((id, priority)) => files.push(spool::SpoolFile { id, priority, path, bytes: meta.len() }),
+                None => warn!("uplink: ignoring spool file with unexpected name {:?}", path),
             }
         }
+        spool::sort_for_flush(&mut files);
+        Ok(files)
+    }
 
-        // If under limit, nothing to do
-        if total_size <= self.spool_max_bytes {
-            return Ok(());
+    /// One round of the session: connect if needed, send unsent frames up to
+    /// `MAX_INFLIGHT`, then read acks (waiting at most `ack_wait` for the first).
+    /// Returns true once the spool is empty and nothing is awaiting an ack.
+    async fn pump(&mut self, ack_wait: Duration) -> Result<bool> {
+        let frames = self.spool_files().await?;
+        if self.session.is_none() {
+            if frames.is_empty() {
+                return Ok(true);
+            }
+            if !self.should_attempt_flush() {
+                return Ok(false);
+            }
         }
-
-        // Sort by modification time (oldest first)
-        files.sort_by(|a, b| a.2.cmp(&b.2));
-
-        // Delete oldest files until under limit (with 10% headroom)
-        let target = (self.spool_max_bytes as f64 * 0.9) as u64;
-        for (path, size, _) in files {
-            if total_size <= target {
-                break;
+        let result = self.pump_session(&frames, ack_wait).await;
+        match &result {
+            Ok(_) => {
+                let oldest = self.session.as_ref().and_then(|s| s.inflight.front()).map(|f| f.sent.elapsed());
+                self.link.refresh(oldest);
             }
-            if let Err(e) = fs::remove_file(&path).await {
-                warn!("spool eviction failed for {:?}: {}", path, e);
-            } else {
-                info!("spool evicted old file: {:?} ({}B)", path, size);
-                total_size = total_size.saturating_sub(size);
+            Err(e) => {
+                if let Some(s) = self.session.take() {
+                    self.link.on_lost(s.inflight.len());
+                }
+                self.link.on_failure();
+                let loss = self.link.refresh(None).loss_pct.unwrap_or(0.0);
+                let ep = &mut self.endpoints[self.current];
+                let backoff = ep.failed(RECONNECT_MIN, RECONNECT_MAX);
+                warn!("uplink: session to {} failed (failures: {}, loss: {:.0}%, retry in {:?}): {:#}",
+                      ep.url, ep.failures, loss, backoff, e);
+                if let Some(next) = self.next_endpoint() {
+                    info!("uplink: failing over to {}", self.endpoints[next].url);
+                }
             }
         }
-
-        Ok(())
+        result
     }
 
-    pub async fn flush_spool(&mut self) -> Result<()> {
-        let dir = Path::new(&self.spool_dir);
-        if !dir.exists() {
-            return Ok(());
+    async fn pump_session(&mut self, frames: &[spool::SpoolFile], ack_wait: Duration) -> Result<bool> {
+        if self.session.is_none() {
+            self.current = self.next_endpoint().context("all endpoints in backoff")?;
+            self.session = Some(self.connect().await?);
+            self.link.on_connect();
         }
-        let mut entries = fs::read_dir(dir).await?;
-        while let Some(ent) = entries.next_entry().await? {
-            let path = ent.path();
-            if !path.is_file() { continue; }
-            let blob = fs::read(&path).await?;
-            if let Err(e) = self.send_blob(&blob).await {
-                // keep it for retry
-                return Err(e);
+        let s = self.session.as_mut().unwrap();
+
+        let mut sent = 0;
+        for f in frames {
+            if s.inflight.len() >= MAX_INFLIGHT { break; }
+            if s.inflight.iter().any(|i| i.id == f.id) { continue; }
+            let record = match fs::read(&f.path).await {
+                Ok(b) => b,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue, // evicted meanwhile
+                Err(e) => return Err(e.into()),
+            };
+            let blob = match spool::decode_record(&f.path, &record) {
+                Ok(b) => b,
+                Err(e) => {
+                    // A bad record must not wedge the queue behind it
+                    match spool::quarantine(&f.path) {
+                        Ok(dest) => warn!("uplink: quarantined {:?}: {:#}", dest, e),
+                        Err(qe) => warn!("uplink: cannot quarantine {:?} ({:#}): {:#}", f.path, e, qe),
+                    }
+                    continue;
+                }
+            };
+            let hdr = link::frame_header(f.id, blob.len())?;
+            timeout(IO_TIMEOUT, async {
+                s.tx.write_all(&hdr).await?;
+                s.tx.write_all(blob).await
+            }).await.context("write timed out")??;
+            s.inflight.push_back(Inflight { id: f.id, path: f.path.clone(), bytes: (hdr.len() + blob.len()) as u64, sent: Instant::now() });
+            sent += 1;
+        }
+        if sent > 0 {
+            timeout(IO_TIMEOUT, s.tx.flush()).await.context("write timed out")??;
+        }
+
+        let mut wait = ack_wait;
+        while !s.inflight.is_empty() {
+            let msg = if wait.is_zero() {
+                match s.replies.try_recv() {
+                    Ok(m) => m,
+                    Err(_) => break, // nothing more for now
+                }
             } else {
-                fs::remove_file(&path).await.ok();
+                match timeout(wait, s.replies.recv()).await {
+                    Ok(Some(m)) => m,
+                    _ => break,
+                }
+            };
+            let (reply, at) = msg?;
+            let (acked, rejected) = match reply {
+                link::Reply::Ack(id) => (id, None),
+                link::Reply::Reject { id, reason } => (id, Some(reason)),
+            };
+            anyhow::ensure!(s.inflight.iter().any(|f| f.id == acked), "reply for frame {} that is not in flight", acked);
+
+            let (mut frames, mut bytes, mut last) = (0, 0, None);
+            while let Some(f) = s.inflight.pop_front() {
+                match &rejected {
+                    // Re-sending it cannot help, and would hold up its class
+                    Some(reason) if f.id == acked => match spool::quarantine(&f.path) {
+                        Ok(dest) => warn!("uplink: frame {} rejected ({}), quarantined {:?}", f.id, reason, dest),
+                        Err(e) => warn!("uplink: frame {} rejected ({}), cannot quarantine {:?}: {:#}", f.id, reason, f.path, e),
+                    },
+                    _ => { fs::remove_file(&f.path).await.ok(); }
+                }
+                frames += 1;
+                bytes += f.bytes;
+                let done = f.id == acked;
+                last = Some(f);
+                if done { break; }
             }
+            // The ack was triggered by the newest frame it covers
+            if let Some(f) = last {
+                let rtt = at.duration_since(f.sent);
+                self.link.on_ack(frames, bytes, rtt, at);
+                self.endpoints[self.current].succeeded();
+                if rejected.is_none() {
+                    info!("uplink: frame {} acknowledged (RTT: {}ms)", acked, rtt.as_millis());
+                }
+            }
+            wait = Duration::ZERO;
         }
-        Ok(())
+
+        if let Some(f) = s.inflight.front() {
+            anyhow::ensure!(f.sent.elapsed() < ACK_TIMEOUT, "no ack for frame {} after {:?}", f.id, ACK_TIMEOUT);
+        }
+        // Anything listed but not in flight still has to go out
+        let unsent = frames.iter().any(|f| !s.inflight.iter().any(|i| i.id == f.id));
+        Ok(s.inflight.is_empty() && !unsent)
     }
 
-    async fn send_blob(&mut self, blob: &[u8]) -> Result<()> {
-        let start = std::time::Instant::now();
+    async fn connect(&self) -> Result<Session> {
+        let ep = &self.endpoints[self.current];
+        let tls = timeout(IO_TIMEOUT, async {
+            let tcp = TcpStream::connect((ep.host.as_str(), ep.port)).await?;
+            tcp.set_nodelay(true)?;
+            let mut tls = ep.tls.connect(ep.server_name.clone(), tcp).await?;
+            tls.write_all(link::HELLO).await?;
+            tls.flush().await?;
+            Ok::<_, std::io::Error>(tls)
+        }).await.context("connect timed out")??;
+
+        let resumed = tls.get_ref().1.handshake_kind() == Some(HandshakeKind::Resumed);
+        info!("uplink: session open to {} ({})", ep.url, if resumed { "resumed" } else { "full handshake" });
+        Ok(Session::new(tls))
+    }
+}
 
-        // endpoint: tls://host:port
-        let ep = self.endpoint.strip_prefix("tls://").context("endpoint must start with tls://")?;
-        let mut parts = ep.split(':');
-        let host = parts.next().context("missing host")?;
-        let port = parts.next().context("missing port")?;
-        let addr = format!("{}:{}", host, port);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scout_crypto::keys::KeyConfig;
+    use scout_crypto::keywrap::KdfParams;
+    use tokio::io::DuplexStream;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("navscout-uplink-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
 
-        let result = async {
-            let tcp = TcpStream::connect(addr).await?;
-            let name = ServerName::try_from(host.to_string())?;
-            let mut tls = self.tls.connect(name, tcp).await?;
+    fn uplink(dir: &Path) -> Uplink {
+        let cfg = KeyConfig {
+            key_path: dir.join("device.key").to_string_lossy().into(),
+            passphrase: String::new().into(),
+            keep_previous: 4,
+            retire_after_days: 30,
+            kdf: KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 },
+        };
+        DeviceKeys::init(&cfg).unwrap();
+        let endpoint = EndpointConfig { url: "tls://127.0.0.1:1".into(), server_name: None, pins: vec![] };
+        Uplink::new(vec![endpoint], None, dir.join("spool").to_string_lossy().into(), dir.join("spool.seq"), 10,
+                    DeviceKeys::load(&cfg).unwrap(), "drone-1".into(), FrameConfig::default(), HealthConfig::default()).unwrap()
+    }
 
-            // simple framing: u32 length + blob
-            let len = (blob.len() as u32).to_be_bytes();
-            tls.write_all(&len).await?;
-            tls.write_all(blob).await?;
-            tls.flush().await?;
+    /// Attach a session over an in-memory stream; returns the ground's end.
+    fn attach(up: &mut Uplink) -> DuplexStream {
+        let (aircraft, ground) = tokio::io::duplex(1 << 16);
+        up.session = Some(Session::new(aircraft));
+        ground
+    }
 
-            Ok::<(), anyhow::Error>(())
-        }.await;
-
-        // Update link health based on result
-        match result {
-            Ok(()) => {
-                let rtt = start.elapsed().as_millis() as u32;
-                self.health.rtt_ms = Some(rtt);
-                self.health.consecutive_failures = 0;
-                // Gradually improve quality on success
-                self.health.quality = (self.health.quality + 10).min(100);
-                info!("uplink: sent {} bytes (RTT: {}ms, quality: {}%)", blob.len(), rtt, self.health.quality);
-                Ok(())
-            }
-            Err(e) => {
-                self.health.consecutive_failures += 1;
-                // Degrade quality on failure
-                self.health.quality = self.health.quality.saturating_sub(20);
-                warn!("uplink: send failed (failures: {}, quality: {}%): {:#}",
-                      self.health.consecutive_failures, self.health.quality, e);
-                Err(e)
-            }
+    /// Read `n` frames off the ground's end, returning their ids.
+    async fn receive(ground: &mut DuplexStream, n: usize) -> Vec<u64> {
+        let mut ids = Vec::new();
+        for _ in 0..n {
+            let mut hdr = [0u8; link::FRAME_HEADER_LEN];
+            ground.read_exact(&mut hdr).await.unwrap();
+            let (id, len) = link::parse_frame_header(&hdr).unwrap();
+            let mut blob = vec![0u8; len as usize];
+            ground.read_exact(&mut blob).await.unwrap();
+            assert_eq!(blob, format!("blob {}", id).as_bytes());
+            ids.push(id);
         }
+        ids
+    }
+
+    async fn pump(up: &mut Uplink, wait: Duration) -> Result<bool> {
+        let frames = up.spool_files().await?;
+        up.pump_session(&frames, wait).await
+    }
+
+    #[tokio::test]
+    async fn window_cumulative_acks_and_resend() {
+        let dir = scratch("pump");
+        let mut up = uplink(&dir);
+        let spool = PathBuf::from(&up.spool_dir);
+        std::fs::create_dir_all(&spool).unwrap();
+        let ids: Vec<u64> = (1000..1020)
+            .map(|id| spool::write_record(&spool, id, Priority::Routine, &spool::encode_record(format!("blob {}", id).as_bytes())).unwrap())
+            .collect();
+
+        // At most MAX_INFLIGHT frames go out unacked
+        let mut ground = attach(&mut up);
+        assert!(!pump(&mut up, Duration::ZERO).await.unwrap());
+        assert_eq!(receive(&mut ground, MAX_INFLIGHT).await, ids[..MAX_INFLIGHT]);
+        assert!(!pump(&mut up, Duration::ZERO).await.unwrap());
+
+        // One ack covers every frame sent before it and frees the window
+        ground.write_all(&link::ack(ids[3])).await.unwrap();
+        assert!(!pump(&mut up, Duration::from_secs(5)).await.unwrap());
+        assert_eq!(up.session.as_ref().unwrap().inflight.len(), MAX_INFLIGHT - 4);
+        let left: Vec<_> = spool::list(&spool).unwrap().iter().map(|f| f.id).collect();
+        assert_eq!(left, ids[4..]);
+        assert!(!pump(&mut up, Duration::ZERO).await.unwrap());
+        assert_eq!(receive(&mut ground, 4).await, ids[MAX_INFLIGHT..]);
+
+        // A dropped session loses what was in flight; the next one sends it again
+        drop(ground);
+        assert!(pump(&mut up, Duration::from_secs(5)).await.is_err());
+        up.session = None;
+        let mut ground = attach(&mut up);
+        assert!(!pump(&mut up, Duration::ZERO).await.unwrap());
+        assert_eq!(receive(&mut ground, MAX_INFLIGHT).await, ids[4..]);
+        ground.write_all(&link::ack(ids[19])).await.unwrap();
+        pump(&mut up, Duration::from_secs(5)).await.unwrap();
+        assert!(spool::list(&spool).unwrap().is_empty());
+        assert!(pump(&mut up, Duration::ZERO).await.unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
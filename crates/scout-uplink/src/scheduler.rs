@@ -0,0 +1,298 @@
+// Uplink scheduler: owns the `Uplink` in its own task so the navigation loop
+// never waits on the network. The loop submits payloads without blocking and
+// routine positions are sampled at `Uplink::recommended_interval` as they are
+// produced; the scheduler flushes on a tick (honouring the reconnect backoff)
+// and flushes critical events immediately, bypassing both the sampling and
+// the backoff. A critical event that arrives while a flush is stuck on the
+// network abandons that flush so the event reaches the spool without waiting
+// out the I/O timeout. Payloads queued during a flush are capped at
+// BACKLOG_MAX, dropping routine ones first; drops count as evictions.
+
+use scout_proto::telemetry::{Payload, Priority};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::health::{self, LinkHealth};
+use crate::Uplink;
+
+const QUEUE_LEN: usize = 1024;
+/// Payloads held back while a flush runs.
+const BACKLOG_MAX: usize = QUEUE_LEN;
+const FLUSH_TICK: Duration = Duration::from_millis(250);
+const SPOOL_STATS_INTERVAL: Duration = Duration::from_secs(60);
+/// How long a flush may keep a critical event waiting before it is abandoned.
+const FLUSH_PATIENCE: Duration = Duration::from_secs(1);
+
+/// What the scheduler publishes after each flush.
+#[derive(Clone)]
+struct LinkState {
+    health: LinkHealth,
+    interval: Duration,
+}
+
+/// The navigation loop's side of the scheduler.
+pub struct UplinkHandle {
+    tx: mpsc::Sender<Payload>,
+    state: watch::Receiver<LinkState>,
+    task: JoinHandle<()>,
+    last_routine: Option<Instant>,
+    sampled_out: u64,
+    dropped: u64,
+}
+
+impl UplinkHandle {
+    /// Queue a payload; never blocks. Routine payloads inside the current
+    /// sampling interval are skipped; anything is dropped if the scheduler is
+    /// far behind.
+    pub fn submit(&mut self, payload: Payload) {
+        if payload.priority() == Priority::Routine {
+            let interval = self.state.borrow().interval;
+            if self.last_routine.map(|t| t.elapsed() < interval).unwrap_or(false) {
+                self.sampled_out += 1;
+                return;
+            }
+            self.last_routine = Some(Instant::now());
+        }
+        if let Err(e) = self.tx.try_send(payload) {
+            self.dropped += 1;
+            if self.dropped.is_power_of_two() {
+                warn!("uplink: queue full, {} payloads dropped so far: {}", self.dropped, e);
+            }
+        }
+    }
+
+    /// Link health as of the scheduler's last flush.
+    pub fn link_health(&self) -> LinkHealth {
+        self.state.borrow().health.clone()
+    }
+
+    /// Seal everything queued and wait for the spool to be acknowledged
+    /// (bounded by the drain timeout given to `spawn`).
+    pub async fn shutdown(self) {
+        if self.sampled_out > 0 {
+            info!("uplink: {} routine payloads skipped by rate sampling", self.sampled_out);
+        }
+        drop(self.tx);
+        if let Err(e) = self.task.await {
+            warn!("uplink: scheduler task failed: {}", e);
+        }
+    }
+}
+
+impl Uplink {
+    /// Move the uplink into a scheduler task. `drain` bounds how long shutdown
+    /// waits for the ground station to ack the spool.
+    pub fn spawn(self, drain: Duration) -> UplinkHandle {
+        let (tx, rx) = mpsc::channel(QUEUE_LEN);
+        let (state_tx, state) = watch::channel(LinkState { health: self.link_health().clone(), interval: self.recommended_interval() });
+        let task = tokio::spawn(schedule(self, rx, state_tx, drain));
+        UplinkHandle { tx, state, task, last_routine: None, sampled_out: 0, dropped: 0 }
+    }
+}
+
+async fn schedule(mut up: Uplink, mut rx: mpsc::Receiver<Payload>, state_tx: watch::Sender<LinkState>, drain: Duration) {
+    let mut tick = tokio::time::interval(FLUSH_TICK);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut last_stats: Option<Instant> = None;
+    let mut backlog: Vec<Payload> = Vec::new();
+    let mut open = true;
+    let modem = up.modem_poll.map(health::spawn_modem_poll);
+
+    while open {
+        tokio::select! {
+            p = rx.recv() => match p {
+                Some(p) => {
+                    if accept(&mut up, p).await {
+                        tick.reset_immediately();
+                    }
+                }
+                None => open = false,
+            },
+            _ = tick.tick() => {
+                if let Some((_, m)) = &modem {
+                    up.link.set_modem(m.borrow().clone());
+                }
+                if last_stats.map(|t| t.elapsed() >= SPOOL_STATS_INTERVAL).unwrap_or(true) {
+                    last_stats = Some(Instant::now());
+                    match up.spool_stats().await {
+                        Ok(st) => {
+                            if let Err(e) = up.send(Payload::SpoolStats(st)).await { warn!("uplink send failed: {:#}", e); }
+                        }
+                        Err(e) => warn!("spool stats failed: {:#}", e),
+                    }
+                }
+
+                // Keep draining the queue while a flush waits on the network
+                let mut abandoned = false;
+                let mut dropped = 0;
+                {
+                    let deadline = tokio::time::Instant::now() + FLUSH_PATIENCE;
+                    let mut critical_waiting = false;
+                    let flush = up.flush_spool();
+                    tokio::pin!(flush);
+                    loop {
+                        tokio::select! {
+                            r = &mut flush => {
+                                if let Err(e) = r { warn!("uplink flush failed: {:#}", e); }
+                                break;
+                            }
+                            p = rx.recv(), if open => match p {
+                                Some(p) => {
+                                    critical_waiting |= p.priority() == Priority::Critical;
+                                    if push_backlog(&mut backlog, p) {
+                                        dropped += 1;
+                                    }
+                                }
+                                None => open = false,
+                            },
+                            _ = tokio::time::sleep_until(deadline), if critical_waiting => {
+                                abandoned = true;
+                                break;
+                            }
+                        }
+                    }
+                }
+                if dropped > 0 {
+                    warn!("uplink: flush backlog full, {} payloads dropped", dropped);
+                    up.evicted += dropped;
+                }
+                if abandoned {
+                    warn!("uplink: flush stalled with a critical event queued; dropping the session");
+                    up.abandon_session();
+                }
+                state_tx.send_replace(LinkState { health: up.link_health().clone(), interval: up.recommended_interval() });
+
+                let mut urgent = false;
+                for p in std::mem::take(&mut backlog) {
+                    urgent |= accept(&mut up, p).await;
+                }
+                if urgent {
+                    tick.reset_immediately();
+                }
+            }
+        }
+    }
+
+    if let Some((task, _)) = modem {
+        task.abort();
+    }
+    info!("run: flushing remaining telemetry spool...");
+    if let Err(e) = up.drain(drain).await {
+        warn!("final spool flush failed (kept for next start): {:#}", e);
+    }
+}
+
+/// Hold `p` back until the flush ends. When the backlog is full, the oldest
+/// payload of the lowest class goes (`p` itself if it is lower still).
+/// Returns true if a payload was dropped.
+fn push_backlog(backlog: &mut Vec<Payload>, p: Payload) -> bool {
+    if backlog.len() < BACKLOG_MAX {
+        backlog.push(p);
+        return false;
+    }
+    let lowest = backlog.iter().enumerate().min_by_key(|(i, q)| (q.priority(), *i)).map(|(i, _)| i);
+    match lowest {
+        Some(i) if backlog[i].priority() <= p.priority() => {
+            backlog.remove(i);
+            backlog.push(p);
+        }
+        _ => {}
+    }
+    true
+}
+
+/// Hand one payload to the uplink. Returns true if it needs an immediate flush.
+async fn accept(up: &mut Uplink, p: Payload) -> bool {
+    let prio = p.priority();
+    if let Err(e) = up.send(p).await {
+        warn!("uplink send failed: {:#}", e);
+    }
+    if prio == Priority::Critical {
+        up.expedite();
+        return true;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scout_proto::telemetry::{Failsafe, FailsafeTriggered, MissionState, Position, Shutdown};
+
+    fn position() -> Payload {
+        Payload::Position(Position {
+            lat: 48.0, lon: 2.0, alt_m: None, sats: 12, hdop: 0.9, state: MissionState::TransitToZone,
+            dist_home_m: 0.0, cross_track_m: None, route_segment: None, route_progress: None,
+            zone_edge_m: None, time_in_state_s: 0, vision: None,
+        })
+    }
+
+    fn failsafe() -> Payload {
+        Payload::FailsafeTriggered(FailsafeTriggered { failsafe: Failsafe::Battery, detail: "21% <= 22%".into() })
+    }
+
+    fn health(quality: u8, consecutive_failures: u32) -> LinkHealth {
+        LinkHealth { quality, consecutive_failures, ..Default::default() }
+    }
+
+    /// A handle whose scheduler only sees what reaches the queue.
+    fn handle(interval: Duration) -> (UplinkHandle, mpsc::Receiver<Payload>) {
+        let (tx, rx) = mpsc::channel(QUEUE_LEN);
+        let (_, state) = watch::channel(LinkState { health: LinkHealth::default(), interval });
+        let task = tokio::spawn(async {});
+        (UplinkHandle { tx, state, task, last_routine: None, sampled_out: 0, dropped: 0 }, rx)
+    }
+
+    #[test]
+    fn sampling_follows_link_status() {
+        let base = Duration::from_millis(500);
+        assert_eq!(health(100, 0).sample_interval(base), base);
+        assert_eq!(health(80, 0).sample_interval(base), base);
+        assert_eq!(health(79, 0).sample_interval(base), base * 2);
+        assert_eq!(health(20, 0).sample_interval(base), base * 4);
+        assert_eq!(health(0, 0).sample_interval(base), base * 10);
+        assert_eq!(health(100, 1).sample_interval(base), base * 2);
+        assert_eq!(health(0, 3).sample_interval(base), base * 8);
+        assert_eq!(health(0, 9).sample_interval(base), base * 16);
+    }
+
+    #[tokio::test]
+    async fn critical_payloads_skip_sampling() {
+        let (mut h, mut rx) = handle(Duration::from_secs(3600));
+        h.submit(position());
+        h.submit(position());
+        h.submit(failsafe());
+        h.submit(failsafe());
+        h.submit(Payload::Shutdown(Shutdown { reason: "test".into() }));
+        h.submit(position());
+        assert_eq!(h.sampled_out, 2);
+        let got: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).map(|p| p.priority()).collect();
+        assert_eq!(got, [Priority::Routine, Priority::Critical, Priority::Critical, Priority::Health]);
+
+        // Every position goes out on a link that allows it
+        let (mut h, mut rx) = handle(Duration::ZERO);
+        h.submit(position());
+        h.submit(position());
+        assert_eq!(h.sampled_out, 0);
+        assert_eq!(std::iter::from_fn(|| rx.try_recv().ok()).count(), 2);
+    }
+
+    #[test]
+    fn full_backlog_drops_routine_first() {
+        let mut backlog = Vec::new();
+        for i in 0..BACKLOG_MAX {
+            assert!(!push_backlog(&mut backlog, if i % 2 == 0 { failsafe() } else { position() }));
+        }
+        // Each new payload displaces the oldest routine one while any is left
+        for _ in 0..BACKLOG_MAX / 2 {
+            assert!(push_backlog(&mut backlog, failsafe()));
+        }
+        assert_eq!(backlog.len(), BACKLOG_MAX);
+        assert!(backlog.iter().all(|p| p.priority() == Priority::Critical));
+        // Nothing lower left: a routine payload is the one dropped
+        assert!(push_backlog(&mut backlog, position()));
+        assert!(backlog.iter().all(|p| p.priority() == Priority::Critical));
+    }
+}
@@ -0,0 +1,377 @@
+// Spool layout, crash recovery and offline access for post-flight review of
+// what an aircraft could not deliver.
+//
+// Each `<id>-<priority>.bin` file is one record: RECORD_MAGIC | blake3(blob) |
+// sealed batch of a single priority class. The id is the write time in Unix
+// nanoseconds, kept above the newest id already spooled (the clock can step
+// back), and doubles as the uplink frame id; order is taken from the names,
+// never from directory listing order. Records are written to a temp file,
+// fsynced and linked into place without replacing an existing record, so a
+// power cut leaves either the whole record or a stray `.tmp` that `recover`
+// deletes.
+// Records that fail their checksum are moved to `quarantine/`.
+// Files named `<id>.bin` without the header (older builds) are routine; only
+// those may lack the header, anything else without it is quarantined.
+// The next blob sequence number of each priority class is kept in a file
+// outside the spool (`uplink.sequence_path`), written ahead of use so a number
+// is never sealed twice (see `aead::seal_bound`): clearing the spool must not
+// rewind it, or the ground station would drop new frames as replays. Older
+// builds kept it in the spool as `sequence`.
+
+use anyhow::{Context, Result};
+use scout_crypto::{aead, keys::DeviceKeys};
+use scout_proto::{codec, link, telemetry::{Envelope, Priority}};
+use std::cmp::Reverse;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+pub const RECORD_MAGIC: &[u8; 4] = b"NSP1";
+const RECORD_HEADER_LEN: usize = 4 + 32;
+pub const QUARANTINE_DIR: &str = "quarantine";
+/// Sequence file inside the spool, from older builds.
+pub const LEGACY_SEQUENCE_FILE: &str = "sequence";
+
+#[derive(Debug, Clone)]
+pub struct SpoolFile {
+    pub id: u64,
+    pub priority: Priority,
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+impl SpoolFile {
+    pub fn written_unix_ms(&self) -> i64 {
+        (self.id / 1_000_000) as i64
+    }
+}
+
+pub fn file_name(id: u64, priority: Priority) -> String {
+    format!("{}-{}.bin", id, priority.name())
+}
+
+/// Frame id and priority of a spool file, `None` if it is not a spool file.
+pub fn parse_name(path: &Path) -> Option<(u64, Priority)> {
+    if path.extension()? != "bin" {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    match stem.split_once('-') {
+        Some((id, p)) => Some((id.parse().ok()?, Priority::parse(p)?)),
+        None => Some((stem.parse().ok()?, Priority::Routine)),
+    }
+}
+
+/// Highest priority first, oldest first within a class.
+pub fn sort_for_flush(files: &mut [SpoolFile]) {
+    files.sort_by_key(|f| (Reverse(f.priority), f.id));
+}
+
+/// Lowest priority first, oldest first within a class.
+pub fn sort_for_eviction(files: &mut [SpoolFile]) {
+    files.sort_by_key(|f| (f.priority, f.id));
+}
+
+pub fn encode_record(blob: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(RECORD_HEADER_LEN + blob.len());
+    out.extend_from_slice(RECORD_MAGIC);
+    out.extend_from_slice(blake3::hash(blob).as_bytes());
+    out.extend_from_slice(blob);
+    out
+}
+
+/// Whether `path` is named `<id>.bin`, as records from builds before the
+/// record header and priority classes were.
+pub fn is_legacy_name(path: &Path) -> bool {
+    path.file_stem().and_then(|s| s.to_str()).map(|s| !s.contains('-')).unwrap_or(false)
+}
+
+/// The sealed blob of the record read from `path`, after checking its checksum.
+pub fn decode_record<'a>(path: &Path, record: &'a [u8]) -> Result<&'a [u8]> {
+    if !record.starts_with(RECORD_MAGIC) {
+        anyhow::ensure!(is_legacy_name(path), "record header missing");
+        return Ok(record); // headerless record from an older build
+    }
+    anyhow::ensure!(record.len() >= RECORD_HEADER_LEN, "truncated record header");
+    let (hdr, blob) = record.split_at(RECORD_HEADER_LEN);
+    anyhow::ensure!(blake3::hash(blob).as_bytes() == &hdr[4..], "record checksum mismatch");
+    Ok(blob)
+}
+
+/// Write `data` as `dir/name` so that it is either complete or absent after a crash.
+pub fn write_atomic(dir: &Path, name: &str, data: &[u8]) -> Result<()> {
+    let tmp = dir.join(format!(".{}.tmp", name));
+    let mut f = fs::File::create(&tmp).with_context(|| format!("create {:?}", tmp))?;
+    f.write_all(data)?;
+    f.sync_all()?;
+    drop(f);
+    fs::rename(&tmp, dir.join(name))?;
+    // Persist the rename itself
+    fs::File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+/// Write `record` under the first id from `min_id` on that no record in any
+/// class has (ids are frame ids), never replacing a record. Returns the id used.
+pub fn write_record(dir: &Path, min_id: u64, priority: Priority, record: &[u8]) -> Result<u64> {
+    let tmp = dir.join(format!(".{}.tmp", file_name(min_id, priority)));
+    let mut f = fs::File::create(&tmp).with_context(|| format!("create {:?}", tmp))?;
+    f.write_all(record)?;
+    f.sync_all()?;
+    drop(f);
+    let mut id = min_id;
+    let taken = |id: u64| {
+        dir.join(format!("{}.bin", id)).exists() || Priority::ALL.iter().any(|p| dir.join(file_name(id, *p)).exists())
+    };
+    let linked = loop {
+        if taken(id) {
+            id += 1;
+            continue;
+        }
+        match fs::hard_link(&tmp, dir.join(file_name(id, priority))) {
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => id += 1,
+            r => break r,
+        }
+    };
+    fs::remove_file(&tmp)?;
+    linked.with_context(|| format!("link {:?}", tmp))?;
+    fs::File::open(dir)?.sync_all()?;
+    Ok(id)
+}
+
+/// Next blob sequence number of each priority class, indexed by `Priority as usize`,
+/// from the file at `path`. A missing file starts at zero.
+pub fn load_sequence(path: &Path) -> Result<[u64; 3]> {
+    let s = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok([0; 3]),
+        Err(e) => return Err(anyhow::Error::new(e).context(format!("read {:?}", path))),
+    };
+    let seq = s.split_whitespace().map(|n| n.parse::<u64>()).collect::<Result<Vec<_>, _>>()
+        .ok().and_then(|v| <[u64; 3]>::try_from(v).ok());
+    seq.with_context(|| format!("{:?} is corrupt", path))
+}
+
+pub fn store_sequence(path: &Path, seq: &[u64; 3]) -> Result<()> {
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name().and_then(|n| n.to_str()).with_context(|| format!("{:?} is not a file path", path))?;
+    fs::create_dir_all(dir)?;
+    write_atomic(dir, name, format!("{} {} {}\n", seq[0], seq[1], seq[2]).as_bytes())
+}
+
+/// Sequence numbers from `path`, taking over any left in `spool_dir` by older
+/// builds (the larger number of each class wins).
+pub fn migrate_sequence(path: &Path, spool_dir: &Path) -> Result<[u64; 3]> {
+    let mut seq = load_sequence(path)?;
+    let legacy = spool_dir.join(LEGACY_SEQUENCE_FILE);
+    if !legacy.exists() {
+        return Ok(seq);
+    }
+    for (n, old) in seq.iter_mut().zip(load_sequence(&legacy)?) {
+        *n = (*n).max(old);
+    }
+    store_sequence(path, &seq)?;
+    fs::remove_file(&legacy).with_context(|| format!("remove {:?}", legacy))?;
+    Ok(seq)
+}
+
+/// Move a bad record out of the queue, keeping it for inspection.
+pub fn quarantine(path: &Path) -> Result<PathBuf> {
+    let dir = path.parent().context("spool file without a directory")?.join(QUARANTINE_DIR);
+    fs::create_dir_all(&dir)?;
+    let dest = dir.join(path.file_name().context("spool file without a name")?);
+    fs::rename(path, &dest)?;
+    Ok(dest)
+}
+
+#[derive(Debug, Default)]
+pub struct Recovery {
+    pub removed_tmp: usize,
+    pub quarantined: usize,
+}
+
+/// Startup check of a spool directory: drop half-written temp files and
+/// quarantine records whose checksum does not match.
+pub fn recover(dir: &Path) -> Result<Recovery> {
+    let mut r = Recovery::default();
+    if !dir.exists() {
+        return Ok(r);
+    }
+    for ent in fs::read_dir(dir).with_context(|| format!("read {:?}", dir))? {
+        let path = ent?.path();
+        if !path.is_file() { continue; }
+        if path.extension().map(|e| e == "tmp").unwrap_or(false) {
+            fs::remove_file(&path)?;
+            r.removed_tmp += 1;
+            continue;
+        }
+        if parse_name(&path).is_none() { continue; }
+        if let Err(e) = fs::read(&path).map_err(anyhow::Error::from).and_then(|rec| decode_record(&path, &rec).map(|_| ())) {
+            let dest = quarantine(&path)?;
+            warn!("spool: quarantined {:?}: {:#}", dest, e);
+            r.quarantined += 1;
+        }
+    }
+    Ok(r)
+}
+
+/// Number of records in `quarantine/`.
+pub fn quarantined(dir: &Path) -> usize {
+    fs::read_dir(dir.join(QUARANTINE_DIR)).map(|d| d.count()).unwrap_or(0)
+}
+
+/// Spool files, oldest first. A missing directory is an empty spool.
+pub fn list(dir: &Path) -> Result<Vec<SpoolFile>> {
+    let mut files = Vec::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    for ent in fs::read_dir(dir).with_context(|| format!("read {:?}", dir))? {
+        let ent = ent?;
+        let meta = ent.metadata()?;
+        if !meta.is_file() { continue; }
+        if let Some((id, priority)) = parse_name(&ent.path()) {
+            files.push(SpoolFile { id, priority, path: ent.path(), bytes: meta.len() });
+        }
+    }
+    files.sort_by_key(|f| f.id);
+    Ok(files)
+}
+
+/// Evict lowest-priority, oldest records first once the spool exceeds
+/// `max_bytes`, down to 90% of it. Returns how many records were evicted.
+pub fn enforce_limit(dir: &Path, max_bytes: u64) -> Result<u64> {
+    let mut files = list(dir)?;
+    let mut total_size: u64 = files.iter().map(|f| f.bytes).sum();
+    if total_size <= max_bytes {
+        return Ok(0);
+    }
+    sort_for_eviction(&mut files);
+
+    // Delete until under limit (with 10% headroom)
+    let target = (max_bytes as f64 * 0.9) as u64;
+    let mut evicted = 0;
+    for f in files {
+        if total_size <= target {
+            break;
+        }
+        if let Err(e) = fs::remove_file(&f.path) {
+            warn!("spool eviction failed for {:?}: {}", f.path, e);
+        } else {
+            if f.priority == Priority::Critical {
+                warn!("spool full of critical data, evicted {:?} ({}B)", f.path, f.bytes);
+            } else {
+                info!("spool evicted {} file: {:?} ({}B)", f.priority.name(), f.path, f.bytes);
+            }
+            total_size = total_size.saturating_sub(f.bytes);
+            evicted += 1;
+        }
+    }
+    Ok(evicted)
+}
+
+/// Check, decrypt and decode the spool record read from `path`.
+pub fn open(keys: &DeviceKeys, path: &Path, record: &[u8]) -> Result<Vec<Envelope>> {
+    let blob = decode_record(path, record)?;
+    let frame = if aead::is_bound(blob) {
+        keys.open_bound(link::TELEMETRY_AAD, blob).map(|(_, frame)| frame)
+    } else {
+        keys.open(link::TELEMETRY_AAD, blob) // sealed by an older build
+    };
+    codec::decode(&frame.context("authentication failed (wrong key or tampered)")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("navscout-spool-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn write_record_never_replaces() {
+        let dir = scratch("no-replace");
+        assert_eq!(write_record(&dir, 100, Priority::Routine, b"first").unwrap(), 100);
+        // Same id again, e.g. after the clock stepped back
+        assert_eq!(write_record(&dir, 100, Priority::Routine, b"second").unwrap(), 101);
+        // Ids are unique across classes and legacy names
+        assert_eq!(write_record(&dir, 100, Priority::Critical, b"third").unwrap(), 102);
+        fs::write(dir.join("103.bin"), b"legacy").unwrap();
+        assert_eq!(write_record(&dir, 103, Priority::Health, b"fourth").unwrap(), 104);
+        assert_eq!(fs::read(dir.join(file_name(100, Priority::Routine))).unwrap(), b"first");
+        assert_eq!(fs::read(dir.join(file_name(101, Priority::Routine))).unwrap(), b"second");
+        let ids: Vec<_> = list(&dir).unwrap().iter().map(|f| (f.id, f.priority)).collect();
+        assert_eq!(ids, [(100, Priority::Routine), (101, Priority::Routine), (102, Priority::Critical), (103, Priority::Routine), (104, Priority::Health)]);
+        assert!(!fs::read_dir(&dir).unwrap().any(|e| e.unwrap().path().extension().unwrap() == "tmp"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn eviction_spares_critical_records() {
+        let dir = scratch("evict");
+        let classes = [Priority::Critical, Priority::Routine, Priority::Health, Priority::Routine, Priority::Critical, Priority::Health, Priority::Routine, Priority::Critical];
+        for (i, prio) in classes.into_iter().enumerate() {
+            fs::write(dir.join(file_name(100 + i as u64, prio)), [0u8; 100]).unwrap();
+        }
+        let left = |dir: &Path| list(dir).unwrap().iter().map(|f| (f.id, f.priority)).collect::<Vec<_>>();
+        assert_eq!(enforce_limit(&dir, 800).unwrap(), 0);
+
+        // 800 bytes over a 600-byte limit: down to 540, routine oldest first, then health
+        assert_eq!(enforce_limit(&dir, 600).unwrap(), 3);
+        assert_eq!(left(&dir), [(100, Priority::Critical), (102, Priority::Health), (104, Priority::Critical), (105, Priority::Health), (107, Priority::Critical)]);
+        assert_eq!(enforce_limit(&dir, 400).unwrap(), 2);
+        assert_eq!(left(&dir), [(100, Priority::Critical), (104, Priority::Critical), (107, Priority::Critical)]);
+        // Only critical records left: the oldest go
+        assert_eq!(enforce_limit(&dir, 200).unwrap(), 2);
+        assert_eq!(left(&dir), [(107, Priority::Critical)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sequence_survives_clearing_the_spool() {
+        let dir = scratch("sequence");
+        let spool = dir.join("spool");
+        let seq = dir.join("spool.seq");
+        fs::create_dir_all(&spool).unwrap();
+        fs::write(spool.join(LEGACY_SEQUENCE_FILE), "7 3 40\n").unwrap();
+        store_sequence(&seq, &[9, 1, 2]).unwrap();
+        assert_eq!(migrate_sequence(&seq, &spool).unwrap(), [9, 3, 40]);
+        assert!(!spool.join(LEGACY_SEQUENCE_FILE).exists());
+        fs::remove_dir_all(&spool).unwrap();
+        assert_eq!(migrate_sequence(&seq, &spool).unwrap(), [9, 3, 40]);
+        fs::write(&seq, "9 3").unwrap();
+        assert!(load_sequence(&seq).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn headerless_records_only_under_legacy_names() {
+        let sealed = b"sealed blob from an older build";
+        assert_eq!(decode_record(Path::new("spool/1700000000000000000.bin"), sealed).unwrap(), sealed);
+        let err = decode_record(Path::new("spool/1700000000000000000-critical.bin"), sealed).unwrap_err();
+        assert!(err.to_string().contains("header missing"));
+        let record = encode_record(sealed);
+        assert_eq!(decode_record(Path::new("spool/1700000000000000000-critical.bin"), &record).unwrap(), sealed);
+        assert!(decode_record(Path::new("spool/1700000000000000000-critical.bin"), &record[..20]).is_err());
+    }
+
+    #[test]
+    fn recover_quarantines_headerless_new_records() {
+        let dir = scratch("recover");
+        fs::write(dir.join("100.bin"), b"legacy").unwrap();
+        fs::write(dir.join(file_name(101, Priority::Critical)), b"torn").unwrap();
+        fs::write(dir.join(file_name(102, Priority::Routine)), encode_record(b"ok")).unwrap();
+        fs::write(dir.join(".103-routine.bin.tmp"), b"half").unwrap();
+        let r = recover(&dir).unwrap();
+        assert_eq!((r.removed_tmp, r.quarantined), (1, 1));
+        assert!(dir.join(QUARANTINE_DIR).join(file_name(101, Priority::Critical)).exists());
+        let ids: Vec<_> = list(&dir).unwrap().iter().map(|f| f.id).collect();
+        assert_eq!(ids, [100, 102]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -79,7 +79,7 @@ pub fn postprocess_ultralytics(
     for i in 0..num_preds {
         let base = i * stride;
         if base + stride > raw.len() { break; }
-        let cx = raw[base + 0];
+        let cx = raw[base];
         let cy = raw[base + 1];
         let w = raw[base + 2];
         let h = raw[base + 3];
@@ -1,3 +1,4 @@
+#[allow(clippy::too_many_arguments)]
 pub fn iou(cx1: f32, cy1: f32, w1: f32, h1: f32, cx2: f32, cy2: f32, w2: f32, h2: f32) -> f32 {
     let (x1a, y1a, x1b, y1b) = (cx1 - w1/2.0, cy1 - h1/2.0, cx1 + w1/2.0, cy1 + h1/2.0);
     let (x2a, y2a, x2b, y2b) = (cx2 - w2/2.0, cy2 - h2/2.0, cx2 + w2/2.0, cy2 + h2/2.0);
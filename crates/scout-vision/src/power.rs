@@ -59,7 +59,7 @@ impl PowerCtl {
             PowerMode::Track => self.cfg.track_infer_every_n.max(1),
             PowerMode::Burst => self.cfg.burst_infer_every_n.max(1),
         };
-        (self.frame_counter % n as u64) == 0
+        self.frame_counter.is_multiple_of(n as u64)
     }
 
     pub fn current_mode(&self) -> PowerMode { self.mode }